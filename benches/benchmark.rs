@@ -1,8 +1,7 @@
 use criterion::{Criterion, criterion_group, criterion_main};
 use order_book_engine::instrument::BTC_USD;
 use order_book_engine::orderbook::OrderBook;
-use order_book_engine::orders::{Order, OrderType, Side};
-use std::time::SystemTime;
+use order_book_engine::orders::{Order, Side};
 
 fn setup_order_book(depth: u64, orders_per_level: u64) -> OrderBook {
     let mut ob = OrderBook::new();
@@ -10,24 +9,10 @@ fn setup_order_book(depth: u64, orders_per_level: u64) -> OrderBook {
     for price in 1..=depth {
         for i in 0..orders_per_level {
             // Sell side
-            ob.add_order(Order {
-                id: (price as u128) * 1_000u128 + (i as u128),
-                side: Side::Sell,
-                order_type: OrderType::Limit,
-                price: Some(price),
-                quantity: 1,
-                timestamp: SystemTime::now(),
-                pair: BTC_USD,
-            });
-            ob.add_order(Order {
-                id: ((depth as u128 + price as u128) * 1_000u128) + (i as u128),
-                side: Side::Buy,
-                order_type: OrderType::Limit,
-                price: Some(price),
-                quantity: 1,
-                timestamp: SystemTime::now(),
-                pair: BTC_USD,
-            });
+            let id = (price as u128) * 1_000u128 + (i as u128);
+            ob.add_order(Order::limit(id, Side::Sell, price, 1, BTC_USD));
+            let id = ((depth as u128 + price as u128) * 1_000u128) + (i as u128);
+            ob.add_order(Order::limit(id, Side::Buy, price, 1, BTC_USD));
         }
     }
     ob
@@ -39,33 +24,44 @@ fn bench_match_order(c: &mut Criterion) {
     let mut ob = setup_order_book(depth, orders_per_level);
     c.bench_function("match 1 market order", |b| {
         b.iter(|| {
-            let market_buy = Order {
-                id: 0,
-                side: Side::Buy,
-                order_type: OrderType::Market,
-                price: None,
-                quantity: depth * orders_per_level / 2,
-                timestamp: SystemTime::now(),
-                pair: BTC_USD,
-            };
-            ob.match_order(market_buy);
+            let market_buy = Order::market(0, Side::Buy, depth * orders_per_level / 2, BTC_USD);
+            let _ = ob.match_order(market_buy);
         })
     });
 
     c.bench_function("match 1 limit crossing order", |b| {
         b.iter(|| {
-            let limit_sell = Order {
-                id: 1,
-                side: Side::Sell,
-                order_type: OrderType::Limit,
-                price: Some(depth / 2),
-                quantity: depth * orders_per_level,
-                timestamp: SystemTime::now(),
-                pair: BTC_USD,
-            };
-            ob.match_order(limit_sell)
+            let limit_sell =
+                Order::limit(1, Side::Sell, depth / 2, depth * orders_per_level, BTC_USD);
+            ob.match_order(limit_sell).unwrap()
         })
     });
 }
-criterion_group!(benches, bench_match_order);
+
+// Market makers cancel far more than they trade: pull and re-post quotes
+// spread across every level of a deep book.
+fn bench_cancel_heavy(c: &mut Criterion) {
+    let depth = 1_000;
+    let orders_per_level = 10;
+    let mut ob = OrderBook::new();
+    for price in 1..=depth {
+        for i in 0..orders_per_level {
+            let id = (price as u128) * 1_000 + i as u128;
+            ob.add_order(Order::limit(id, Side::Sell, price, 1, BTC_USD));
+        }
+    }
+    let mut next = 0u64;
+    c.bench_function("cancel and re-add across a deep book", |b| {
+        b.iter(|| {
+            // stride through levels so successive cancels hit different prices
+            next = (next + 7_919) % (depth * orders_per_level);
+            let price = next / orders_per_level + 1;
+            let id = (price as u128) * 1_000 + (next % orders_per_level) as u128;
+            assert!(ob.cancel_order(id));
+            ob.add_order(Order::limit(id, Side::Sell, price, 1, BTC_USD));
+        })
+    });
+}
+
+criterion_group!(benches, bench_match_order, bench_cancel_heavy);
 criterion_main!(benches);
@@ -0,0 +1,310 @@
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use uuid::Uuid;
+
+use crate::{errors::AuthError, orders::AccountId};
+
+/// Header carrying the caller's API key id.
+pub const KEY_HEADER: &str = "x-api-key";
+/// Header carrying the request time, in milliseconds since the Unix epoch.
+pub const TIMESTAMP_HEADER: &str = "x-api-timestamp";
+/// Header carrying a value the caller never reuses with the same key.
+pub const NONCE_HEADER: &str = "x-api-nonce";
+/// Header carrying the hex HMAC-SHA256 of the request, see [`sign`].
+pub const SIGNATURE_HEADER: &str = "x-api-signature";
+
+/// Header carrying the server's admin token on `/admin` requests.
+pub const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+/// How far a request's timestamp may be from the server clock. Nonces are
+/// remembered for this long; older requests are refused by their timestamp.
+pub const MAX_CLOCK_SKEW: Duration = Duration::from_secs(30);
+
+/// Signature over one request: hex HMAC-SHA256, keyed with the API secret, of
+///
+/// ```text
+/// {timestamp}\n{nonce}\n{METHOD}\n{path and query}\n{body}
+/// ```
+pub fn sign(
+    secret: &str,
+    timestamp: u64,
+    nonce: &str,
+    method: &str,
+    path: &str,
+    body: &[u8],
+) -> String {
+    hex::encode(
+        mac(secret, timestamp, nonce, method, path, body)
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+fn mac(
+    secret: &str,
+    timestamp: u64,
+    nonce: &str,
+    method: &str,
+    path: &str,
+    body: &[u8],
+) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{timestamp}\n{nonce}\n{method}\n{path}\n").as_bytes());
+    mac.update(body);
+    mac
+}
+
+/// Whether `given` is the admin token `expected`, compared in constant time.
+pub fn admin_token_matches(expected: &str, given: &str) -> bool {
+    let (expected, given) = (Sha256::digest(expected), Sha256::digest(given));
+    expected
+        .iter()
+        .zip(given.iter())
+        .fold(0, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// An API key as handed out to a client, secret included.
+///
+/// Returned once, when the key is created; the server never shows the
+/// secret again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiCredentials {
+    pub account: AccountId,
+    pub key_id: String,
+    pub secret: String,
+}
+
+impl ApiCredentials {
+    /// Authentication headers for a request sent now with a fresh nonce.
+    pub fn headers(&self, method: &str, path: &str, body: &[u8]) -> [(&'static str, String); 4] {
+        let timestamp = unix_millis(SystemTime::now());
+        let nonce = Uuid::new_v4().simple().to_string();
+        let signature = sign(&self.secret, timestamp, &nonce, method, path, body);
+        [
+            (KEY_HEADER, self.key_id.clone()),
+            (TIMESTAMP_HEADER, timestamp.to_string()),
+            (NONCE_HEADER, nonce),
+            (SIGNATURE_HEADER, signature),
+        ]
+    }
+
+    /// A signed `method` request to `{api_base}{path}` carrying `body` as JSON.
+    pub fn request<T: Serialize>(
+        &self,
+        client: &reqwest::Client,
+        method: reqwest::Method,
+        api_base: &str,
+        path: &str,
+        body: &T,
+    ) -> reqwest::RequestBuilder {
+        let body = serde_json::to_vec(body).expect("request bodies serialize to JSON");
+        let mut request = client
+            .request(method.clone(), format!("{}{}", api_base, path))
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        for (name, value) in self.headers(method.as_str(), path, &body) {
+            request = request.header(name, value);
+        }
+        request.body(body)
+    }
+
+    /// A signed `GET` request to `{api_base}{path}`.
+    pub fn get(
+        &self,
+        client: &reqwest::Client,
+        api_base: &str,
+        path: &str,
+    ) -> reqwest::RequestBuilder {
+        self.without_body(client, reqwest::Method::GET, api_base, path)
+    }
+
+    /// A signed `DELETE` request to `{api_base}{path}`.
+    pub fn delete(
+        &self,
+        client: &reqwest::Client,
+        api_base: &str,
+        path: &str,
+    ) -> reqwest::RequestBuilder {
+        self.without_body(client, reqwest::Method::DELETE, api_base, path)
+    }
+
+    fn without_body(
+        &self,
+        client: &reqwest::Client,
+        method: reqwest::Method,
+        api_base: &str,
+        path: &str,
+    ) -> reqwest::RequestBuilder {
+        let mut request = client.request(method.clone(), format!("{}{}", api_base, path));
+        for (name, value) in self.headers(method.as_str(), path, b"") {
+            request = request.header(name, value);
+        }
+        request
+    }
+}
+
+/// The authentication headers of one request, see [`ApiCredentials::headers`].
+#[derive(Debug, Clone)]
+pub struct SignedRequest<'a> {
+    pub key_id: &'a str,
+    pub timestamp: u64,
+    pub nonce: &'a str,
+    pub signature: &'a str,
+    pub method: &'a str,
+    pub path: &'a str,
+    pub body: &'a [u8],
+}
+
+#[derive(Debug, Clone)]
+struct ApiKey {
+    account: AccountId,
+    secret: String,
+    /// Nonces seen within [`MAX_CLOCK_SKEW`], with their request timestamps.
+    nonces: HashMap<String, u64>,
+}
+
+/// Trading accounts and the API keys that act for them.
+///
+/// An account exists once it has a key; an account may hold several keys.
+#[derive(Debug, Clone, Default)]
+pub struct AccountRegistry {
+    keys: HashMap<String, ApiKey>,
+}
+
+impl AccountRegistry {
+    /// Issues a new random API key for `account`.
+    pub fn create_key(&mut self, account: &str) -> ApiCredentials {
+        let key_id = Uuid::new_v4().simple().to_string();
+        let secret = hex::encode(rand::rng().random::<[u8; 32]>());
+        self.keys.insert(
+            key_id.clone(),
+            ApiKey {
+                account: account.to_string(),
+                secret: secret.clone(),
+                nonces: HashMap::new(),
+            },
+        );
+        ApiCredentials {
+            account: account.to_string(),
+            key_id,
+            secret,
+        }
+    }
+
+    /// Checks `request`'s signature, that it was signed within
+    /// [`MAX_CLOCK_SKEW`] of `now` and that its nonce has not been used before,
+    /// then returns the account the key acts for.
+    pub fn authenticate(
+        &mut self,
+        request: &SignedRequest,
+        now: SystemTime,
+    ) -> Result<AccountId, AuthError> {
+        let key = self
+            .keys
+            .get_mut(request.key_id)
+            .ok_or(AuthError::UnknownKey)?;
+        let now = unix_millis(now);
+        let skew = MAX_CLOCK_SKEW.as_millis() as u64;
+        if request.timestamp.abs_diff(now) > skew {
+            return Err(AuthError::StaleTimestamp);
+        }
+        let signature = hex::decode(request.signature).map_err(|_| AuthError::BadSignature)?;
+        mac(
+            &key.secret,
+            request.timestamp,
+            request.nonce,
+            request.method,
+            request.path,
+            request.body,
+        )
+        .verify_slice(&signature)
+        .map_err(|_| AuthError::BadSignature)?;
+
+        key.nonces.retain(|_, &mut seen| seen.abs_diff(now) <= skew);
+        if key
+            .nonces
+            .insert(request.nonce.to_string(), request.timestamp)
+            .is_some()
+        {
+            return Err(AuthError::ReplayedNonce);
+        }
+        Ok(key.account.clone())
+    }
+}
+
+fn unix_millis(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request<'a>(
+        key: &'a ApiCredentials,
+        headers: &'a [(&'static str, String); 4],
+        body: &'a [u8],
+    ) -> SignedRequest<'a> {
+        SignedRequest {
+            key_id: &key.key_id,
+            timestamp: headers[1].1.parse().unwrap(),
+            nonce: &headers[2].1,
+            signature: &headers[3].1,
+            method: "POST",
+            path: "/orders",
+            body,
+        }
+    }
+
+    #[test]
+    fn test_signed_requests_authenticate_once() {
+        let mut registry = AccountRegistry::default();
+        let key = registry.create_key("alice");
+        let body = br#"{"side":"Buy"}"#;
+        let headers = key.headers("POST", "/orders", body);
+        let now = SystemTime::now();
+
+        let signed = request(&key, &headers, body);
+        assert_eq!(registry.authenticate(&signed, now), Ok("alice".to_string()));
+        assert_eq!(
+            registry.authenticate(&signed, now),
+            Err(AuthError::ReplayedNonce)
+        );
+        assert_eq!(
+            registry.authenticate(&signed, now + MAX_CLOCK_SKEW * 2),
+            Err(AuthError::StaleTimestamp)
+        );
+    }
+
+    #[test]
+    fn test_tampered_or_foreign_requests_are_refused() {
+        let mut registry = AccountRegistry::default();
+        let key = registry.create_key("alice");
+        let headers = key.headers("POST", "/orders", b"{}");
+        let now = SystemTime::now();
+
+        let tampered = request(&key, &headers, b"{\"quantity\":1000}");
+        assert_eq!(
+            registry.authenticate(&tampered, now),
+            Err(AuthError::BadSignature)
+        );
+
+        let foreign = ApiCredentials {
+            key_id: "unknown".into(),
+            ..key.clone()
+        };
+        let headers = foreign.headers("POST", "/orders", b"{}");
+        assert_eq!(
+            registry.authenticate(&request(&foreign, &headers, b"{}"), now),
+            Err(AuthError::UnknownKey)
+        );
+    }
+}
@@ -3,28 +3,48 @@ use serde::{
     de::{self, DeserializeOwned},
 };
 use serde_json::json;
-use std::{collections::HashMap, str::FromStr, time::SystemTime};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{RwLockWriteGuard, broadcast::error::RecvError};
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
 use tracing::{error, info, warn};
 
 use axum::{
     Json, Router,
-    body::Bytes,
+    body::{Body, Bytes},
     extract::{
-        FromRequest, FromRequestParts, Path, Query, Request, State, WebSocketUpgrade,
+        Extension, FromRequest, FromRequestParts, Path, Query, Request, State, WebSocketUpgrade,
         ws::{Message, WebSocket},
     },
-    http::{HeaderName, StatusCode},
-    middleware,
-    response::{AppendHeaders, IntoResponse},
-    routing::{delete, get, post},
+    http::{HeaderMap, HeaderName, StatusCode, request::Parts},
+    middleware::{self, Next},
+    response::{AppendHeaders, IntoResponse, Response},
+    routing::{get, post, put},
 };
 use uuid::Uuid;
 
 use crate::{
-    instrument::Pair,
-    orderbook::BookSnapshot,
-    orders::{Order, OrderType, Side},
+    accounts::{
+        ADMIN_TOKEN_HEADER, ApiCredentials, KEY_HEADER, NONCE_HEADER, SIGNATURE_HEADER,
+        SignedRequest, TIMESTAMP_HEADER, admin_token_matches,
+    },
+    auction::{AuctionQuote, IndicativeAuction},
+    balances::Balance,
+    depth::BookDelta,
+    errors::{AuthError, BalanceError, OrderError, RegistryError, RiskViolation, SpecViolation},
+    fees::FeeReport,
+    instrument::{Asset, InstrumentSpec, Pair},
+    orderbook::{BookSnapshot, OrderBook},
+    orders::{
+        AccountId, ClientOrder, ClientOrderIds, MaxSlippage, Order, OrderRecord, OrderStatus,
+        OrderType, PostOnly, SelfTradePrevention, Side, TimeInForce,
+    },
+    positions::PositionReport,
+    risk::{RiskCheck, RiskLimits, RiskManager},
+    session::{PhaseChange, TradingPhase},
     state::AppState,
     store::StoreError,
     trade::Trade,
@@ -35,26 +55,58 @@ type ApiErr = (StatusCode, Json<serde_json::Value>);
 fn err(status: StatusCode, msg: &str) -> ApiErr {
     (status, Json(json!({ "error": msg })))
 }
+/// Maps engine rejections to statuses: unknown orders are `404`, other
+/// accounts' orders are `403`, orders the market's trading phase refuses are
+/// `409`, anything else is `400`.
+fn order_err(e: &OrderError) -> ApiErr {
+    let status = match e {
+        OrderError::UnknownOrder => StatusCode::NOT_FOUND,
+        OrderError::NotOwner => StatusCode::FORBIDDEN,
+        OrderError::NotAcceptedInPhase(_)
+        | OrderError::CancelNotAcceptedInPhase(_)
+        | OrderError::InvalidPhaseTransition { .. } => StatusCode::CONFLICT,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    err(status, &e.to_string())
+}
+fn auth_err(e: &AuthError) -> ApiErr {
+    err(StatusCode::UNAUTHORIZED, &e.to_string())
+}
+fn spec_err(violation: &SpecViolation) -> ApiErr {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({ "error": violation.to_string(), "violation": violation })),
+    )
+}
+/// Risk rejections carry the violation's `code` and values next to `error`;
+/// breaking the order rate is `429`, any other limit `400`.
+fn risk_err(violation: &RiskViolation) -> ApiErr {
+    let status = match violation {
+        RiskViolation::MaxOrderRate { .. } => StatusCode::TOO_MANY_REQUESTS,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    let mut body = json!(violation);
+    body["error"] = json!(violation.to_string());
+    (status, Json(body))
+}
 
-///Layer/extractor that validates `pair` if it exists in the route params.
+///Layer/extractor that validates `pair` against the instrument registry if it
+/// exists in the route params.
 /// Work for routes with 0, 1, or many path params.
 #[derive(Clone, Copy, Debug)]
 struct PairGuard;
 
-impl<S> FromRequestParts<S> for PairGuard
-where
-    S: Send + Sync,
-{
+impl FromRequestParts<AppState> for PairGuard {
     type Rejection = (StatusCode, Json<serde_json::Value>);
 
     async fn from_request_parts(
         parts: &mut axum::http::request::Parts,
-        state: &S,
+        state: &AppState,
     ) -> Result<Self, Self::Rejection> {
         //Grab all named params as a HashMap
         let Ok(Path(params)) = <axum::extract::Path<
             HashMap<std::string::String, std::string::String>,
-        > as axum::extract::FromRequestParts<S>>::from_request_parts(
+        > as axum::extract::FromRequestParts<AppState>>::from_request_parts(
             parts, state
         )
         .await
@@ -62,8 +114,16 @@ where
             return Ok(PairGuard); //no params -> nothing to validate
         };
         if let Some(pair_str) = params.get("pair") {
-            Pair::from_str(pair_str).map_err(|e| {
-                let supported: Vec<String> = Pair::supported().iter().map(|p| p.code()).collect();
+            let instruments = state.instruments.read().await;
+            let listed = Pair::from_str(pair_str).and_then(|pair| {
+                if instruments.contains(&pair) {
+                    Ok(pair)
+                } else {
+                    Err(format!("unsupported symbol: `{}`", pair_str))
+                }
+            });
+            listed.map_err(|e| {
+                let supported: Vec<String> = instruments.pairs().map(|p| p.code()).collect();
                 (
                     StatusCode::BAD_REQUEST,
                     Json(json!({
@@ -77,13 +137,106 @@ where
     }
 }
 
+/// The account a request was authenticated as, set by [`authenticate`].
+#[derive(Clone, Debug)]
+pub struct Authenticated(pub AccountId);
+
+/// Largest body an authenticated route reads in order to verify its signature.
+const MAX_SIGNED_BODY: usize = 2 * 1024 * 1024;
+
+fn auth_header<'a>(headers: &'a HeaderMap, name: &'static str) -> Result<&'a str, AuthError> {
+    headers
+        .get(name)
+        .ok_or(AuthError::MissingHeader(name))?
+        .to_str()
+        .map_err(|_| AuthError::InvalidHeader(name))
+}
+
+/// Reads the authentication headers of a request whose body is `body`.
+fn signed_request<'a>(parts: &'a Parts, body: &'a [u8]) -> Result<SignedRequest<'a>, AuthError> {
+    let headers = &parts.headers;
+    Ok(SignedRequest {
+        key_id: auth_header(headers, KEY_HEADER)?,
+        timestamp: auth_header(headers, TIMESTAMP_HEADER)?
+            .parse()
+            .map_err(|_| AuthError::InvalidHeader(TIMESTAMP_HEADER))?,
+        nonce: auth_header(headers, NONCE_HEADER)?,
+        signature: auth_header(headers, SIGNATURE_HEADER)?,
+        method: parts.method.as_str(),
+        path: parts
+            .uri
+            .path_and_query()
+            .map_or(parts.uri.path(), |pq| pq.as_str()),
+        body,
+    })
+}
+
+/// Middleware for order entry: verifies the request's API key signature
+/// (see [`crate::accounts::sign`]), refuses stale timestamps and replayed
+/// nonces, and hands the key's account to the handler as [`Authenticated`].
+///
+/// *Unauthorized:* 401, JSON `{ "error": "signature does not match the request" }`
+async fn authenticate(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiErr> {
+    let (mut parts, body) = req.into_parts();
+    let body = axum::body::to_bytes(body, MAX_SIGNED_BODY)
+        .await
+        .map_err(|e| err(StatusCode::BAD_REQUEST, &e.to_string()))?;
+    let authenticated = match signed_request(&parts, &body) {
+        Ok(request) => {
+            let mut accounts = state.accounts.write().await;
+            accounts.authenticate(&request, SystemTime::now())
+        }
+        Err(e) => Err(e),
+    };
+    let account = authenticated.map_err(|e| {
+        warn!(method = %parts.method, uri = %parts.uri, "request refused: {}", e);
+        auth_err(&e)
+    })?;
+    parts.extensions.insert(Authenticated(account));
+    Ok(next.run(Request::from_parts(parts, Body::from(body))).await)
+}
+
+/// Middleware for the `/admin` routes: the `x-admin-token` header must carry
+/// the server's admin token ([`AppState::admin_token`]). Without one
+/// configured, every admin request is refused.
+///
+/// *Unauthorized:* 401, JSON `{ "error": "invalid admin token" }`
+pub async fn authenticate_admin(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiErr> {
+    let checked = match &state.admin_token {
+        Some(expected) => auth_header(req.headers(), ADMIN_TOKEN_HEADER).and_then(|given| {
+            if admin_token_matches(expected, given) {
+                Ok(())
+            } else {
+                Err(AuthError::BadAdminToken)
+            }
+        }),
+        None => Err(AuthError::AdminDisabled),
+    };
+    if let Err(e) = checked {
+        warn!(method = %req.method(), uri = %req.uri(), "admin request refused: {}", e);
+        return Err(auth_err(&e));
+    }
+    Ok(next.run(req).await)
+}
+
 fn log_rejected(payload: &NewOrder, reason: &str) {
     warn!(
         reason = %reason,
         side = ?payload.side,
         order_type = ?payload.order_type,
         price = ?payload.price,
+        trigger_price = ?payload.trigger_price,
         quantity = payload.quantity,
+        time_in_force = ?payload.time_in_force,
+        account = ?payload.account,
         pair = %payload.pair.code(),
         "order rejected"
     );
@@ -105,7 +258,7 @@ where
         // read full body
         let bytes = Bytes::from_request(req, state)
             .await
-            .map_err(|e| (err(StatusCode::BAD_REQUEST, &e.to_string())))?;
+            .map_err(|e| err(StatusCode::BAD_REQUEST, &e.to_string()))?;
 
         match serde_json::from_slice::<T>(&bytes) {
             Ok(val) => Ok(LoggedJson(val)),
@@ -149,6 +302,28 @@ mod u128_string {
     }
 }
 
+/// Like [`u128_string`], for lists of IDs.
+mod u128_strings {
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(xs: &[u128], s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.collect_seq(xs.iter().map(u128::to_string))
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<Vec<u128>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(d)?
+            .iter()
+            .map(|s| s.parse::<u128>().map_err(serde::de::Error::custom))
+            .collect()
+    }
+}
+
 /// Response for `POST /orders`.
 ///
 /// - `order_id`: the newly generated order ID  
@@ -160,6 +335,64 @@ pub struct OrderAck {
     trades: Vec<Trade>,
 }
 
+/// Response for `GET /orders/{pair}/{id}`, and each item of `GET /orders/{pair}`.
+///
+/// - `price`: current limit price (`None` for market and stop orders)
+/// - `original_quantity`: size the order was submitted with
+/// - `remaining_quantity`: quantity still working in the book, `0` once closed
+/// - `average_fill_price`: volume-weighted over `fills`, `None` until the first fill
+/// - `fills`: every trade the order took part in
+/// - `client_order_id`: the ID the client placed the order with, if any
+#[derive(Serialize, Deserialize)]
+pub struct OrderReport {
+    #[serde(with = "u128_string")]
+    pub order_id: u128,
+    pub status: OrderStatus,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub price: Option<u64>,
+    pub original_quantity: u64,
+    pub remaining_quantity: u64,
+    pub filled_quantity: u64,
+    pub average_fill_price: Option<f64>,
+    pub fills: Vec<Trade>,
+    pub client_order_id: Option<String>,
+}
+
+impl OrderReport {
+    fn new(record: &OrderRecord, remaining_quantity: u64) -> Self {
+        OrderReport {
+            order_id: record.id,
+            status: record.status,
+            side: record.side,
+            order_type: record.order_type,
+            price: record.price,
+            original_quantity: record.original_quantity,
+            remaining_quantity,
+            filled_quantity: record.filled_quantity(),
+            average_fill_price: record.average_fill_price(),
+            fills: record.fills.clone(),
+            client_order_id: record.client_order_id.clone(),
+        }
+    }
+}
+
+/// Filters for `DELETE /orders`; both are optional.
+#[derive(Deserialize)]
+pub struct MassCancelQuery {
+    pair: Option<Pair>,
+    side: Option<Side>,
+}
+
+/// Response for `DELETE /orders`.
+///
+/// - `cancelled`: IDs of the orders cancelled, pair by pair
+#[derive(Serialize, Deserialize)]
+pub struct MassCancelAck {
+    #[serde(with = "u128_strings")]
+    pub cancelled: Vec<u128>,
+}
+
 #[derive(Deserialize)]
 pub struct TradesQuery {
     #[serde(default = "default_limit")]
@@ -179,6 +412,18 @@ pub struct TradesPage {
 /// - `price`: limit price (ignored for market)  
 /// - `quantity`: how many units to trade
 /// - `pair`: trading pair, e.g. `"BTC-USD"` or `"ETH-USD"`
+/// - `time_in_force`: `GTC` (default), `IOC`, `FOK` or `GTD`
+/// - `expire_at`: deadline for `GTD` orders, in milliseconds since the Unix epoch
+/// - `post_only`: `Reject` or `Reprice` to make a limit order maker-only
+/// - `trigger_price`: last-trade price that fires a `Stop`/`StopLimit` order
+/// - `display_quantity`: visible slice of an iceberg limit order
+/// - `account`: optional; must match the account of the API key the order is signed with,
+///   which owns the order. Orders from the same account never trade with each other
+/// - `self_trade_prevention`: what to do on a would-be self-trade (default `CancelNewest`)
+/// - `max_slippage`: protection for `Market`/`Stop` orders, e.g. `{"ticks": 5}` or `{"bps": 50}`;
+///   defaults to the server-wide setting
+/// - `client_order_id`: optional ID of the client's own, up to 64 characters; resubmitting it
+///   within the idempotency window returns the original order instead of placing another
 #[derive(serde::Deserialize)]
 pub struct NewOrder {
     pub side: Side,
@@ -187,7 +432,154 @@ pub struct NewOrder {
     pub quantity: u64,
     #[serde(rename = "symbol", deserialize_with = "parse_pair")]
     pub pair: Pair,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    pub expire_at: Option<u64>,
+    pub post_only: Option<PostOnly>,
+    pub trigger_price: Option<u64>,
+    pub display_quantity: Option<u64>,
+    pub account: Option<String>,
+    #[serde(default)]
+    pub self_trade_prevention: SelfTradePrevention,
+    pub max_slippage: Option<MaxSlippage>,
+    pub client_order_id: Option<String>,
+}
+
+/// Longest `client_order_id` accepted.
+const MAX_CLIENT_ORDER_ID_LEN: usize = 64;
+
+/// Client order IDs must be non-empty and at most [`MAX_CLIENT_ORDER_ID_LEN`] long.
+fn validate_client_order_id(payload: &NewOrder) -> Result<(), &'static str> {
+    match &payload.client_order_id {
+        Some(id) if id.is_empty() || id.len() > MAX_CLIENT_ORDER_ID_LEN => {
+            Err("`client_order_id` must be 1 to 64 characters")
+        }
+        _ => Ok(()),
+    }
 }
+
+/// Checks that `time_in_force` and `expire_at` are consistent and
+/// returns the GTD deadline, if any.
+fn validate_time_in_force(payload: &NewOrder) -> Result<Option<SystemTime>, &'static str> {
+    match (payload.time_in_force, payload.expire_at) {
+        (TimeInForce::GTD, _)
+            if matches!(payload.order_type, OrderType::Market | OrderType::Stop) =>
+        {
+            Err("market orders cannot be GTD")
+        }
+        (TimeInForce::GTD, None) => Err("GTD orders require `expire_at`"),
+        (TimeInForce::GTD, Some(ms)) => {
+            let deadline = UNIX_EPOCH + Duration::from_millis(ms);
+            if deadline <= SystemTime::now() {
+                return Err("`expire_at` must be in the future");
+            }
+            Ok(Some(deadline))
+        }
+        (_, Some(_)) => Err("`expire_at` is only valid for GTD orders"),
+        (_, None) => Ok(None),
+    }
+}
+
+/// Stops need a trigger price (and stop-limits a limit price); nothing else may carry one.
+fn validate_trigger(payload: &NewOrder) -> Result<(), &'static str> {
+    match (payload.order_type, payload.trigger_price, payload.price) {
+        (OrderType::Stop | OrderType::StopLimit, None, _) => {
+            Err("stop orders require `trigger_price`")
+        }
+        (OrderType::Stop, Some(_), Some(_)) => Err("stop orders must not set `price`"),
+        (OrderType::StopLimit, Some(_), None) => Err("stop-limit orders require `price`"),
+        (OrderType::Limit | OrderType::Market, Some(_), _) => {
+            Err("`trigger_price` is only valid for stop orders")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Icebergs must rest as limit orders and show a non-empty slice of their size.
+fn validate_display_quantity(payload: &NewOrder) -> Result<(), &'static str> {
+    let Some(display) = payload.display_quantity else {
+        return Ok(());
+    };
+    if !matches!(payload.order_type, OrderType::Limit | OrderType::StopLimit) {
+        return Err("`display_quantity` is only valid for limit orders");
+    }
+    if display == 0 || display > payload.quantity {
+        return Err("`display_quantity` must be > 0 and <= quantity");
+    }
+    Ok(())
+}
+
+/// Enforces the pair's [`InstrumentSpec`]: tick size on every price, lot size
+/// and bounds on the quantity, and min notional for priced orders.
+fn validate_instrument(payload: &NewOrder, spec: &InstrumentSpec) -> Result<(), SpecViolation> {
+    spec.check_quantity(payload.quantity)?;
+    if let Some(display) = payload.display_quantity {
+        spec.check_lot(display)?;
+    }
+    for price in [payload.price, payload.trigger_price].into_iter().flatten() {
+        spec.check_price(price)?;
+    }
+    if let Some(price) = payload.price.or(payload.trigger_price) {
+        spec.check_notional(price, payload.quantity)?;
+    }
+    Ok(())
+}
+
+/// Slippage protection only applies to orders that execute at market.
+fn validate_max_slippage(payload: &NewOrder) -> Result<(), &'static str> {
+    if payload.max_slippage.is_some()
+        && !matches!(payload.order_type, OrderType::Market | OrderType::Stop)
+    {
+        return Err("`max_slippage` is only valid for market and stop orders");
+    }
+    Ok(())
+}
+
+/// Post-only only makes sense for limit orders that are allowed to rest.
+fn validate_post_only(payload: &NewOrder) -> Result<(), &'static str> {
+    if payload.post_only.is_none() {
+        return Ok(());
+    }
+    if payload.order_type != OrderType::Limit {
+        return Err("post-only orders must be limit orders");
+    }
+    if matches!(payload.time_in_force, TimeInForce::IOC | TimeInForce::FOK) {
+        return Err("post-only orders must be GTC or GTD");
+    }
+    Ok(())
+}
+/// Request payload for `PATCH /orders/{pair}/{id}`.
+///
+/// - `price`: new limit price (omit to keep the current one)  
+/// - `quantity`: new total open quantity (omit to keep the current one)
+#[derive(serde::Deserialize)]
+pub struct AmendOrder {
+    pub price: Option<u64>,
+    pub quantity: Option<u64>,
+}
+
+/// Enforces the pair's [`InstrumentSpec`] on the amended price and quantity,
+/// including the notional of the order as it will stand after the amend.
+fn validate_amend(
+    spec: &InstrumentSpec,
+    payload: &AmendOrder,
+    current: &Order,
+) -> Result<(), SpecViolation> {
+    if let Some(price) = payload.price {
+        spec.check_price(price)?;
+    }
+    if let Some(quantity) = payload.quantity {
+        spec.check_quantity(quantity)?;
+    }
+    if let Some(price) = payload.price.or(current.price) {
+        let quantity = payload
+            .quantity
+            .unwrap_or_else(|| current.remaining_quantity());
+        spec.check_notional(price, quantity)?;
+    }
+    Ok(())
+}
+
 fn parse_pair<'de, D>(deserializer: D) -> Result<Pair, D::Error>
 where
     D: Deserializer<'de>,
@@ -195,19 +587,62 @@ where
     let s = String::deserialize(deserializer)?;
     Pair::from_str(&s).map_err(|_| de::Error::custom(format!("unsupported symbol `{}`", s)))
 }
-/// A websocket message, either a snapshot of the order book or
-/// a single trade event.
+/// A websocket message, either a snapshot of the order book, a change
+/// to it or a single trade event.
 ///
 /// Serialized as an internally-tagged enum:
 /// ```json
-/// {"type": "BookSnapshot", "data": { /* snapshot fields */}}
+/// {"type": "BookSnapshot", "data": {"pair": "BTC-USD", "seq": 7, "bids": [[48, 10]], "asks": []}}
+/// {"type": "BookDelta", "data": {"pair": "BTC-USD", "seq": 8, "changes": [{"side": "Buy", "price": 48, "quantity": 0}]}}
 /// {"type": "Trade", "data": { /* trade fields */}}
+/// {"type": "Phase", "data": {"pair": "BTC-USD", "from": "PreOpen", "phase": "Continuous"}}
+/// {"type": "Auction", "data": {"pair": "BTC-USD", "quote": {"price": 100, "volume": 5, ...}}}
+/// ```
+///
+/// `BookDelta` frames carry the next sequence number of the snapshot they
+/// apply to, see [`BookSnapshot::apply`]. `Auction` frames follow every book
+/// update while the pair is in a call phase.
+///
+/// `Position` and `Owned` frames are only sent on the signed-in account's own
+/// socket (`/ws/account`); `Owned` answers an [`AccountCommand::Own`]:
+/// ```json
+/// {"type": "Position", "data": {"pair": "BTC-USD", "quantity": 3, "realized_pnl": -2, ...}}
+/// {"type": "Owned", "data": {"pair": "BTC-USD", "order_ids": ["1234"]}}
 /// ```
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type", content = "data")]
 pub enum WsFrame {
     BookSnapshot(BookSnapshot),
+    BookDelta(BookDelta),
     Trade(Trade),
+    Phase(PhaseChange),
+    Auction(IndicativeAuction),
+    Position(PositionReport),
+    Owned(OwnedOrders),
+}
+
+/// A message a client sends on its account websocket, tagged like [`WsFrame`]:
+/// ```json
+/// {"type": "Own", "data": {"pair": "BTC-USD", "order_ids": ["1234", "5678"]}}
+/// ```
+///
+/// `Own` makes the session the owner of the listed orders, which a
+/// cancel-on-disconnect session cancels when it ends.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", content = "data")]
+pub enum AccountCommand {
+    Own(OwnedOrders),
+}
+
+/// Orders in one pair owned by an account websocket session.
+///
+/// Answering `Own`, `order_ids` lists those of the requested orders that are
+/// live and belong to the account; the others were not taken.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OwnedOrders {
+    pub pair: Pair,
+    #[serde(with = "u128_strings")]
+    pub order_ids: Vec<u128>,
 }
 
 /// `GET /trades/{pair}`
@@ -253,16 +688,330 @@ pub async fn get_trade_log(
     Ok((AppendHeaders(headers), Json(TradesPage { items, next })))
 }
 
+/// `GET /instruments`
+/// Lists the trading rules ([`InstrumentSpec`]) of every listed pair, so
+/// clients can validate orders before sending them.
+pub async fn list_instruments(State(state): State<AppState>) -> Json<Vec<InstrumentSpec>> {
+    Json(state.instruments.read().await.specs().cloned().collect())
+}
+
+/// `POST /admin/instruments`
+/// Lists a new pair (assets need not be known beforehand) and opens its book.
+///
+/// *Success:* 201, JSON `InstrumentSpec`  
+/// *Bad Request:* 400, e.g. `tick_size` of `0`  
+/// *Conflict:* 409, JSON `{ "error": "pair `ETH-BTC` is already listed" }`
+pub async fn add_instrument(
+    State(state): State<AppState>,
+    LoggedJson(spec): LoggedJson<InstrumentSpec>,
+) -> Result<(StatusCode, Json<InstrumentSpec>), ApiErr> {
+    state
+        .list_instrument(spec.clone())
+        .await
+        .map_err(registry_err)?;
+    Ok((StatusCode::CREATED, Json(spec)))
+}
+
+/// `PUT /admin/instruments/{pair}`
+/// Replaces a listed pair's trading rules, price bands included. Applies to
+/// orders entered from now on; resting orders are left alone.
+///
+/// *Success:* 200, JSON `InstrumentSpec`  
+/// *Bad Request:* 400, invalid spec or `pair` in the body not matching the path
+pub async fn update_instrument(
+    State(state): State<AppState>,
+    Path(pair): Path<Pair>,
+    LoggedJson(spec): LoggedJson<InstrumentSpec>,
+) -> Result<Json<InstrumentSpec>, ApiErr> {
+    if spec.pair != pair {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "`pair` in body does not match path",
+        ));
+    }
+    state
+        .update_instrument(spec.clone())
+        .await
+        .map_err(registry_err)?;
+    Ok(Json(spec))
+}
+
+/// Request payload for `PUT /admin/markets/{pair}/phase`.
+#[derive(Deserialize)]
+pub struct SetPhase {
+    pub phase: TradingPhase,
+}
+
+/// Response for `GET /markets/{pair}`.
+///
+/// - `auction`: the indicative auction price and volume, only reported
+///   during a call phase and while the book crosses
+/// - `reference_price`: anchor of the static price band
+/// - `price_band`: `[low, high]` continuous trading is confined to, if bands apply
+/// - `auction_ends_at`: end of the running volatility auction, in ms since the Unix epoch
+#[derive(Serialize, Deserialize)]
+pub struct MarketStatus {
+    pub pair: Pair,
+    pub phase: TradingPhase,
+    pub auction: Option<AuctionQuote>,
+    pub reference_price: Option<u64>,
+    pub price_band: Option<(u64, u64)>,
+    pub auction_ends_at: Option<u64>,
+}
+
+/// `GET /markets/{pair}`
+/// Reports the pair's current trading phase and, during a call phase,
+/// the indicative auction.
+pub async fn get_market_status(
+    State(state): State<AppState>,
+    Path(pair): Path<Pair>,
+) -> Result<Json<MarketStatus>, ApiErr> {
+    let books = state.order_books.read().await;
+    let Some(book) = books.get(&pair) else {
+        return Err(err(StatusCode::BAD_REQUEST, "unsupported pair"));
+    };
+    let phase = book.phase();
+    let auction = phase.is_call().then(|| book.indicative_auction()).flatten();
+    let auction_ends_at = book
+        .volatility_auction_ends_at()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64);
+    Ok(Json(MarketStatus {
+        pair,
+        phase,
+        auction,
+        reference_price: book.reference_price,
+        price_band: book.band_limits(),
+        auction_ends_at,
+    }))
+}
+
+/// `GET /fees/{pair}`
+/// Reports the fees charged on the pair since startup, overall and per account,
+/// along with the pair's current fee schedule.
+pub async fn get_fee_report(
+    State(state): State<AppState>,
+    Path(pair): Path<Pair>,
+) -> Result<Json<FeeReport>, ApiErr> {
+    let books = state.order_books.read().await;
+    let Some(book) = books.get(&pair) else {
+        return Err(err(StatusCode::BAD_REQUEST, "unsupported pair"));
+    };
+    Ok(Json(book.fee_report(pair)))
+}
+
+/// `PUT /admin/markets/{pair}/phase`
+/// Moves the pair to another trading phase and notifies websocket subscribers.
+///
+/// Opening from `PreOpen` or `Halted`, and closing from `PreClose`, runs a
+/// call auction; the resulting trades are persisted and broadcast like any other.
+///
+/// *Success:* 200, JSON `PhaseChange`  
+/// *Conflict:* 409, the transition is not allowed (e.g. `Closed` → `Continuous`)
+pub async fn set_market_phase(
+    State(state): State<AppState>,
+    Path(pair): Path<Pair>,
+    LoggedJson(payload): LoggedJson<SetPhase>,
+) -> Result<Json<PhaseChange>, ApiErr> {
+    let (from, trades) = {
+        let mut books = state.order_books.write().await;
+        let Some(book) = books.get_mut(&pair) else {
+            return Err(err(StatusCode::BAD_REQUEST, "unsupported pair"));
+        };
+        let mut log = state.trade_log.write().await;
+        let from = book.phase();
+        let trades = book.set_phase(payload.phase).map_err(|e| order_err(&e))?;
+        log.extend(trades.clone());
+        (from, trades)
+    };
+    info!(pair = %pair, ?from, phase = ?payload.phase, "trading phase changed");
+
+    persist_and_broadcast(&state, &trades).await?;
+    let change = PhaseChange {
+        pair: pair.clone(),
+        from,
+        phase: payload.phase,
+    };
+    let _ = state.phase_tx.send(change.clone());
+    state.book_changed(pair).await;
+    Ok(Json(change))
+}
+
+/// `POST /admin/accounts/{account}/keys`
+/// Issues an API key for `account`, opening the account if it is new. The
+/// secret is only ever returned here.
+///
+/// *Success:* 201, JSON `ApiCredentials`
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    Path(account): Path<AccountId>,
+) -> (StatusCode, Json<ApiCredentials>) {
+    (
+        StatusCode::CREATED,
+        Json(state.create_api_key(&account).await),
+    )
+}
+
+/// Request payload for `POST /admin/accounts/{account}/deposit` and `/withdraw`.
+#[derive(Deserialize)]
+pub struct Transfer {
+    pub asset: Asset,
+    pub amount: u64,
+}
+
+/// Response for `GET /balances` and the deposit and withdraw endpoints.
+///
+/// - `balances`: per asset, what is `available` and what open orders hold `reserved`
+#[derive(Serialize, Deserialize)]
+pub struct AccountBalances {
+    pub account: AccountId,
+    pub balances: BTreeMap<Asset, Balance>,
+}
+
+/// `POST /admin/accounts/{account}/deposit`
+/// Credits `amount` of `asset` to the account.
+///
+/// *Success:* 200, JSON `AccountBalances`
+/// *Failure:* 400 if `amount` is zero or the balance would overflow
+pub async fn deposit(
+    State(state): State<AppState>,
+    Path(account): Path<AccountId>,
+    LoggedJson(payload): LoggedJson<Transfer>,
+) -> Result<Json<AccountBalances>, ApiErr> {
+    if payload.amount == 0 {
+        return Err(err(StatusCode::BAD_REQUEST, "amount must be positive"));
+    }
+    state
+        .deposit(&account, &payload.asset, payload.amount)
+        .map_err(|e| balance_err(&e))?;
+    persist_ledger(&state).await?;
+    let balances = state.balances_of(&account);
+    Ok(Json(AccountBalances { account, balances }))
+}
+
+/// `POST /admin/accounts/{account}/withdraw`
+/// Debits `amount` of `asset` from the account's available balance; funds
+/// held by open orders cannot be withdrawn.
+///
+/// *Success:* 200, JSON `AccountBalances`
+/// *Failure:* 400 if `amount` is zero or more than is available
+pub async fn withdraw(
+    State(state): State<AppState>,
+    Path(account): Path<AccountId>,
+    LoggedJson(payload): LoggedJson<Transfer>,
+) -> Result<Json<AccountBalances>, ApiErr> {
+    if payload.amount == 0 {
+        return Err(err(StatusCode::BAD_REQUEST, "amount must be positive"));
+    }
+    state
+        .withdraw(&account, &payload.asset, payload.amount)
+        .map_err(|e| balance_err(&e))?;
+    persist_ledger(&state).await?;
+    let balances = state.balances_of(&account);
+    Ok(Json(AccountBalances { account, balances }))
+}
+
+/// Response for `GET|PUT /admin/accounts/{account}/risk-limits`.
+#[derive(Serialize, Deserialize)]
+pub struct AccountRiskLimits {
+    pub account: AccountId,
+    pub limits: RiskLimits,
+}
+
+/// `GET /admin/accounts/{account}/risk-limits`
+/// Reports the account's pre-trade risk limits; `null` limits are not enforced.
+///
+/// *Success:* 200, JSON `AccountRiskLimits`
+pub async fn get_risk_limits(
+    State(state): State<AppState>,
+    Path(account): Path<AccountId>,
+) -> Json<AccountRiskLimits> {
+    let limits = state.risk.read().await.limits(&account);
+    Json(AccountRiskLimits { account, limits })
+}
+
+/// `PUT /admin/accounts/{account}/risk-limits`
+/// Replaces the account's pre-trade risk limits; limits left out are lifted.
+///
+/// *Success:* 200, JSON `AccountRiskLimits`
+pub async fn set_risk_limits(
+    State(state): State<AppState>,
+    Path(account): Path<AccountId>,
+    LoggedJson(limits): LoggedJson<RiskLimits>,
+) -> Json<AccountRiskLimits> {
+    state.risk.write().await.set_limits(&account, limits);
+    info!(account, ?limits, "set risk limits");
+    Json(AccountRiskLimits { account, limits })
+}
+
+/// `GET /metrics`
+/// Exports the engine's counters in the Prometheus text format.
+pub async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        state.metrics.render(),
+    )
+}
+
+/// `GET /balances`
+/// Reports the signed-in account's balance of every asset it holds.
+///
+/// *Success:* 200, JSON `AccountBalances`
+pub async fn get_balances(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+) -> Json<AccountBalances> {
+    let balances = state.balances_of(&account);
+    Json(AccountBalances { account, balances })
+}
+
+/// Response for `GET /positions`.
+#[derive(Serialize, Deserialize)]
+pub struct AccountPositions {
+    pub account: AccountId,
+    pub positions: Vec<PositionReport>,
+}
+
+/// `GET /positions`
+/// Reports the signed-in account's position in every pair it has traded:
+/// net quantity, average entry price, realized P&L net of fees and
+/// unrealized P&L marked to the book mid.
+///
+/// *Success:* 200, JSON `AccountPositions`
+pub async fn get_positions(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+) -> Json<AccountPositions> {
+    let positions = state.positions_of(&account).await;
+    Json(AccountPositions { account, positions })
+}
+
+fn balance_err(e: &BalanceError) -> ApiErr {
+    err(StatusCode::BAD_REQUEST, &e.to_string())
+}
+
+fn registry_err(e: RegistryError) -> ApiErr {
+    let status = match e {
+        RegistryError::AlreadyListed(_) => StatusCode::CONFLICT,
+        RegistryError::NotListed(_) => StatusCode::NOT_FOUND,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    err(status, &e.to_string())
+}
+
 /// `GET /book`
-/// Returns a JSON snapshot of the current order‐book.
+/// Returns a JSON snapshot of the current order‐book, tagged with the depth
+/// sequence number websocket `BookDelta`s continue from.
 pub async fn get_order_book(
     Path(pair): Path<Pair>,
     State(state): State<AppState>,
 ) -> impl IntoResponse {
-    let books = state.order_books.read().await;
-    let snapshot = books
-        .get(&pair)
-        .map(|book| BookSnapshot::for_pair(pair.clone(), book))
+    let snapshot = state
+        .publish_depth(&pair)
+        .await
         .unwrap_or_else(|| BookSnapshot::empty(pair));
     Json(snapshot).into_response()
 }
@@ -273,25 +1022,183 @@ pub async fn get_order_book(
 /// *Success:*  
 ///   • 200, JSON `OrderAck`  
 /// *Bad Request:*  
-///   • 400, JSON `{ "error": "unsupported pair", "supported": ["BTC-USD","ETH-USD",…] }`  
+///   • 400, JSON `{ "error": "GTD orders require `expire_at`" }` (and other time-in-force errors)  
+///   • 400, JSON `{ "error": "post-only order would take liquidity" }`  
+///   • 400, JSON `{ "error": "...", "violation": { "reason": "price_not_on_tick", ... } }`
+///     when the order breaks the pair's [`InstrumentSpec`]  
+///   • 400, JSON `{ "error": "...", "code": "max_open_orders", ... }` when the order
+///     breaks the account's [`RiskLimits`]  
+/// *Unauthorized:*  
+///   • 401, the request is not signed with a valid API key  
+/// *Forbidden:*  
+///   • 403, `account` is not the API key's account  
+/// *Conflict:*  
+///   • 409, JSON `{ "error": "order not accepted while the market is Halted" }`  
+/// *Unprocessable Entity:*  
+///   • 422, JSON `{ "error": "unsupported symbol: `SOL-USD`" }` for a malformed or unlisted `symbol`  
+/// *Too Many Requests:*  
+///   • 429, JSON `{ "error": "...", "code": "max_order_rate", "limit": 10 }`  
 /// *Failure:*  
 ///   • 500, JSON `{ "error": "internal server error" }`
 pub async fn create_order(
     State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
     LoggedJson(payload): LoggedJson<NewOrder>,
 ) -> Result<Json<OrderAck>, ApiErr> {
+    let spec = state.instrument(&payload.pair).await;
+    let expire_at = validate_new_order(&payload, &account, spec.as_ref())?;
+    let placed = OrderEntry::lock(&state)
+        .await
+        .place(&state, &account, &payload, expire_at)?;
+
+    if let Some((from, phase)) = placed.phases {
+        persist_and_broadcast(&state, &placed.ack.trades).await?;
+        announce_phase_change(&state, &payload.pair, from, phase);
+        state.book_changed(payload.pair).await;
+    }
+    Ok(Json(placed.ack))
+}
+
+/// Runs every check on `payload` that needs no book: its fields, the signing
+/// `account` and the trading rules of its pair (`None` if not listed).
+/// Rejections are logged.
+///
+/// Returns the GTD deadline, if any.
+fn validate_new_order(
+    payload: &NewOrder,
+    account: &str,
+    spec: Option<&InstrumentSpec>,
+) -> Result<Option<SystemTime>, ApiErr> {
+    // An unlisted symbol is as unusable as a malformed one
+    let Some(spec) = spec else {
+        let reason = format!("unsupported symbol: `{}`", payload.pair);
+        log_rejected(payload, &reason);
+        return Err(err(StatusCode::UNPROCESSABLE_ENTITY, &reason));
+    };
+    if payload.account.as_ref().is_some_and(|a| *a != account) {
+        log_rejected(payload, "`account` does not match the API key");
+        return Err(err(
+            StatusCode::FORBIDDEN,
+            "`account` does not match the API key",
+        ));
+    }
     if payload.quantity == 0 {
-        log_rejected(&payload, "quantity must be > 0");
+        log_rejected(payload, "quantity must be > 0");
         return Err(err(StatusCode::BAD_REQUEST, "quantity must be > 0"));
     }
-    let (order_id, trades) = {
-        let mut books = state.order_books.write().await;
+    let expire_at = validate_time_in_force(payload).map_err(|reason| {
+        log_rejected(payload, reason);
+        err(StatusCode::BAD_REQUEST, reason)
+    })?;
+    validate_trigger(payload).map_err(|reason| {
+        log_rejected(payload, reason);
+        err(StatusCode::BAD_REQUEST, reason)
+    })?;
+    validate_display_quantity(payload).map_err(|reason| {
+        log_rejected(payload, reason);
+        err(StatusCode::BAD_REQUEST, reason)
+    })?;
+    validate_post_only(payload).map_err(|reason| {
+        log_rejected(payload, reason);
+        err(StatusCode::BAD_REQUEST, reason)
+    })?;
+    validate_max_slippage(payload).map_err(|reason| {
+        log_rejected(payload, reason);
+        err(StatusCode::BAD_REQUEST, reason)
+    })?;
+    validate_client_order_id(payload).map_err(|reason| {
+        log_rejected(payload, reason);
+        err(StatusCode::BAD_REQUEST, reason)
+    })?;
+    validate_instrument(payload, spec).map_err(|violation| {
+        log_rejected(payload, &violation.to_string());
+        spec_err(&violation)
+    })?;
+    Ok(expire_at)
+}
+
+/// The locks orders are placed under, taken in this order. A batch takes
+/// them once for all of its orders.
+struct OrderEntry<'a> {
+    books: RwLockWriteGuard<'a, HashMap<Pair, OrderBook>>,
+    client_orders: RwLockWriteGuard<'a, ClientOrderIds>,
+    risk: RwLockWriteGuard<'a, RiskManager>,
+    log: RwLockWriteGuard<'a, Vec<Trade>>,
+}
+
+/// An order [`OrderEntry::place`] accepted.
+///
+/// - `phases`: the trading phase of the order's book before and after it;
+///   `None` for a retry answered with the original order, which changed nothing
+struct Placed {
+    ack: OrderAck,
+    phases: Option<(TradingPhase, TradingPhase)>,
+}
+
+impl<'a> OrderEntry<'a> {
+    async fn lock(state: &'a AppState) -> Self {
+        let books = state.order_books.write().await;
+        // Client order IDs are checked and recorded under the books lock, so
+        // concurrent retries cannot both place
+        let client_orders = state.client_orders.write().await;
+        let risk = state.risk.write().await;
+        let log = state.trade_log.write().await;
+        OrderEntry {
+            books,
+            client_orders,
+            risk,
+            log,
+        }
+    }
 
-        let Some(book) = books.get_mut(&payload.pair) else {
-            log_rejected(&payload, "unsupported pair");
+    /// Puts a validated order through the risk checks and into its book.
+    /// Rejections are logged.
+    fn place(
+        &mut self,
+        state: &AppState,
+        account: &str,
+        payload: &NewOrder,
+        expire_at: Option<SystemTime>,
+    ) -> Result<Placed, ApiErr> {
+        if let Some(client_order_id) = &payload.client_order_id
+            && let Some(original) = self.client_orders.recent(
+                account,
+                client_order_id,
+                state.client_order_id_window,
+                SystemTime::now(),
+            )
+        {
+            info!(
+                account,
+                client_order_id, "Duplicate order; returning the original."
+            );
+            return Ok(Placed {
+                ack: OrderAck {
+                    order_id: original.order_id,
+                    trades: original.trades.clone(),
+                },
+                phases: None,
+            });
+        }
+
+        let Some(book) = self.books.get_mut(&payload.pair) else {
+            log_rejected(payload, "unsupported pair");
             return Err(err(StatusCode::BAD_REQUEST, "unsupported pair"));
         };
-        let mut log = state.trade_log.write().await;
+        let check = RiskCheck {
+            account,
+            side: payload.side,
+            quantity: payload.quantity,
+            price: payload.price.or(payload.trigger_price),
+            replaces: None,
+        };
+        self.risk
+            .check(&check, book, Instant::now())
+            .map_err(|violation| {
+                log_rejected(payload, &violation.to_string());
+                state.metrics.count_risk_rejection(&violation);
+                risk_err(&violation)
+            })?;
         let order = Order {
             id: Uuid::new_v4().as_u128(),
             side: payload.side,
@@ -300,29 +1207,200 @@ pub async fn create_order(
             quantity: payload.quantity,
             timestamp: SystemTime::now(),
             pair: payload.pair.clone(),
+            time_in_force: payload.time_in_force,
+            expire_at,
+            post_only: payload.post_only,
+            trigger_price: payload.trigger_price,
+            display_quantity: payload.display_quantity,
+            hidden_quantity: 0,
+            owner: Some(account.to_string()),
+            self_trade_prevention: payload.self_trade_prevention,
+            max_slippage: match payload.order_type {
+                OrderType::Market | OrderType::Stop => {
+                    payload.max_slippage.or(state.default_slippage)
+                }
+                _ => None,
+            },
+            client_order_id: payload.client_order_id.clone(),
         };
         let order_id = order.id;
-        let trades = book.match_order(order);
-        log.extend(trades.clone());
-        (order_id, trades)
-    };
+        let from = book.phase();
+        let trades = book.match_order(order).map_err(|e| {
+            log_rejected(payload, &e.to_string());
+            order_err(&e)
+        })?;
+        self.log.extend(trades.clone());
+        if let Some(client_order_id) = payload.client_order_id.clone() {
+            let original = ClientOrder {
+                pair: payload.pair.clone(),
+                order_id,
+                trades: trades.clone(),
+                placed_at: SystemTime::now(),
+            };
+            self.client_orders
+                .insert(account, client_order_id, original);
+        }
+        Ok(Placed {
+            ack: OrderAck { order_id, trades },
+            phases: Some((from, book.phase())),
+        })
+    }
+}
+
+/// Persists `trades` in the store, then broadcasts them on `trade_tx`.
+async fn persist_and_broadcast(state: &AppState, trades: &[Trade]) -> Result<(), ApiErr> {
+    state
+        .persist_and_broadcast(trades)
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))
+}
+
+async fn persist_ledger(state: &AppState) -> Result<(), ApiErr> {
+    state
+        .persist_ledger()
+        .await
+        .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))
+}
 
-    //persist all trades in store
-    let mut store = state.store.write().await;
-    for trade in &trades {
-        store
-            .insert_trade(trade)
-            .map_err(|e| err(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+/// Announces a phase change the engine made on its own, such as a
+/// volatility auction started by a price band breach.
+fn announce_phase_change(state: &AppState, pair: &Pair, from: TradingPhase, phase: TradingPhase) {
+    if from != phase {
+        let _ = state.phase_tx.send(PhaseChange {
+            pair: pair.clone(),
+            from,
+            phase,
+        });
     }
+}
 
-    //broadcast trades after successful persistence
-    for trade in &trades {
-        let _ = state.trade_tx.send(trade.clone());
+/// `PATCH /orders/{pair}/{id}`
+/// Atomically amends a resting order (cancel/replace in a single step).
+///
+/// - Lowering `quantity` at the same price keeps the order's queue position.
+/// - Changing `price` or raising `quantity` re-queues it; if the new price
+///   crosses, the order trades immediately.
+///
+/// *Success:* 200, JSON `OrderAck` with any trades produced  
+/// *Bad Request:* 400, e.g. nothing to amend, `quantity == 0`, a post-only order that would cross,
+/// or a price/quantity that breaks the pair's [`InstrumentSpec`] or the account's [`RiskLimits`]  
+/// *Forbidden:* 403, the order belongs to another account  
+/// *Not Found:* 404, JSON `{ "error": "order not found" }`
+pub async fn amend_order(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+    Path((pair, order_id)): Path<(Pair, u128)>,
+    LoggedJson(payload): LoggedJson<AmendOrder>,
+) -> Result<Json<OrderAck>, ApiErr> {
+    if payload.price.is_none() && payload.quantity.is_none() {
+        return Err(err(StatusCode::BAD_REQUEST, "nothing to amend"));
+    }
+    if payload.quantity == Some(0) {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            "quantity must be > 0; use DELETE to cancel",
+        ));
     }
-    let _ = state.book_tx.send(payload.pair);
+    let Some(spec) = state.instrument(&pair).await else {
+        return Err(err(StatusCode::BAD_REQUEST, "unsupported pair"));
+    };
+    let (trades, from, phase) = {
+        let mut books = state.order_books.write().await;
+        let Some(book) = books.get_mut(&pair) else {
+            return Err(err(StatusCode::BAD_REQUEST, "unsupported pair"));
+        };
+        book.check_owner(order_id, &account)
+            .map_err(|e| order_err(&e))?;
+        if let Some(current) = book.order(order_id) {
+            validate_amend(&spec, &payload, current).map_err(|v| spec_err(&v))?;
+            let check = RiskCheck {
+                account: &account,
+                side: current.side,
+                quantity: payload
+                    .quantity
+                    .unwrap_or_else(|| current.remaining_quantity()),
+                price: payload.price.or(current.price),
+                replaces: Some(order_id),
+            };
+            state
+                .risk
+                .write()
+                .await
+                .check(&check, book, Instant::now())
+                .map_err(|violation| {
+                    state.metrics.count_risk_rejection(&violation);
+                    risk_err(&violation)
+                })?;
+        }
+        let mut log = state.trade_log.write().await;
+        let from = book.phase();
+        let trades = book
+            .amend_order(order_id, payload.price, payload.quantity)
+            .map_err(|e| order_err(&e))?;
+        log.extend(trades.clone());
+        (trades, from, book.phase())
+    };
+    info!("Order {} amended.", order_id);
+
+    persist_and_broadcast(&state, &trades).await?;
+    announce_phase_change(&state, &pair, from, phase);
+    state.book_changed(pair).await;
     Ok(Json(OrderAck { order_id, trades }))
 }
 
+/// `GET /orders/{pair}/{id}`
+/// Reports one of the signed-in account's orders: status, quantities and
+/// fills. Works for orders that are still live as well as recently filled,
+/// cancelled or expired ones.
+///
+/// *Success:* 200, JSON `OrderReport`  
+/// *Not Found:* 404, JSON `{ "error": "order not found" }`  
+/// *Forbidden:* 403, the order belongs to another account
+pub async fn get_order(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+    Path((pair, order_id)): Path<(Pair, u128)>,
+) -> Result<Json<OrderReport>, ApiErr> {
+    let books = state.order_books.read().await;
+    let Some(book) = books.get(&pair) else {
+        return Err(err(StatusCode::BAD_REQUEST, "unsupported pair"));
+    };
+    book.check_owner(order_id, &account)
+        .map_err(|e| order_err(&e))?;
+    let record = book
+        .order_record(order_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "order not found"))?;
+    Ok(Json(OrderReport::new(
+        record,
+        book.remaining_quantity(order_id),
+    )))
+}
+
+/// `GET /orders/{pair}`
+/// Lists the signed-in account's open orders in the pair (resting and
+/// untriggered stops), bids first.
+///
+/// *Success:* 200, JSON array of `OrderReport`
+pub async fn list_open_orders(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+    Path(pair): Path<Pair>,
+) -> Result<Json<Vec<OrderReport>>, ApiErr> {
+    let books = state.order_books.read().await;
+    let Some(book) = books.get(&pair) else {
+        return Err(err(StatusCode::BAD_REQUEST, "unsupported pair"));
+    };
+    let reports = book
+        .open_orders()
+        .filter(|o| o.owner.as_deref() == Some(account.as_str()))
+        .filter_map(|o| {
+            let record = book.order_record(o.id)?;
+            Some(OrderReport::new(record, o.remaining_quantity()))
+        })
+        .collect();
+    Ok(Json(reports))
+}
+
 /// `DELETE /orders/{id}`
 /// Path parameter:
 /// - `id` – the UUID of the order to cancel.
@@ -330,23 +1408,369 @@ pub async fn create_order(
 /// Cancels the order with the given ID.
 /// *Success:* 200, JSON `{ "status": "cancelled" }`
 /// *Failure:* 404, JSON `{ "error": "Order not found", "status": 404 }`
+/// *Forbidden:* 403, the order belongs to another account
+/// *Conflict:* 409, the market is `Closed`
 pub async fn cancel_order(
     State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
     Path((pair, order_id)): Path<(Pair, u128)>,
 ) -> impl IntoResponse {
-    let mut books = state.order_books.write().await;
+    cancel_owned_order(&state, &account, pair, order_id).await
+}
+
+/// Cancels `account`'s order `order_id` in `pair`, answering as [`cancel_order`].
+async fn cancel_owned_order(
+    state: &AppState,
+    account: &str,
+    pair: Pair,
+    order_id: u128,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let cancelled = {
+        let mut books = state.order_books.write().await;
+        cancel_in_books(&mut books, account, &pair, order_id)
+    };
+    match cancelled {
+        Ok(()) => {
+            state.book_changed(pair).await;
+            (StatusCode::OK, Json(json!({"status": "cancelled"})))
+        }
+        Err(e) => e,
+    }
+}
 
-    let Some(book) = books.get_mut(&pair) else {
-        return err(StatusCode::BAD_REQUEST, "unsupported pair");
+/// Cancels `account`'s order `order_id` in `pair`'s book. Refusals are logged.
+fn cancel_in_books(
+    books: &mut HashMap<Pair, OrderBook>,
+    account: &str,
+    pair: &Pair,
+    order_id: u128,
+) -> Result<(), ApiErr> {
+    let Some(book) = books.get_mut(pair) else {
+        return Err(err(StatusCode::BAD_REQUEST, "unsupported pair"));
     };
+    if !book.phase().accepts_cancels() {
+        warn!(
+            "Cancel of order {} refused: market is {:?}.",
+            order_id,
+            book.phase()
+        );
+        return Err(order_err(&OrderError::CancelNotAcceptedInPhase(
+            book.phase(),
+        )));
+    }
+    if let Err(e) = book.check_owner(order_id, account) {
+        warn!(
+            "Cancel of order {} by {} refused: {}.",
+            order_id, account, e
+        );
+        return Err(order_err(&e));
+    }
     if book.cancel_order(order_id) {
         info!("Order {} cancelled successfully.", order_id);
-        let _ = state.book_tx.send(pair);
-        (StatusCode::OK, Json(json!({"status": "cancelled"})))
+        Ok(())
     } else {
         warn!("Cancel failed: Order {} not found.", order_id);
-        err(StatusCode::NOT_FOUND, "order not found")
+        Err(err(StatusCode::NOT_FOUND, "order not found"))
+    }
+}
+
+/// Finds the order `account` placed as `client_order_id`.
+async fn client_order(
+    state: &AppState,
+    account: &str,
+    client_order_id: &str,
+) -> Result<(Pair, u128), ApiErr> {
+    let client_orders = state.client_orders.read().await;
+    client_orders
+        .get(account, client_order_id)
+        .map(|order| (order.pair.clone(), order.order_id))
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "order not found"))
+}
+
+/// `GET /orders/client/{client_order_id}`
+/// Reports the signed-in account's order placed with `client_order_id`, like
+/// `GET /orders/{pair}/{id}`. IDs are forgotten once their idempotency window
+/// has passed, see [`AppState::prune_client_order_ids`].
+///
+/// *Success:* 200, JSON `OrderReport`  
+/// *Not Found:* 404, JSON `{ "error": "order not found" }`
+pub async fn get_order_by_client_id(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+    Path(client_order_id): Path<String>,
+) -> Result<Json<OrderReport>, ApiErr> {
+    let (pair, order_id) = client_order(&state, &account, &client_order_id).await?;
+    let books = state.order_books.read().await;
+    let Some(book) = books.get(&pair) else {
+        return Err(err(StatusCode::NOT_FOUND, "order not found"));
+    };
+    let record = book
+        .order_record(order_id)
+        .ok_or_else(|| err(StatusCode::NOT_FOUND, "order not found"))?;
+    Ok(Json(OrderReport::new(
+        record,
+        book.remaining_quantity(order_id),
+    )))
+}
+
+/// `DELETE /orders/client/{client_order_id}`
+/// Cancels the signed-in account's order placed with `client_order_id`,
+/// answering like `DELETE /orders/{pair}/{id}`.
+pub async fn cancel_order_by_client_id(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+    Path(client_order_id): Path<String>,
+) -> Result<impl IntoResponse, ApiErr> {
+    let (pair, order_id) = client_order(&state, &account, &client_order_id).await?;
+    Ok(cancel_owned_order(&state, &account, pair, order_id).await)
+}
+
+/// `DELETE /orders`
+/// Cancels all of the signed-in account's live orders, including untriggered
+/// stops, in one step per book.
+///
+/// # Query Parameters
+/// - `pair`: only cancel orders in this pair
+/// - `side`: only cancel `Buy` or `Sell` orders
+///
+/// Without `pair`, books whose market does not accept cancels are left alone.
+///
+/// *Success:* 200, JSON `MassCancelAck`, with an empty list if nothing was open  
+/// *Bad Request:* 400, `pair` is not listed  
+/// *Conflict:* 409, the market `pair` names is `Closed`
+pub async fn cancel_all_orders(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+    Query(q): Query<MassCancelQuery>,
+) -> Result<Json<MassCancelAck>, ApiErr> {
+    if let Some(pair) = &q.pair
+        && state.instrument(pair).await.is_none()
+    {
+        return Err(err(StatusCode::BAD_REQUEST, "unsupported pair"));
+    }
+    let cancelled = state
+        .cancel_all(&account, q.pair.as_ref(), q.side)
+        .await
+        .map_err(|e| {
+            warn!("Mass cancel by {} refused: {}.", account, e);
+            order_err(&e)
+        })?;
+    Ok(Json(MassCancelAck {
+        cancelled: cancelled.into_values().flatten().collect(),
+    }))
+}
+
+/// Most items `POST /orders/batch` and `DELETE /orders/batch` take at once.
+const MAX_BATCH: usize = 100;
+
+/// Request payload for `POST /orders/batch`: up to [`MAX_BATCH`] orders, each
+/// as for `POST /orders`.
+#[derive(Deserialize)]
+pub struct NewOrderBatch {
+    pub orders: Vec<NewOrder>,
+}
+
+/// One order to cancel in a `DELETE /orders/batch` request.
+#[derive(Deserialize)]
+pub struct CancelItem {
+    #[serde(rename = "symbol", deserialize_with = "parse_pair")]
+    pub pair: Pair,
+    #[serde(with = "u128_string")]
+    pub order_id: u128,
+}
+
+/// Request payload for `DELETE /orders/batch`: up to [`MAX_BATCH`] orders.
+#[derive(Deserialize)]
+pub struct CancelBatch {
+    pub orders: Vec<CancelItem>,
+}
+
+/// The outcome of one item of a batch, in the order the items were sent.
+///
+/// - `status`: the HTTP status the item would have got as a request of its own
+/// - `body`: the response body it would have got, e.g. an `OrderAck` or
+///   `{ "error": "…" }`
+#[derive(Serialize, Deserialize)]
+pub struct BatchItemResult<T> {
+    pub status: u16,
+    pub body: BatchItemBody<T>,
+}
+
+/// The body of a [`BatchItemResult`]: `T` on success, the error body otherwise.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BatchItemBody<T> {
+    Ok(T),
+    Err(serde_json::Value),
+}
+
+impl<T> BatchItemResult<T> {
+    fn new(result: Result<T, ApiErr>) -> Self {
+        match result {
+            Ok(body) => BatchItemResult {
+                status: StatusCode::OK.as_u16(),
+                body: BatchItemBody::Ok(body),
+            },
+            Err((status, Json(body))) => BatchItemResult {
+                status: status.as_u16(),
+                body: BatchItemBody::Err(body),
+            },
+        }
+    }
+}
+
+/// Response for `POST /orders/batch` (`T` is `OrderAck`) and
+/// `DELETE /orders/batch`.
+#[derive(Serialize, Deserialize)]
+pub struct BatchResponse<T> {
+    pub results: Vec<BatchItemResult<T>>,
+}
+
+fn check_batch_size(items: usize) -> Result<(), ApiErr> {
+    if items == 0 {
+        return Err(err(StatusCode::BAD_REQUEST, "batch is empty"));
+    }
+    if items > MAX_BATCH {
+        return Err(err(
+            StatusCode::BAD_REQUEST,
+            &format!("batch has {} items, the max is {}", items, MAX_BATCH),
+        ));
     }
+    Ok(())
+}
+
+/// `POST /orders/batch`
+/// Places several orders in one request. They are placed one after the other
+/// in the order given, under a single lock of the books, so nothing else
+/// trades in between; each stands on its own and may fail without affecting
+/// the others.
+///
+/// *Success:* 200, JSON `BatchResponse` with a result per order, e.g. `200` and
+/// an `OrderAck`, or the error `POST /orders` would have answered  
+/// *Bad Request:* 400, the batch is empty or has more than [`MAX_BATCH`] orders
+pub async fn create_order_batch(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+    LoggedJson(batch): LoggedJson<NewOrderBatch>,
+) -> Result<Json<BatchResponse<OrderAck>>, ApiErr> {
+    check_batch_size(batch.orders.len())?;
+    let validated: Vec<_> = {
+        let instruments = state.instruments.read().await;
+        batch
+            .orders
+            .iter()
+            .map(|payload| validate_new_order(payload, &account, instruments.get(&payload.pair)))
+            .collect()
+    };
+
+    let mut results = Vec::with_capacity(batch.orders.len());
+    let mut trades = Vec::new();
+    let mut phase_changes = Vec::new();
+    let mut touched = BTreeSet::new();
+    {
+        let mut entry = OrderEntry::lock(&state).await;
+        for (payload, expire_at) in batch.orders.iter().zip(validated) {
+            let placed =
+                expire_at.and_then(|expire_at| entry.place(&state, &account, payload, expire_at));
+            if let Ok(Placed {
+                ack,
+                phases: Some((from, phase)),
+            }) = &placed
+            {
+                trades.extend(ack.trades.iter().cloned());
+                phase_changes.push((payload.pair.clone(), *from, *phase));
+                touched.insert(payload.pair.clone());
+            }
+            results.push(BatchItemResult::new(placed.map(|placed| placed.ack)));
+        }
+    }
+    info!(account, orders = results.len(), "Order batch processed.");
+
+    persist_and_broadcast(&state, &trades).await?;
+    for (pair, from, phase) in &phase_changes {
+        announce_phase_change(&state, pair, *from, *phase);
+    }
+    for pair in touched {
+        state.book_changed(pair).await;
+    }
+    Ok(Json(BatchResponse { results }))
+}
+
+/// `DELETE /orders/batch`
+/// Cancels several orders in one request, in the order given, under a single
+/// lock of the books; each may fail without affecting the others.
+///
+/// *Success:* 200, JSON `BatchResponse` with a result per order, `200` and
+/// `{ "status": "cancelled" }` or the error `DELETE /orders/{pair}/{id}` would
+/// have answered  
+/// *Bad Request:* 400, the batch is empty or has more than [`MAX_BATCH`] orders
+pub async fn cancel_order_batch(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+    LoggedJson(batch): LoggedJson<CancelBatch>,
+) -> Result<Json<BatchResponse<serde_json::Value>>, ApiErr> {
+    check_batch_size(batch.orders.len())?;
+    let mut results = Vec::with_capacity(batch.orders.len());
+    let mut touched = BTreeSet::new();
+    {
+        let mut books = state.order_books.write().await;
+        for item in &batch.orders {
+            let cancelled = cancel_in_books(&mut books, &account, &item.pair, item.order_id);
+            if cancelled.is_ok() {
+                touched.insert(item.pair.clone());
+            }
+            results.push(BatchItemResult::new(
+                cancelled.map(|()| json!({"status": "cancelled"})),
+            ));
+        }
+    }
+    for pair in touched {
+        state.book_changed(pair).await;
+    }
+    Ok(Json(BatchResponse { results }))
+}
+
+/// Request payload for `POST /cancel-all-after`.
+///
+/// - `timeout_ms`: how long until the account's orders are cancelled; `0` disarms the switch
+#[derive(Deserialize)]
+pub struct CancelAllAfter {
+    pub timeout_ms: u64,
+}
+
+/// Response for `POST /cancel-all-after`.
+///
+/// - `cancel_at`: when the orders will be cancelled, in milliseconds since the
+///   Unix epoch; `null` once disarmed
+#[derive(Serialize, Deserialize)]
+pub struct CancelAllAfterAck {
+    pub cancel_at: Option<u64>,
+}
+
+/// `POST /cancel-all-after`
+/// Arms the signed-in account's dead-man's switch: unless this is called again
+/// within `timeout_ms`, all of the account's orders in every pair are cancelled.
+/// Clients refresh it well before it runs out for as long as they are healthy.
+///
+/// *Success:* 200, JSON `CancelAllAfterAck`
+pub async fn cancel_all_after(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+    LoggedJson(payload): LoggedJson<CancelAllAfter>,
+) -> Json<CancelAllAfterAck> {
+    let timeout = Duration::from_millis(payload.timeout_ms);
+    let cancel_at = state.cancel_all_after(&account, timeout).await;
+    info!(
+        account,
+        timeout_ms = payload.timeout_ms,
+        "cancel-all-after set"
+    );
+    Json(CancelAllAfterAck {
+        cancel_at: cancel_at.map(|t| {
+            t.duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64)
+        }),
+    })
 }
 
 /// `GET /ws`  
@@ -360,30 +1784,32 @@ pub async fn ws_handler(
     ws.on_upgrade(move |socket| handle_socket(socket, state, pair))
 }
 
+/// Sends `frame` as a text message.
+async fn send_frame(socket: &mut WebSocket, frame: &WsFrame) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(frame).unwrap();
+    socket.send(Message::Text(text.into())).await
+}
+
 /// Once the socket connection is upgraded from HTTP to WebSocket, drives the message loop:
-///  - Sends an initial `BookSnapshot`  
-///  - Listens for trade and book‐update broadcasts and forwards them
+///  - Sends an initial `BookSnapshot` tagged with its depth sequence number
+///  - Listens for trade, depth, book‐update and phase‐change broadcasts and forwards them,
+///    each depth change as a `BookDelta` numbered one after the last
+///  - Sends a fresh `BookSnapshot` instead if it fell behind and missed deltas
+///  - Follows each book update with an `Auction` frame while the pair is in a call phase
 pub async fn handle_socket(mut socket: WebSocket, state: AppState, pair: Pair) {
     let mut trade_rx = state.trade_tx.subscribe();
+    let mut depth_rx = state.depth_tx.subscribe();
     let mut book_rx = state.book_tx.subscribe();
+    let mut phase_rx = state.phase_tx.subscribe();
 
     let pair_code = pair.code();
-    //initial snapshot
-    let initial = {
-        let books = state.order_books.read().await;
-        match books.get(&pair) {
-            Some(book) => BookSnapshot::for_pair(pair.clone(), book),
-            None => BookSnapshot::empty(pair.clone()),
-        }
-    };
-    if let Err(e) = socket
-        .send(Message::Text(
-            serde_json::to_string(&WsFrame::BookSnapshot(initial))
-                .unwrap()
-                .into(),
-        ))
+    //initial snapshot; subscribed first so no delta after it is missed
+    let initial = state
+        .publish_depth(&pair)
         .await
-    {
+        .unwrap_or_else(|| BookSnapshot::empty(pair.clone()));
+    let mut seq = initial.seq;
+    if let Err(e) = send_frame(&mut socket, &WsFrame::BookSnapshot(initial)).await {
         error!("Failed to send initial snapshot: {:?}", e);
         return;
     }
@@ -392,41 +1818,260 @@ pub async fn handle_socket(mut socket: WebSocket, state: AppState, pair: Pair) {
         tokio::select! {
             Ok(trade) = trade_rx.recv() => {
 
-                if trade.symbol == pair_code {
-                if let Err(e) = socket.send(Message::Text(serde_json::to_string(&WsFrame::Trade(trade)).unwrap().into())).await {
+                if trade.symbol == pair_code
+                && let Err(e) = send_frame(&mut socket, &WsFrame::Trade(trade)).await {
                     error!("WebSocket send trade failed: {:?}", e);
                     break;
                 }
-                }
 
             }
+            delta = depth_rx.recv() => {
+                let frame = match delta {
+                    Ok(delta) if delta.pair == pair && delta.seq > seq => {
+                        seq = delta.seq;
+                        WsFrame::BookDelta(delta)
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!(pair = %pair, missed, "websocket fell behind the depth feed, resending snapshot");
+                        let Some(snap) = state.publish_depth(&pair).await else { continue };
+                        seq = snap.seq;
+                        WsFrame::BookSnapshot(snap)
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if let Err(e) = send_frame(&mut socket, &frame).await {
+                    error!("WebSocket send depth failed: {:?}", e);
+                    break;
+                }
+            }
             Ok(updated_pair) = book_rx.recv() => {
-                if updated_pair == pair {
-                    //get related book
-                    let book = {
-                         state.order_books.read().await[&pair].clone()
-                    };
+                if updated_pair != pair {
+                    continue;
+                }
+                let auction = {
+                    let books = state.order_books.read().await;
+                    books
+                        .get(&pair)
+                        .filter(|book| book.phase().is_call())
+                        .map(|book| IndicativeAuction { pair: pair.clone(), quote: book.indicative_auction() })
+                };
+                if let Some(auction) = auction
+                && let Err(e) = send_frame(&mut socket, &WsFrame::Auction(auction)).await {
+                    error!("WebSocket send indicative auction failed: {:?}", e);
+                    break;
+                }
+            }
+            Ok(change) = phase_rx.recv() => {
+                if change.pair == pair
+                && let Err(e) = send_frame(&mut socket, &WsFrame::Phase(change)).await {
+                    error!("WebSocket send phase change failed: {:?}", e);
+                    break;
+                }
+            } else => break
+        }
+    }
+}
+
+/// Query parameters for `GET /ws/account`.
+///
+/// - `cancel_on_disconnect`: cancel the orders the session owns when it ends
+///   (default `false`)
+#[derive(Deserialize)]
+pub struct AccountSocketQuery {
+    #[serde(default)]
+    cancel_on_disconnect: bool,
+}
+
+/// Heartbeats a client may miss before its account websocket is dropped.
+const MISSED_HEARTBEATS: u32 = 3;
+
+/// `GET /ws/account`
+/// Upgrades a signed request to a WebSocket private to the signed-in account,
+/// see [`handle_account_socket`].
+pub async fn ws_account_handler(
+    State(state): State<AppState>,
+    Extension(Authenticated(account)): Extension<Authenticated>,
+    Query(q): Query<AccountSocketQuery>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| {
+        handle_account_socket(socket, state, account, q.cancel_on_disconnect)
+    })
+}
 
-                    let snap = BookSnapshot::for_pair(pair.clone(), &book);
-                    if let Err(e) = socket.send(Message::Text(serde_json::to_string(&WsFrame::BookSnapshot(snap)).unwrap().into())).await {
-                        error!("WebSocket send snapshot failed: {:?}", e);
+/// Streams `account`'s positions: a `Position` frame for every position on
+/// connect, then one whenever a position changes, by a fill or by its book's
+/// mid moving.
+///
+/// The socket is pinged every [`AppState::ws_heartbeat`] and dropped once the
+/// client has sent nothing, pongs included, for [`MISSED_HEARTBEATS`] of them.
+/// Runs until then, or until the client closes the socket or it fails.
+///
+/// The client takes ownership of its orders with [`AccountCommand::Own`]
+/// messages, each answered with an `Owned` frame. With `cancel_on_disconnect`,
+/// the orders the session owns are cancelled when it ends, with one book update
+/// per pair; the account's other orders, including those owned by its other
+/// sessions, are left alone.
+pub async fn handle_account_socket(
+    mut socket: WebSocket,
+    state: AppState,
+    account: AccountId,
+    cancel_on_disconnect: bool,
+) {
+    let mut book_rx = state.book_tx.subscribe();
+    let mut sent: HashMap<Pair, PositionReport> = HashMap::new();
+    let mut changed = state.positions_of(&account).await;
+    let first_ping = tokio::time::Instant::now() + state.ws_heartbeat;
+    let mut heartbeat = tokio::time::interval_at(first_ping, state.ws_heartbeat);
+    let mut last_seen = Instant::now();
+    let mut owned: BTreeMap<Pair, BTreeSet<u128>> = BTreeMap::new();
+    'session: loop {
+        for report in changed.drain(..) {
+            if sent.get(&report.pair) == Some(&report) {
+                continue;
+            }
+            let frame = serde_json::to_string(&WsFrame::Position(report.clone())).unwrap();
+            if let Err(e) = socket.send(Message::Text(frame.into())).await {
+                error!("WebSocket send position failed: {:?}", e);
+                break 'session;
+            }
+            sent.insert(report.pair.clone(), report);
+        }
+        tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(Message::Text(text))) => {
+                    last_seen = Instant::now();
+                    let orders = match serde_json::from_str::<AccountCommand>(&text) {
+                        Ok(AccountCommand::Own(orders)) => live_orders_of(&state, &account, orders).await,
+                        Err(e) => {
+                            warn!(account, "ignored account websocket message: {}", e);
+                            continue;
+                        }
+                    };
+                    owned.entry(orders.pair.clone()).or_default().extend(&orders.order_ids);
+                    let frame = serde_json::to_string(&WsFrame::Owned(orders)).unwrap();
+                    if let Err(e) = socket.send(Message::Text(frame.into())).await {
+                        error!("WebSocket send owned orders failed: {:?}", e);
                         break;
                     }
                 }
-            } else => break
+                Some(Ok(_)) => last_seen = Instant::now(),
+            },
+            update = book_rx.recv() => match update {
+                Ok(pair) => {
+                    let books = state.order_books.read().await;
+                    changed.extend(
+                        books
+                            .get(&pair)
+                            .and_then(|book| book.position_report(pair.clone(), &account)),
+                    );
+                }
+                // Missed some updates; compare every position instead
+                Err(RecvError::Lagged(_)) => {
+                    changed = state.positions_of(&account).await;
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > state.ws_heartbeat * MISSED_HEARTBEATS {
+                    warn!(account, "account websocket missed its heartbeats");
+                    break;
+                }
+                if let Err(e) = socket.send(Message::Ping(Bytes::new())).await {
+                    error!("WebSocket send ping failed: {:?}", e);
+                    break;
+                }
+            }
         }
     }
+    info!(account, "account websocket closed");
+    if cancel_on_disconnect {
+        let cancelled = state.cancel_orders(&account, &owned).await;
+        info!(
+            account,
+            count = cancelled.values().map(Vec::len).sum::<usize>(),
+            "cancelled orders on disconnect"
+        );
+    }
+}
+
+/// Those of `orders` that are live and belong to `account`.
+async fn live_orders_of(state: &AppState, account: &str, orders: OwnedOrders) -> OwnedOrders {
+    let books = state.order_books.read().await;
+    let order_ids = match books.get(&orders.pair) {
+        Some(book) => orders
+            .order_ids
+            .into_iter()
+            .filter(|&id| {
+                book.order(id)
+                    .is_some_and(|o| o.owner.as_deref() == Some(account))
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    OwnedOrders {
+        pair: orders.pair,
+        order_ids,
+    }
 }
 
 /// Constructs the application’s `Router` with all routes and shared state.
+///
+/// Order entry (creating, amending and cancelling orders), order, balance
+/// and position queries and the account websocket must be signed with an API
+/// key, see [`authenticate`]. The `/admin` routes need the admin token, see
+/// [`authenticate_admin`].
 pub fn router(state: AppState) -> Router {
+    let order_entry = Router::new()
+        .route("/orders", post(create_order).delete(cancel_all_orders))
+        .route("/orders/{pair}", get(list_open_orders))
+        .route(
+            "/orders/batch",
+            post(create_order_batch).delete(cancel_order_batch),
+        )
+        .route(
+            "/orders/{pair}/{id}",
+            get(get_order).delete(cancel_order).patch(amend_order),
+        )
+        .route(
+            "/orders/client/{client_order_id}",
+            get(get_order_by_client_id).delete(cancel_order_by_client_id),
+        )
+        .route("/cancel-all-after", post(cancel_all_after))
+        .route("/balances", get(get_balances))
+        .route("/positions", get(get_positions))
+        .route("/ws/account", get(ws_account_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authenticate));
+    let admin = Router::new()
+        .route("/admin/instruments", post(add_instrument))
+        .route("/admin/instruments/{pair}", put(update_instrument))
+        .route("/admin/markets/{pair}/phase", put(set_market_phase))
+        .route("/admin/accounts/{account}/keys", post(create_api_key))
+        .route("/admin/accounts/{account}/deposit", post(deposit))
+        .route("/admin/accounts/{account}/withdraw", post(withdraw))
+        .route(
+            "/admin/accounts/{account}/risk-limits",
+            get(get_risk_limits).put(set_risk_limits),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            authenticate_admin,
+        ));
     let router = Router::new()
-        .route("/orders", post(create_order))
-        .route("/orders/{pair}/{id}", delete(cancel_order))
+        .merge(order_entry)
+        .merge(admin)
         .route("/trades/{pair}", get(get_trade_log))
         .route("/book/{pair}", get(get_order_book))
+        .route("/instruments", get(list_instruments))
+        .route("/markets/{pair}", get(get_market_status))
+        .route("/metrics", get(get_metrics))
+        .route("/fees/{pair}", get(get_fee_report))
         .route("/ws/{pair}", get(ws_handler))
-        .layer(middleware::from_extractor::<PairGuard>());
+        .layer(middleware::from_extractor_with_state::<PairGuard, _>(
+            state.clone(),
+        ));
 
     router
         .layer(
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::SystemTime,
+};
+
+use crate::{instrument::Pair, orders::Order};
+
+/// Outcome of a call auction if the book were uncrossed right now.
+///
+/// - `price`: the single equilibrium price every auction trade executes at
+/// - `volume`: quantity that would trade at `price`
+/// - `buy_volume` / `sell_volume`: total demand and supply willing to trade at
+///   `price`; their difference is the imbalance left in the book afterwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuctionQuote {
+    pub price: u64,
+    pub volume: u64,
+    pub buy_volume: u64,
+    pub sell_volume: u64,
+}
+
+impl AuctionQuote {
+    /// Buy surplus (positive) or sell surplus (negative) at `price`.
+    pub fn imbalance(&self) -> i128 {
+        self.buy_volume as i128 - self.sell_volume as i128
+    }
+}
+
+/// Indicative auction price and volume, pushed to websocket subscribers
+/// while a pair collects orders for a call auction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndicativeAuction {
+    pub pair: Pair,
+    /// `None` while the book is not crossed, i.e. nothing would trade.
+    pub quote: Option<AuctionQuote>,
+}
+
+/// Finds the equilibrium price of a call auction over `bids` and `asks`.
+///
+/// Every bid or ask price level is a candidate. The winner is chosen by:
+/// 1. the largest executable volume, `min(demand at or above, supply at or below)`;
+/// 2. the smallest imbalance between demand and supply at that price;
+/// 3. market pressure: the highest price if every remaining candidate has a buy
+///    surplus, the lowest if every one has a sell surplus;
+/// 4. the price closest to `reference` (usually the last trade price), or to the
+///    middle of the remaining candidates if there is none; the lower price wins ties.
+///
+/// Expired GTD orders are ignored. Returns `None` if the book does not cross.
+pub fn equilibrium(
+    bids: &BTreeMap<u64, VecDeque<Order>>,
+    asks: &BTreeMap<u64, VecDeque<Order>>,
+    reference: Option<u64>,
+) -> Option<AuctionQuote> {
+    let now = SystemTime::now();
+    let level_volume = |orders: &VecDeque<Order>| -> u64 {
+        orders
+            .iter()
+            .filter(|o| !o.is_expired(now))
+            .map(Order::remaining_quantity)
+            .sum()
+    };
+    let mut prices: Vec<u64> = bids.keys().chain(asks.keys()).copied().collect();
+    prices.sort_unstable();
+    prices.dedup();
+
+    let mut candidates: Vec<AuctionQuote> = prices
+        .into_iter()
+        .map(|price| {
+            let buy_volume = bids.range(price..).map(|(_, q)| level_volume(q)).sum();
+            let sell_volume = asks.range(..=price).map(|(_, q)| level_volume(q)).sum();
+            AuctionQuote {
+                price,
+                volume: u64::min(buy_volume, sell_volume),
+                buy_volume,
+                sell_volume,
+            }
+        })
+        .collect();
+
+    // 1. maximum executable volume
+    let best_volume = candidates.iter().map(|c| c.volume).max()?;
+    if best_volume == 0 {
+        return None;
+    }
+    candidates.retain(|c| c.volume == best_volume);
+
+    // 2. minimum imbalance
+    let least_imbalance = candidates.iter().map(|c| c.imbalance().abs()).min()?;
+    candidates.retain(|c| c.imbalance().abs() == least_imbalance);
+
+    // 3. market pressure
+    if candidates.iter().all(|c| c.imbalance() > 0) {
+        return candidates.last().copied();
+    }
+    if candidates.iter().all(|c| c.imbalance() < 0) {
+        return candidates.first().copied();
+    }
+
+    // 4. reference price, or the middle of the remaining range
+    let (low, high) = (candidates.first()?.price, candidates.last()?.price);
+    let target = reference.unwrap_or(low + (high - low) / 2);
+    candidates
+        .into_iter()
+        .min_by_key(|c| c.price.abs_diff(target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{instrument::BTC_USD, orders::Side};
+
+    fn level(quantities: &[u64]) -> VecDeque<Order> {
+        quantities
+            .iter()
+            .map(|&quantity| Order {
+                price: None,
+                ..Order::limit(0, Side::Buy, 0, quantity, BTC_USD)
+            })
+            .collect()
+    }
+
+    fn book(levels: &[(u64, u64)]) -> BTreeMap<u64, VecDeque<Order>> {
+        levels.iter().map(|&(p, q)| (p, level(&[q]))).collect()
+    }
+
+    #[test]
+    fn test_picks_price_with_max_volume() {
+        let bids = book(&[(102, 5), (101, 5), (99, 10)]);
+        let asks = book(&[(98, 4), (100, 4), (103, 10)]);
+
+        let quote = equilibrium(&bids, &asks, None).unwrap();
+        // At 100 and 101: demand 10, supply 8 -> 8 trades; 101 has the same
+        // volume and imbalance, so buy pressure picks the higher price.
+        assert_eq!(quote.volume, 8);
+        assert_eq!(quote.price, 101);
+        assert_eq!(quote.imbalance(), 2);
+    }
+
+    #[test]
+    fn test_uncrossed_book_has_no_equilibrium() {
+        let bids = book(&[(99, 5)]);
+        let asks = book(&[(100, 5)]);
+        assert_eq!(equilibrium(&bids, &asks, Some(100)), None);
+    }
+
+    #[test]
+    fn test_balanced_ties_use_reference_price() {
+        let bids = book(&[(105, 5)]);
+        let asks = book(&[(100, 5)]);
+
+        assert_eq!(equilibrium(&bids, &asks, Some(104)).unwrap().price, 105);
+        assert_eq!(equilibrium(&bids, &asks, Some(90)).unwrap().price, 100);
+        assert_eq!(equilibrium(&bids, &asks, None).unwrap().price, 100);
+    }
+}
@@ -0,0 +1,479 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::SystemTime,
+};
+use tracing::warn;
+
+use crate::{
+    errors::BalanceError,
+    fees::fee,
+    instrument::Asset,
+    orders::{AccountId, Order, OrderType, Side},
+    trade::Trade,
+};
+
+/// The venue's own account, which collects the fees funded orders pay and
+/// pays the rebates they earn.
+pub const FEE_ACCOUNT: &str = "fees";
+
+/// One account's holdings of one asset.
+///
+/// - `available`: free to trade or withdraw
+/// - `reserved`: held by the account's open orders until they fill or close
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balance {
+    pub available: u64,
+    pub reserved: u64,
+}
+
+impl Balance {
+    pub fn total(&self) -> u64 {
+        self.available + self.reserved
+    }
+}
+
+/// Why an account's balance changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub enum EntryReason {
+    Deposit,
+    Withdrawal,
+    /// Settlement of a fill of `order_id`, net of its fee.
+    Fill {
+        order_id: u128,
+    },
+    /// Net fees collected by [`FEE_ACCOUNT`] on a trade between `maker_id` and `taker_id`.
+    Fees {
+        maker_id: u128,
+        taker_id: u128,
+    },
+}
+
+/// A change to an account's total holdings of an asset.
+///
+/// Entries are persisted in the store and replayed on startup. Reservations
+/// only move funds between `available` and `reserved` and are not recorded,
+/// since the orders holding them do not survive a restart.
+#[derive(Debug, Clone, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub struct LedgerEntry {
+    pub seq: u64,
+    pub account: AccountId,
+    pub asset: String,
+    pub delta: i128,
+    pub reason: EntryReason,
+    pub timestamp: SystemTime,
+}
+
+/// Balances of every account, per [`Asset`].
+///
+/// Changes to totals are journalled as [`LedgerEntry`]s, which wait in the
+/// ledger until [`BalanceLedger::take_unsaved`] hands them to the store.
+#[derive(Debug, Default)]
+pub struct BalanceLedger {
+    accounts: HashMap<AccountId, BTreeMap<Asset, Balance>>,
+    next_seq: u64,
+    unsaved: Vec<LedgerEntry>,
+}
+
+/// The ledger shared by every book; locked only for the span of one change.
+pub type SharedBalances = Arc<Mutex<BalanceLedger>>;
+
+impl BalanceLedger {
+    /// Rebuilds balances from persisted entries, in `seq` order.
+    pub fn replay(entries: impl IntoIterator<Item = LedgerEntry>) -> Self {
+        let mut ledger = BalanceLedger::default();
+        for entry in entries {
+            let Ok(asset) = entry.asset.parse::<Asset>() else {
+                continue;
+            };
+            let balance = ledger.entry(&entry.account, &asset);
+            balance.available =
+                (balance.available as i128 + entry.delta).clamp(0, u64::MAX as i128) as u64;
+            ledger.next_seq = ledger.next_seq.max(entry.seq + 1);
+        }
+        ledger
+    }
+
+    /// `account`'s balance of `asset`; zero if it never held any.
+    pub fn balance(&self, account: &str, asset: &Asset) -> Balance {
+        self.accounts
+            .get(account)
+            .and_then(|assets| assets.get(asset))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Every asset `account` holds or has held, ordered by asset.
+    pub fn balances(&self, account: &str) -> BTreeMap<Asset, Balance> {
+        self.accounts.get(account).cloned().unwrap_or_default()
+    }
+
+    /// Credits `amount` of `asset` to `account`.
+    pub fn deposit(
+        &mut self,
+        account: &str,
+        asset: &Asset,
+        amount: u64,
+    ) -> Result<Balance, BalanceError> {
+        let balance = self.entry(account, asset);
+        balance.available = balance
+            .available
+            .checked_add(amount)
+            .filter(|_| balance.total().checked_add(amount).is_some())
+            .ok_or(BalanceError::Overflow)?;
+        let balance = *balance;
+        self.journal(account, asset, amount as i128, EntryReason::Deposit);
+        Ok(balance)
+    }
+
+    /// Debits `amount` of `asset` from `account`'s available balance.
+    pub fn withdraw(
+        &mut self,
+        account: &str,
+        asset: &Asset,
+        amount: u64,
+    ) -> Result<Balance, BalanceError> {
+        self.reserve(account, asset, amount)?;
+        let balance = self.entry(account, asset);
+        balance.reserved -= amount;
+        let balance = *balance;
+        self.journal(account, asset, -(amount as i128), EntryReason::Withdrawal);
+        Ok(balance)
+    }
+
+    /// Moves `amount` from available to reserved.
+    pub fn reserve(
+        &mut self,
+        account: &str,
+        asset: &Asset,
+        amount: u64,
+    ) -> Result<(), BalanceError> {
+        let balance = self.entry(account, asset);
+        if balance.available < amount {
+            return Err(BalanceError::InsufficientFunds {
+                asset: asset.clone(),
+                required: amount,
+                available: balance.available,
+            });
+        }
+        balance.available -= amount;
+        balance.reserved += amount;
+        Ok(())
+    }
+
+    /// Moves `amount` from reserved back to available.
+    pub fn release(&mut self, account: &str, asset: &Asset, amount: u64) {
+        let balance = self.entry(account, asset);
+        let amount = amount.min(balance.reserved);
+        balance.reserved -= amount;
+        balance.available += amount;
+    }
+
+    /// Applies one side of a fill of `order_id`: `spent` leaves the reserved
+    /// balance of `paid`, `received` is credited to the available balance of `got`.
+    fn settle(
+        &mut self,
+        account: &str,
+        order_id: u128,
+        (paid, spent): (&Asset, u64),
+        (got, received): (&Asset, u64),
+    ) {
+        let balance = self.entry(account, paid);
+        balance.reserved = balance.reserved.saturating_sub(spent);
+        let balance = self.entry(account, got);
+        balance.available = balance.available.saturating_add(received);
+        let reason = EntryReason::Fill { order_id };
+        self.journal(account, paid, -(spent as i128), reason);
+        self.journal(account, got, received as i128, reason);
+    }
+
+    /// Credits the net `fees` of a trade to [`FEE_ACCOUNT`]; a negative amount
+    /// pays out rebates from it.
+    fn collect_fees(&mut self, trade: &Trade, asset: &Asset, fees: i64) {
+        let balance = self.entry(FEE_ACCOUNT, asset);
+        let before = balance.available;
+        balance.available = (before as i128 + fees as i128).clamp(0, u64::MAX as i128) as u64;
+        let delta = balance.available as i128 - before as i128;
+        if delta != fees as i128 {
+            warn!("fee account short of {} rebates", asset);
+        }
+        if delta != 0 {
+            let reason = EntryReason::Fees {
+                maker_id: trade.maker_id,
+                taker_id: trade.taker_id,
+            };
+            self.journal(FEE_ACCOUNT, asset, delta, reason);
+        }
+    }
+
+    /// Entries recorded since the last call, oldest first.
+    pub fn take_unsaved(&mut self) -> Vec<LedgerEntry> {
+        std::mem::take(&mut self.unsaved)
+    }
+
+    fn entry(&mut self, account: &str, asset: &Asset) -> &mut Balance {
+        self.accounts
+            .entry(account.to_string())
+            .or_default()
+            .entry(asset.clone())
+            .or_default()
+    }
+
+    fn journal(&mut self, account: &str, asset: &Asset, delta: i128, reason: EntryReason) {
+        self.unsaved.push(LedgerEntry {
+            seq: self.next_seq,
+            account: account.to_string(),
+            asset: asset.code().to_string(),
+            delta,
+            reason,
+            timestamp: SystemTime::now(),
+        });
+        self.next_seq += 1;
+    }
+}
+
+/// Funds one open order holds in the ledger.
+#[derive(Debug, Clone)]
+struct Hold {
+    account: AccountId,
+    asset: Asset,
+    amount: u64,
+}
+
+/// Quote a buy of `quantity` at `price` can cost, fee at `fee_bps` included.
+pub fn max_cost(price: u64, quantity: u64, fee_bps: i64) -> u64 {
+    let notional = price as u128 * quantity as u128;
+    let cost = notional as i128 + fee(notional, fee_bps.max(0)) as i128;
+    u64::try_from(cost).unwrap_or(u64::MAX)
+}
+
+/// How many units at `price` a `budget` of quote pays for, fee at `fee_bps` included.
+pub fn affordable_quantity(budget: u64, price: u64, fee_bps: i64) -> u64 {
+    if price == 0 {
+        return u64::MAX;
+    }
+    let per_unit = price as u128 * (10_000 + fee_bps.max(0)) as u128;
+    let mut quantity = (budget as u128 * 10_000 / per_unit).min(u64::MAX as u128) as u64;
+    // Fees truncate, so a slightly larger quantity may still fit
+    while quantity < u64::MAX && max_cost(price, quantity + 1, fee_bps) <= budget {
+        quantity += 1;
+    }
+    quantity
+}
+
+/// The funds an [`crate::orderbook::OrderBook`]'s open orders hold against the
+/// shared [`BalanceLedger`].
+///
+/// Buys hold quote: their notional at the limit price plus the highest fee the
+/// pair could charge. Market buys hold the account's whole available quote
+/// until they finish; plain stop buys hold nothing until they fire. Sells hold
+/// the base quantity they offer. Holds shrink as orders fill and whatever is
+/// left returns to the account when the order closes.
+///
+/// A book without a ledger, and orders without an owner, are not funded.
+#[derive(Debug, Clone, Default)]
+pub struct Funds {
+    ledger: Option<SharedBalances>,
+    holds: HashMap<u128, Hold>,
+}
+
+impl Funds {
+    pub fn new(ledger: SharedBalances) -> Self {
+        Funds {
+            ledger: Some(ledger),
+            holds: HashMap::new(),
+        }
+    }
+
+    fn lock(ledger: &SharedBalances) -> MutexGuard<'_, BalanceLedger> {
+        ledger.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// What `order` must hold, as `(account, asset, amount)`; `None` if it is unfunded.
+    fn requirement(
+        &self,
+        order: &Order,
+        ledger: &BalanceLedger,
+        fee_bps: i64,
+    ) -> Option<(AccountId, Asset, u64)> {
+        let account = order.owner.clone()?;
+        let quantity = order.remaining_quantity();
+        match (order.side, order.order_type, order.price) {
+            (Side::Sell, ..) => Some((account, order.pair.base.clone(), quantity)),
+            (Side::Buy, OrderType::Stop, _) => None,
+            (Side::Buy, _, Some(price)) => Some((
+                account,
+                order.pair.quote.clone(),
+                max_cost(price, quantity, fee_bps),
+            )),
+            (Side::Buy, _, None) => {
+                let quote = order.pair.quote.clone();
+                // With nothing available, ask for a unit so the reservation fails
+                let available = ledger.balance(&account, &quote).available.max(1);
+                Some((account, quote, available))
+            }
+        }
+    }
+
+    /// Reserves what `order` needs, unless it already holds funds.
+    ///
+    /// Fails with [`BalanceError::InsufficientFunds`] if the owner cannot cover it.
+    pub fn reserve(&mut self, order: &Order, fee_bps: i64) -> Result<(), BalanceError> {
+        let Some(shared) = &self.ledger else {
+            return Ok(());
+        };
+        if self.holds.contains_key(&order.id) {
+            return Ok(());
+        }
+        let mut ledger = Self::lock(shared);
+        let Some((account, asset, amount)) = self.requirement(order, &ledger, fee_bps) else {
+            return Ok(());
+        };
+        ledger.reserve(&account, &asset, amount)?;
+        self.holds.insert(
+            order.id,
+            Hold {
+                account,
+                asset,
+                amount,
+            },
+        );
+        Ok(())
+    }
+
+    /// Swaps the hold of an order being amended to what `amended` needs.
+    /// On failure the original hold is kept.
+    pub fn rebook(&mut self, amended: &Order, fee_bps: i64) -> Result<(), BalanceError> {
+        let Some(previous) = self.holds.remove(&amended.id) else {
+            return self.reserve(amended, fee_bps);
+        };
+        if let Some(shared) = &self.ledger {
+            Self::lock(shared).release(&previous.account, &previous.asset, previous.amount);
+        }
+        self.reserve(amended, fee_bps).inspect_err(|_| {
+            if let Some(shared) = &self.ledger {
+                let mut ledger = Self::lock(shared);
+                // Just released, so it is still available
+                let _ = ledger.reserve(&previous.account, &previous.asset, previous.amount);
+            }
+            self.holds.insert(amended.id, previous.clone());
+        })
+    }
+
+    /// Returns anything `order` holds beyond what it still needs, e.g. after
+    /// its quantity was reduced in place.
+    pub fn trim(&mut self, order: &Order, fee_bps: i64) {
+        let (Some(shared), Some(hold)) = (&self.ledger, self.holds.get_mut(&order.id)) else {
+            return;
+        };
+        let mut ledger = Self::lock(shared);
+        let needed = match (order.side, order.price) {
+            (Side::Buy, Some(price)) => max_cost(price, order.remaining_quantity(), fee_bps),
+            (Side::Sell, _) => order.remaining_quantity(),
+            (Side::Buy, None) => return,
+        };
+        if hold.amount > needed {
+            ledger.release(&hold.account, &hold.asset, hold.amount - needed);
+            hold.amount = needed;
+        }
+    }
+
+    /// Returns everything `order_id` still holds to its account.
+    pub fn release(&mut self, order_id: u128) {
+        let (Some(shared), Some(hold)) = (&self.ledger, self.holds.remove(&order_id)) else {
+            return;
+        };
+        Self::lock(shared).release(&hold.account, &hold.asset, hold.amount);
+    }
+
+    /// What `order` (a buy) still holds to spend; `None` if it is not funded.
+    pub fn spendable(&self, order: &Order) -> Option<u64> {
+        let hold = self.holds.get(&order.id)?;
+        (order.side == Side::Buy).then_some(hold.amount)
+    }
+
+    /// How much of `order` (a buy) its hold can pay for at `price`, fee at
+    /// `fee_bps` included; `None` if it is not funded.
+    pub fn affordable(&self, order: &Order, price: u64, fee_bps: i64) -> Option<u64> {
+        let budget = self.spendable(order)?;
+        (price > 0).then(|| affordable_quantity(budget, price, fee_bps))
+    }
+
+    /// Settles `trade` between `maker` and `taker`: the buyer pays the
+    /// notional plus its fee out of its hold and receives the base; the seller
+    /// delivers the base out of its hold and receives the notional less its fee.
+    /// The fees of funded orders go to [`FEE_ACCOUNT`].
+    ///
+    /// Matching sizes every fill so that it fits both holds (see [`Funds::affordable`]).
+    pub fn settle(&mut self, trade: &Trade, maker: &Order, taker: &Order) {
+        let Some(shared) = &self.ledger else {
+            return;
+        };
+        let mut ledger = Self::lock(shared);
+        let notional = trade.price as i128 * trade.quantity as i128;
+        let mut fees = 0;
+        for (order, fee) in [(maker, trade.maker_fee), (taker, trade.taker_fee)] {
+            let Some(hold) = self.holds.get_mut(&order.id) else {
+                continue;
+            };
+            fees += fee;
+            let (base, quote) = (&order.pair.base, &order.pair.quote);
+            let (paid, got) = match order.side {
+                Side::Buy => {
+                    let cost = (notional + fee as i128).clamp(0, u64::MAX as i128) as u64;
+                    ((quote, cost), (base, trade.quantity))
+                }
+                Side::Sell => {
+                    let proceeds = (notional - fee as i128).clamp(0, u64::MAX as i128) as u64;
+                    ((base, trade.quantity), (quote, proceeds))
+                }
+            };
+            hold.amount = hold
+                .amount
+                .checked_sub(paid.1)
+                .expect("fills never cost more than the order holds");
+            ledger.settle(&hold.account, order.id, paid, got);
+        }
+        ledger.collect_fees(trade, &maker.pair.quote, fees);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deposit_reserve_withdraw_and_replay() {
+        let mut ledger = BalanceLedger::default();
+        ledger.deposit("alice", &Asset::USD, 1_000).unwrap();
+        ledger.reserve("alice", &Asset::USD, 600).unwrap();
+        assert_eq!(
+            ledger.withdraw("alice", &Asset::USD, 500),
+            Err(BalanceError::InsufficientFunds {
+                asset: Asset::USD,
+                required: 500,
+                available: 400
+            })
+        );
+        ledger.release("alice", &Asset::USD, 600);
+        let balance = ledger.withdraw("alice", &Asset::USD, 500).unwrap();
+        assert_eq!(
+            balance,
+            Balance {
+                available: 500,
+                reserved: 0
+            }
+        );
+
+        let entries = ledger.take_unsaved();
+        assert_eq!(
+            entries.iter().map(|e| e.delta).collect::<Vec<_>>(),
+            vec![1_000, -500],
+            "reservations are not journalled"
+        );
+        let replayed = BalanceLedger::replay(entries);
+        assert_eq!(replayed.balance("alice", &Asset::USD).available, 500);
+        assert_eq!(replayed.next_seq, 2);
+    }
+}
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    instrument::Pair,
+    orderbook::{BookSnapshot, OrderBook},
+    orders::Side,
+};
+
+/// A price level whose total visible quantity changed; `quantity` 0 removes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelUpdate {
+    pub side: Side,
+    pub price: u64,
+    pub quantity: u64,
+}
+
+/// The levels of `pair` that changed from sequence number `seq - 1` to `seq`.
+///
+/// Applied in order on top of a [`BookSnapshot`] of the same pair, deltas keep
+/// a local copy of the book's visible depth; see [`BookSnapshot::apply`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookDelta {
+    pub pair: Pair,
+    pub seq: u64,
+    pub changes: Vec<LevelUpdate>,
+}
+
+/// The depth last published for every pair, numbered per pair from 0 (an
+/// empty book). The number goes up by one with each delta, so a client that
+/// sees it skip knows it missed one.
+#[derive(Debug, Default)]
+pub struct DepthFeed {
+    published: HashMap<Pair, BookSnapshot>,
+}
+
+impl DepthFeed {
+    /// Compares `book` with the depth last published for `pair`.
+    ///
+    /// Returns the book's snapshot, numbered, and the delta that brings the
+    /// last published depth up to it, if any level changed.
+    pub fn publish(&mut self, pair: &Pair, book: &OrderBook) -> (BookSnapshot, Option<BookDelta>) {
+        let mut current = BookSnapshot::for_pair(pair.clone(), book);
+        let published = self
+            .published
+            .entry(pair.clone())
+            .or_insert_with(|| BookSnapshot::empty(pair.clone()));
+        let mut changes = level_changes(Side::Buy, &published.bids, &current.bids);
+        changes.extend(level_changes(Side::Sell, &published.asks, &current.asks));
+        if changes.is_empty() {
+            current.seq = published.seq;
+            return (current, None);
+        }
+        current.seq = published.seq + 1;
+        *published = current.clone();
+        let delta = BookDelta {
+            pair: pair.clone(),
+            seq: current.seq,
+            changes,
+        };
+        (current, Some(delta))
+    }
+}
+
+/// The levels of one side that differ between `before` and `after`.
+fn level_changes(side: Side, before: &[(u64, u64)], after: &[(u64, u64)]) -> Vec<LevelUpdate> {
+    let before: BTreeMap<u64, u64> = before.iter().copied().collect();
+    let after: BTreeMap<u64, u64> = after.iter().copied().collect();
+    let removed = before
+        .keys()
+        .filter(|price| !after.contains_key(price))
+        .map(|&price| (price, 0));
+    let changed = after
+        .iter()
+        .filter(|(price, quantity)| before.get(price) != Some(quantity))
+        .map(|(&price, &quantity)| (price, quantity));
+    removed
+        .chain(changed)
+        .map(|(price, quantity)| LevelUpdate {
+            side,
+            price,
+            quantity,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{instrument::BTC_USD, orders::Order};
+
+    fn limit_order(id: u128, side: Side, price: u64, quantity: u64) -> Order {
+        Order::limit(id, side, price, quantity, BTC_USD)
+    }
+
+    #[test]
+    fn test_deltas_rebuild_the_published_book() {
+        let mut book = OrderBook::new();
+        let mut feed = DepthFeed::default();
+        let (mut local, delta) = feed.publish(&BTC_USD, &book);
+        assert_eq!(local.seq, 0);
+        assert!(delta.is_none());
+
+        book.add_order(limit_order(1, Side::Buy, 100, 4));
+        book.add_order(limit_order(2, Side::Buy, 99, 1));
+        book.add_order(limit_order(3, Side::Sell, 105, 2));
+        let (_, first) = feed.publish(&BTC_USD, &book);
+        let first = first.unwrap();
+        assert_eq!(first.seq, 1);
+        assert_eq!(first.changes.len(), 3);
+
+        // Unchanged levels publish nothing and keep the number
+        let (snapshot, none) = feed.publish(&BTC_USD, &book);
+        assert!(none.is_none());
+        assert_eq!(snapshot.seq, 1);
+
+        book.add_order(limit_order(4, Side::Buy, 100, 3));
+        assert!(book.cancel_order(2));
+        let (snapshot, second) = feed.publish(&BTC_USD, &book);
+        let second = second.unwrap();
+        assert_eq!(second.seq, 2);
+        assert_eq!(
+            second.changes,
+            vec![
+                LevelUpdate {
+                    side: Side::Buy,
+                    price: 99,
+                    quantity: 0
+                },
+                LevelUpdate {
+                    side: Side::Buy,
+                    price: 100,
+                    quantity: 7
+                },
+            ]
+        );
+
+        // Skipping a delta is a gap; applying them in order catches up
+        assert!(!local.apply(&second));
+        assert!(local.apply(&first));
+        assert!(local.apply(&first), "stale deltas are ignored");
+        assert!(local.apply(&second));
+        assert_eq!(local.seq, 2);
+        assert_eq!(local.bids, snapshot.bids);
+        assert_eq!(local.asks, snapshot.asks);
+    }
+}
@@ -1,7 +1,136 @@
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::{instrument::Asset, session::TradingPhase};
+
 #[derive(Error, Debug)]
 pub enum MarketMakerError {
     #[error("connection error")]
     ConnectError(String),
 }
+
+/// Reasons the matching engine refuses an order outright.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum OrderError {
+    #[error("post-only order would take liquidity")]
+    PostOnlyWouldCross,
+    #[error("order not found")]
+    UnknownOrder,
+    #[error("order not accepted while the market is {0:?}")]
+    NotAcceptedInPhase(TradingPhase),
+    #[error("cancels not accepted while the market is {0:?}")]
+    CancelNotAcceptedInPhase(TradingPhase),
+    #[error("order belongs to another account")]
+    NotOwner,
+    #[error(transparent)]
+    Funds(#[from] BalanceError),
+    #[error("price {price} is outside the price band [{low}, {high}]")]
+    PriceOutsideBand { price: u64, low: u64, high: u64 },
+    #[error("market cannot move from {from:?} to {to:?}")]
+    InvalidPhaseTransition {
+        from: TradingPhase,
+        to: TradingPhase,
+    },
+}
+
+/// Ways an order can break its instrument's trading rules.
+///
+/// Serialized with a `reason` tag next to the offending and allowed values, e.g.
+/// `{"reason": "price_not_on_tick", "price": 101, "tick_size": 5}`.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SpecViolation {
+    #[error("price {price} is not a positive multiple of tick size {tick_size}")]
+    PriceNotOnTick { price: u64, tick_size: u64 },
+    #[error("price {price} is above max price {max_price}")]
+    PriceAboveMax { price: u64, max_price: u64 },
+    #[error("quantity {quantity} is not a multiple of lot size {lot_size}")]
+    QuantityNotOnLot { quantity: u64, lot_size: u64 },
+    #[error("quantity {quantity} is below min quantity {min_quantity}")]
+    QuantityBelowMin { quantity: u64, min_quantity: u64 },
+    #[error("quantity {quantity} is above max quantity {max_quantity}")]
+    QuantityAboveMax { quantity: u64, max_quantity: u64 },
+    #[error("notional {notional} is below min notional {min_notional}")]
+    NotionalBelowMin { notional: u64, min_notional: u64 },
+}
+
+/// Pre-trade risk limits an order would break, see [`crate::risk::RiskLimits`].
+///
+/// Serialized with a `code` tag next to the offending and allowed values, e.g.
+/// `{"code": "max_open_orders", "open": 5, "limit": 5}`.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum RiskViolation {
+    #[error("quantity {quantity} is above the account's max order quantity {limit}")]
+    MaxOrderQuantity { quantity: u64, limit: u64 },
+    #[error("notional {notional} is above the account's max order notional {limit}")]
+    MaxOrderNotional { notional: u64, limit: u64 },
+    #[error("account already has {open} open orders in the pair, the max is {limit}")]
+    MaxOpenOrders { open: usize, limit: usize },
+    #[error("net position could reach {position}, beyond the account's max of {limit}")]
+    MaxNetPosition { position: i64, limit: u64 },
+    #[error("more than {limit} orders per second")]
+    MaxOrderRate { limit: u32 },
+}
+
+impl RiskViolation {
+    /// The machine-readable code the violation is serialized with.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RiskViolation::MaxOrderQuantity { .. } => "max_order_quantity",
+            RiskViolation::MaxOrderNotional { .. } => "max_order_notional",
+            RiskViolation::MaxOpenOrders { .. } => "max_open_orders",
+            RiskViolation::MaxNetPosition { .. } => "max_net_position",
+            RiskViolation::MaxOrderRate { .. } => "max_order_rate",
+        }
+    }
+}
+
+/// Failures loading or changing the instrument registry.
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("could not read instrument file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid instrument file: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("pair `{0}` is already listed")]
+    AlreadyListed(String),
+    #[error("pair `{0}` is not listed")]
+    NotListed(String),
+    #[error("invalid instrument spec: {0}")]
+    InvalidSpec(&'static str),
+}
+
+/// Reasons a request to an authenticated route is refused.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    #[error("missing `{0}` header")]
+    MissingHeader(&'static str),
+    #[error("invalid `{0}` header")]
+    InvalidHeader(&'static str),
+    #[error("unknown API key")]
+    UnknownKey,
+    #[error("request timestamp is too far from the server clock")]
+    StaleTimestamp,
+    #[error("nonce has already been used")]
+    ReplayedNonce,
+    #[error("signature does not match the request")]
+    BadSignature,
+    #[error("invalid admin token")]
+    BadAdminToken,
+    #[error("the admin API is disabled; no admin token is configured")]
+    AdminDisabled,
+}
+
+/// Reasons a change to an account's balances is refused.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BalanceError {
+    #[error("insufficient {asset}: {required} required, {available} available")]
+    InsufficientFunds {
+        asset: Asset,
+        required: u64,
+        available: u64,
+    },
+    #[error("balance would overflow")]
+    Overflow,
+}
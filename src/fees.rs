@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{errors::RegistryError, instrument::Pair, orders::AccountId, trade::Trade};
+
+/// Maker and taker fee rates in basis points of a trade's notional
+/// (`price * quantity`). A negative rate is a rebate.
+///
+/// `tiers` lower the rates for accounts that have traded more: the tier with
+/// the highest `min_volume` at or below the account's volume applies, falling
+/// back to the base rates.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeeSchedule {
+    pub maker_bps: i64,
+    pub taker_bps: i64,
+    pub tiers: Vec<FeeTier>,
+}
+
+/// Rates for accounts whose traded notional on the pair is at least `min_volume`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeTier {
+    pub min_volume: u64,
+    pub maker_bps: i64,
+    pub taker_bps: i64,
+}
+
+impl FeeSchedule {
+    /// The `(maker_bps, taker_bps)` an account with `volume` traded pays.
+    pub fn rates_for(&self, volume: u64) -> (i64, i64) {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.min_volume <= volume)
+            .max_by_key(|tier| tier.min_volume)
+            .map_or((self.maker_bps, self.taker_bps), |tier| {
+                (tier.maker_bps, tier.taker_bps)
+            })
+    }
+
+    /// Checks that rates stay within ±100% and that no trade pays out more
+    /// in maker rebates than it charges its taker, whatever tiers the two
+    /// sides are in.
+    pub fn check_valid(&self) -> Result<(), RegistryError> {
+        let rates = std::iter::once((self.maker_bps, self.taker_bps))
+            .chain(self.tiers.iter().map(|t| (t.maker_bps, t.taker_bps)));
+        let (mut lowest_maker, mut lowest_taker) = (i64::MAX, i64::MAX);
+        for (maker, taker) in rates {
+            if maker.abs() > 10_000 || taker.abs() > 10_000 {
+                return Err(RegistryError::InvalidSpec(
+                    "fee rates must be within ±10000 bps",
+                ));
+            }
+            lowest_maker = lowest_maker.min(maker);
+            lowest_taker = lowest_taker.min(taker);
+        }
+        if lowest_maker + lowest_taker < 0 {
+            return Err(RegistryError::InvalidSpec(
+                "maker rebates must not exceed taker fees",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Fee for `notional` at `bps`, truncated towards zero.
+pub fn fee(notional: u128, bps: i64) -> i64 {
+    let fee = notional as i128 * bps as i128 / 10_000;
+    fee.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// What one account has traded and paid on a pair.
+///
+/// Fees are positive when paid and negative when received as rebates.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountFees {
+    pub volume: u64,
+    pub maker_fees: i64,
+    pub taker_fees: i64,
+}
+
+/// Charges a pair's [`FeeSchedule`] on its trades as they happen and keeps the
+/// per-account volume and fee totals tiers and reports are based on.
+#[derive(Debug, Clone, Default)]
+pub struct FeeLedger {
+    schedule: FeeSchedule,
+    accounts: HashMap<AccountId, AccountFees>,
+    totals: AccountFees,
+}
+
+impl FeeLedger {
+    /// Replaces the schedule; applies to trades from now on.
+    pub fn set_schedule(&mut self, schedule: FeeSchedule) {
+        self.schedule = schedule;
+    }
+
+    /// The highest rate any account can be charged, or 0 if every rate is a rebate.
+    pub fn max_bps(&self) -> i64 {
+        let schedule = &self.schedule;
+        std::iter::once((schedule.maker_bps, schedule.taker_bps))
+            .chain(schedule.tiers.iter().map(|t| (t.maker_bps, t.taker_bps)))
+            .map(|(maker, taker)| maker.max(taker))
+            .fold(0, i64::max)
+    }
+
+    /// Sets `trade.maker_fee` and `trade.taker_fee` from each side's current
+    /// tier, then adds the trade to both accounts' volume and fee totals.
+    pub fn charge(
+        &mut self,
+        trade: &mut Trade,
+        maker: Option<&AccountId>,
+        taker: Option<&AccountId>,
+    ) {
+        let notional = trade.price as u128 * trade.quantity as u128;
+        let volume_of = |account: Option<&AccountId>| {
+            account
+                .and_then(|a| self.accounts.get(a))
+                .map_or(0, |totals| totals.volume)
+        };
+        let (maker_bps, _) = self.schedule.rates_for(volume_of(maker));
+        let (_, taker_bps) = self.schedule.rates_for(volume_of(taker));
+        trade.maker_fee = fee(notional, maker_bps);
+        trade.taker_fee = fee(notional, taker_bps);
+
+        let traded = u64::try_from(notional).unwrap_or(u64::MAX);
+        self.totals.volume = self.totals.volume.saturating_add(traded);
+        self.totals.maker_fees += trade.maker_fee;
+        self.totals.taker_fees += trade.taker_fee;
+        if let Some(account) = maker {
+            let totals = self.accounts.entry(account.clone()).or_default();
+            totals.volume = totals.volume.saturating_add(traded);
+            totals.maker_fees += trade.maker_fee;
+        }
+        if let Some(account) = taker {
+            let totals = self.accounts.entry(account.clone()).or_default();
+            totals.volume = totals.volume.saturating_add(traded);
+            totals.taker_fees += trade.taker_fee;
+        }
+    }
+
+    /// Fee totals for the pair, overall and per account.
+    pub fn report(&self, pair: Pair) -> FeeReport {
+        FeeReport {
+            pair,
+            schedule: self.schedule.clone(),
+            volume: self.totals.volume,
+            maker_fees: self.totals.maker_fees,
+            taker_fees: self.totals.taker_fees,
+            net_fees: self.totals.maker_fees + self.totals.taker_fees,
+            accounts: self
+                .accounts
+                .iter()
+                .map(|(account, totals)| (account.clone(), totals.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Response for `GET /fees/{pair}`: fees charged on the pair since startup.
+///
+/// - `volume`: traded notional
+/// - `net_fees`: what the venue kept, taker fees plus (possibly negative) maker fees
+/// - `accounts`: the same totals per account, ordered by account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeReport {
+    pub pair: Pair,
+    pub schedule: FeeSchedule,
+    pub volume: u64,
+    pub maker_fees: i64,
+    pub taker_fees: i64,
+    pub net_fees: i64,
+    pub accounts: BTreeMap<AccountId, AccountFees>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn trade(price: u64, quantity: u64) -> Trade {
+        Trade {
+            price,
+            quantity,
+            maker_id: 1,
+            taker_id: 2,
+            timestamp: SystemTime::now(),
+            symbol: "BTC-USD".into(),
+            maker_fee: 0,
+            taker_fee: 0,
+        }
+    }
+
+    #[test]
+    fn test_tiers_apply_by_volume_and_rebates_are_negative() {
+        let mut ledger = FeeLedger::default();
+        ledger.set_schedule(FeeSchedule {
+            maker_bps: 10,
+            taker_bps: 20,
+            tiers: vec![FeeTier {
+                min_volume: 10_000,
+                maker_bps: -5,
+                taker_bps: 15,
+            }],
+        });
+        let (maker, taker) = ("mm".to_string(), "taker".to_string());
+
+        let mut first = trade(100, 100);
+        ledger.charge(&mut first, Some(&maker), Some(&taker));
+        assert_eq!((first.maker_fee, first.taker_fee), (10, 20));
+
+        // Both accounts now have 10_000 of volume and reach the tier
+        let mut second = trade(100, 100);
+        ledger.charge(&mut second, Some(&maker), Some(&taker));
+        assert_eq!((second.maker_fee, second.taker_fee), (-5, 15));
+
+        let report = ledger.report(crate::instrument::BTC_USD);
+        assert_eq!(report.volume, 20_000);
+        assert_eq!(report.net_fees, 40);
+        assert_eq!(report.accounts["mm"].maker_fees, 5);
+        assert_eq!(report.accounts["taker"].taker_fees, 35);
+    }
+
+    #[test]
+    fn test_schedule_rejects_net_payouts() {
+        let schedule = FeeSchedule {
+            maker_bps: -10,
+            taker_bps: 5,
+            tiers: Vec::new(),
+        };
+        assert!(schedule.check_valid().is_err());
+        assert!(FeeSchedule::default().check_valid().is_ok());
+
+        // Each tier nets out positive, but a tier's rebate beats the base taker fee
+        let across_tiers = FeeSchedule {
+            maker_bps: 0,
+            taker_bps: 5,
+            tiers: vec![FeeTier {
+                min_volume: 10_000,
+                maker_bps: -10,
+                taker_bps: 15,
+            }],
+        };
+        assert!(across_tiers.check_valid().is_err());
+    }
+}
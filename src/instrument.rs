@@ -1,41 +1,74 @@
 use serde::{Deserialize, Serialize};
-use std::{fmt, str::FromStr};
+use std::{borrow::Cow, collections::BTreeMap, fmt, path::Path, str::FromStr};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum Asset {
-    BTC,
-    USD,
-    ETH,
+use crate::{
+    errors::{RegistryError, SpecViolation},
+    fees::FeeSchedule,
+};
+
+/// A tradable asset, identified by its code (e.g. `BTC`, `USD`, `SOL`).
+///
+/// Codes are 1–10 uppercase ASCII letters or digits. Any well-formed code is
+/// an `Asset`; which pairs actually trade is decided by the [`InstrumentRegistry`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Asset(Cow<'static, str>);
+
+impl Asset {
+    pub const BTC: Asset = Asset(Cow::Borrowed("BTC"));
+    pub const ETH: Asset = Asset(Cow::Borrowed("ETH"));
+    pub const USD: Asset = Asset(Cow::Borrowed("USD"));
+
+    pub fn code(&self) -> &str {
+        &self.0
+    }
 }
 
 impl fmt::Display for Asset {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(match self {
-            Asset::BTC => "BTC",
-            Asset::USD => "USD",
-            Asset::ETH => "ETH",
-        })
+        f.write_str(&self.0)
     }
 }
 impl FromStr for Asset {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "BTC" => Ok(Asset::BTC),
-            "ETH" => Ok(Asset::ETH),
-            "USD" => Ok(Asset::USD),
-            _ => Err(format!("unknown asset `{s}`")),
+        let well_formed = (1..=10).contains(&s.len())
+            && s.bytes()
+                .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit());
+        if !well_formed {
+            return Err(format!("invalid asset `{s}`"));
         }
+        Ok(Asset(Cow::Owned(s.to_string())))
+    }
+}
+
+// Glue for #[serde(try_from, into)]
+impl TryFrom<String> for Asset {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+impl From<Asset> for String {
+    fn from(a: Asset) -> Self {
+        a.0.into_owned()
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+/// A market between two assets, written `BASE-QUOTE` (e.g. `BTC-USD`, `ETH-BTC`).
+///
+/// Parsing only checks the format; whether a pair is listed is up to the
+/// [`InstrumentRegistry`].
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(try_from = "&'de str", into = "String")]
 pub struct Pair {
     pub base: Asset,
     pub quote: Asset,
 }
 
+/// Pairs listed when no instrument file is given.
+static BUILT_IN: [Pair; 2] = [BTC_USD, ETH_USD];
+
 impl Pair {
     pub fn code(&self) -> String {
         self.to_string()
@@ -46,8 +79,174 @@ impl Pair {
             quote: Asset::USD,
         }
     }
+    /// The built-in pairs the default [`InstrumentRegistry`] lists.
     pub fn supported() -> &'static [Pair] {
-        &[BTC_USD, ETH_USD]
+        &BUILT_IN
+    }
+}
+
+/// Trading rules for a [`Pair`], published on `GET /instruments` and
+/// enforced on order entry.
+///
+/// - `tick_size`: prices (limit and trigger) must be a multiple of this
+/// - `lot_size`: quantities (and iceberg display quantities) must be a multiple of this
+/// - `min_quantity` / `max_quantity`: bounds on an order's total quantity
+/// - `min_notional`: lowest `price * quantity` a priced order may have
+/// - `max_price`: highest accepted limit or trigger price
+/// - `price_bands`: circuit breaker limits; off unless configured
+/// - `fees`: maker/taker fee schedule; free unless configured
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstrumentSpec {
+    pub pair: Pair,
+    pub tick_size: u64,
+    pub lot_size: u64,
+    pub min_quantity: u64,
+    pub max_quantity: u64,
+    pub min_notional: u64,
+    pub max_price: u64,
+    #[serde(default)]
+    pub price_bands: PriceBands,
+    #[serde(default)]
+    pub fees: FeeSchedule,
+}
+
+/// Volatility circuit breaker for a pair, in basis points around an anchor price.
+///
+/// - `static_bps`: max distance from the reference price (the last auction
+///   price, or the first trade if no auction has run)
+/// - `dynamic_bps`: max distance from the last trade price
+/// - `auction_secs`: how long the volatility auction started by a breach
+///   collects orders before the pair reopens
+///
+/// While trading continuously, limit orders priced outside the bands are
+/// rejected and no trade executes outside them; an order that would trade
+/// through a band halts matching and starts a volatility auction instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PriceBands {
+    pub static_bps: Option<u64>,
+    pub dynamic_bps: Option<u64>,
+    pub auction_secs: u64,
+}
+
+impl Default for PriceBands {
+    /// No bands; a breach-triggered auction would last 30 seconds.
+    fn default() -> Self {
+        PriceBands {
+            static_bps: None,
+            dynamic_bps: None,
+            auction_secs: 30,
+        }
+    }
+}
+
+impl PriceBands {
+    /// The `(low, high)` prices trades may execute at, intersecting the static
+    /// band around `reference` and the dynamic band around `last_trade`.
+    ///
+    /// `None` if no band applies (unset, or no anchor price yet).
+    pub fn limits(&self, reference: Option<u64>, last_trade: Option<u64>) -> Option<(u64, u64)> {
+        let band = |bps: Option<u64>, anchor: Option<u64>| {
+            let (bps, anchor) = (bps?, anchor?);
+            let width = (anchor as u128 * bps as u128 / 10_000) as u64;
+            Some((anchor.saturating_sub(width), anchor.saturating_add(width)))
+        };
+        match (
+            band(self.static_bps, reference),
+            band(self.dynamic_bps, last_trade),
+        ) {
+            (Some((lo1, hi1)), Some((lo2, hi2))) => Some((lo1.max(lo2), hi1.min(hi2))),
+            (one, other) => one.or(other),
+        }
+    }
+}
+
+impl InstrumentSpec {
+    /// Default rules: whole-unit ticks and lots, up to a million units per order.
+    pub fn new(pair: Pair) -> Self {
+        InstrumentSpec {
+            pair,
+            tick_size: 1,
+            lot_size: 1,
+            min_quantity: 1,
+            max_quantity: 1_000_000,
+            min_notional: 1,
+            max_price: 1_000_000_000,
+            price_bands: PriceBands::default(),
+            fees: FeeSchedule::default(),
+        }
+    }
+
+    /// Checks that the rules themselves are usable.
+    pub fn check_valid(&self) -> Result<(), RegistryError> {
+        let problem = if self.tick_size == 0 {
+            "tick_size must be > 0"
+        } else if self.lot_size == 0 {
+            "lot_size must be > 0"
+        } else if self.min_quantity > self.max_quantity {
+            "min_quantity must be <= max_quantity"
+        } else {
+            return self.fees.check_valid();
+        };
+        Err(RegistryError::InvalidSpec(problem))
+    }
+
+    /// Checks a price (limit or trigger) against `tick_size` and `max_price`.
+    pub fn check_price(&self, price: u64) -> Result<(), SpecViolation> {
+        if price == 0 || !price.is_multiple_of(self.tick_size) {
+            return Err(SpecViolation::PriceNotOnTick {
+                price,
+                tick_size: self.tick_size,
+            });
+        }
+        if price > self.max_price {
+            return Err(SpecViolation::PriceAboveMax {
+                price,
+                max_price: self.max_price,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks a quantity against `lot_size` and the min/max bounds.
+    pub fn check_quantity(&self, quantity: u64) -> Result<(), SpecViolation> {
+        self.check_lot(quantity)?;
+        if quantity < self.min_quantity {
+            return Err(SpecViolation::QuantityBelowMin {
+                quantity,
+                min_quantity: self.min_quantity,
+            });
+        }
+        if quantity > self.max_quantity {
+            return Err(SpecViolation::QuantityAboveMax {
+                quantity,
+                max_quantity: self.max_quantity,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that `quantity` is a multiple of `lot_size`.
+    pub fn check_lot(&self, quantity: u64) -> Result<(), SpecViolation> {
+        if !quantity.is_multiple_of(self.lot_size) {
+            return Err(SpecViolation::QuantityNotOnLot {
+                quantity,
+                lot_size: self.lot_size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that `price * quantity` reaches `min_notional`.
+    pub fn check_notional(&self, price: u64, quantity: u64) -> Result<(), SpecViolation> {
+        let notional = price.saturating_mul(quantity);
+        if notional < self.min_notional {
+            return Err(SpecViolation::NotionalBelowMin {
+                notional,
+                min_notional: self.min_notional,
+            });
+        }
+        Ok(())
     }
 }
 
@@ -57,15 +256,18 @@ impl fmt::Display for Pair {
     }
 }
 
-// Fast, allocation-free FromStr that *also* enforces your whitelist.
+// Format-only parse; listing is checked against the registry.
 impl FromStr for Pair {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "BTC-USD" => Ok(BTC_USD),
-            "ETH-USD" => Ok(ETH_USD),
-            _ => Err(format!("unsupported symbol: `{}`", s)),
+        let unsupported = || format!("unsupported symbol: `{}`", s);
+        let (base, quote) = s.split_once('-').ok_or_else(unsupported)?;
+        let base: Asset = base.parse().map_err(|_| unsupported())?;
+        let quote: Asset = quote.parse().map_err(|_| unsupported())?;
+        if base == quote {
+            return Err(unsupported());
         }
+        Ok(Pair { base, quote })
     }
 }
 
@@ -90,3 +292,81 @@ pub const ETH_USD: Pair = Pair {
     base: Asset::ETH,
     quote: Asset::USD,
 };
+
+/// The set of listed instruments and their trading rules, keyed by pair.
+///
+/// Loaded at startup (from a JSON file of [`InstrumentSpec`]s, or the built-in
+/// pairs by default) and extended at runtime through the admin API.
+#[derive(Debug, Clone)]
+pub struct InstrumentRegistry {
+    specs: BTreeMap<Pair, InstrumentSpec>,
+}
+
+impl InstrumentRegistry {
+    /// Builds a registry from `specs`, rejecting invalid specs and duplicate pairs.
+    pub fn from_specs(
+        specs: impl IntoIterator<Item = InstrumentSpec>,
+    ) -> Result<Self, RegistryError> {
+        let mut registry = InstrumentRegistry {
+            specs: BTreeMap::new(),
+        };
+        for spec in specs {
+            registry.insert(spec)?;
+        }
+        Ok(registry)
+    }
+
+    /// Loads a JSON array of [`InstrumentSpec`]s from `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        let bytes = std::fs::read(path)?;
+        let specs: Vec<InstrumentSpec> = serde_json::from_slice(&bytes)?;
+        Self::from_specs(specs)
+    }
+
+    /// Trading rules for `pair`, if it is listed.
+    pub fn get(&self, pair: &Pair) -> Option<&InstrumentSpec> {
+        self.specs.get(pair)
+    }
+
+    pub fn contains(&self, pair: &Pair) -> bool {
+        self.specs.contains_key(pair)
+    }
+
+    /// Listed pairs, ordered by base then quote asset.
+    pub fn pairs(&self) -> impl Iterator<Item = &Pair> {
+        self.specs.keys()
+    }
+
+    /// Every listed spec, in the same order as [`InstrumentRegistry::pairs`].
+    pub fn specs(&self) -> impl Iterator<Item = &InstrumentSpec> {
+        self.specs.values()
+    }
+
+    /// Lists a new pair. Fails if the pair is already listed or the spec is invalid.
+    pub fn insert(&mut self, spec: InstrumentSpec) -> Result<(), RegistryError> {
+        if self.contains(&spec.pair) {
+            return Err(RegistryError::AlreadyListed(spec.pair.code()));
+        }
+        spec.check_valid()?;
+        self.specs.insert(spec.pair.clone(), spec);
+        Ok(())
+    }
+
+    /// Replaces the trading rules of an already listed pair.
+    pub fn update(&mut self, spec: InstrumentSpec) -> Result<(), RegistryError> {
+        if !self.contains(&spec.pair) {
+            return Err(RegistryError::NotListed(spec.pair.code()));
+        }
+        spec.check_valid()?;
+        self.specs.insert(spec.pair.clone(), spec);
+        Ok(())
+    }
+}
+
+impl Default for InstrumentRegistry {
+    /// Lists [`Pair::supported`] with default [`InstrumentSpec`]s.
+    fn default() -> Self {
+        Self::from_specs(Pair::supported().iter().cloned().map(InstrumentSpec::new))
+            .expect("built-in instruments are valid")
+    }
+}
@@ -1,9 +1,18 @@
+pub mod accounts;
 pub mod api;
+pub mod auction;
+pub mod balances;
+pub mod depth;
 pub mod errors;
+pub mod fees;
 pub mod instrument;
 pub mod market_maker;
+pub mod metrics;
 pub mod orderbook;
 pub mod orders;
+pub mod positions;
+pub mod risk;
+pub mod session;
 pub mod simulate;
 pub mod state;
 pub mod store;
@@ -1,14 +1,26 @@
 use axum::Router;
 use clap::{Parser, Subcommand};
-use order_book_engine::instrument::{Asset, Pair};
+use order_book_engine::accounts::ApiCredentials;
+use order_book_engine::instrument::{Asset, InstrumentRegistry, Pair};
+use order_book_engine::orders::MaxSlippage;
 use order_book_engine::utils::shutdown_token;
-use order_book_engine::{api, instrument, market_maker, simulate, state::AppState};
+use order_book_engine::{
+    api, instrument, market_maker, simulate,
+    state::{AppState, DEFAULT_CLIENT_ORDER_ID_WINDOW},
+};
 use serde_json::json;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::net::TcpListener;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
+use uuid::Uuid;
+
+/// How often expired GTD orders are swept out of the books.
+const SWEEP_EVERY: Duration = Duration::from_millis(250);
+
+/// What each demo account is funded with, per asset.
+const DEMO_FUNDS: u64 = 10_000_000;
 
 #[derive(Parser)]
 #[command(name = "engine-cli")]
@@ -18,6 +30,18 @@ use tracing_subscriber::FmtSubscriber;
     about = "A demo of a limit-order-book-engine"
 )]
 struct Cli {
+    /// JSON file listing the instruments to trade; defaults to BTC-USD and ETH-USD
+    #[arg(long, global = true)]
+    instruments: Option<PathBuf>,
+    /// Default slippage protection for market and stop orders, in basis points of the best price
+    #[arg(long, global = true)]
+    max_slippage_bps: Option<u64>,
+    /// How long a `client_order_id` stays reserved for retries of its order, in seconds
+    #[arg(long, global = true, default_value_t = DEFAULT_CLIENT_ORDER_ID_WINDOW.as_secs())]
+    client_order_id_window_secs: u64,
+    /// Token `/admin` requests must carry in `x-admin-token`; one is generated and logged if omitted
+    #[arg(long, global = true)]
+    admin_token: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -53,19 +77,24 @@ async fn wait_for_server(api_base: &str) -> anyhow::Result<()> {
     }
     Ok(())
 }
-async fn seed_book(ep: &str) -> anyhow::Result<()> {
+async fn seed_book(ep: &str, credentials: &ApiCredentials) -> anyhow::Result<()> {
     // Seed the book with a resting bid @48 and ask @52
     let client = reqwest::Client::new();
     for (side, price) in &[("Buy", 48), ("Sell", 52)] {
-        client
-            .post(format!("{}/orders", ep))
-            .json(&json!({
-                "side": side,
-                "order_type": "Limit",
-                "price": price,
-                "quantity": 10,
-                "symbol": Pair::crypto_usd(instrument::Asset::BTC).code()
-            }))
+        credentials
+            .request(
+                &client,
+                reqwest::Method::POST,
+                ep,
+                "/orders",
+                &json!({
+                    "side": side,
+                    "order_type": "Limit",
+                    "price": price,
+                    "quantity": 10,
+                    "symbol": Pair::crypto_usd(instrument::Asset::BTC).code()
+                }),
+            )
             .send()
             .await?
             .error_for_status()?;
@@ -74,6 +103,19 @@ async fn seed_book(ep: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Issues an API key for a demo account. The first time the account is opened
+/// it is funded with `DEMO_FUNDS` of BTC and USD; after a restart it keeps the
+/// balances replayed from the ledger.
+async fn open_demo_account(state: &AppState, account: &str) -> anyhow::Result<ApiCredentials> {
+    if state.balances_of(account).is_empty() {
+        for asset in [Asset::BTC, Asset::USD] {
+            state.deposit(account, &asset, DEMO_FUNDS)?;
+        }
+        state.persist_ledger().await?;
+    }
+    Ok(state.create_api_key(account).await)
+}
+
 async fn get_app_listener(port: u16, state: AppState) -> anyhow::Result<(TcpListener, Router)> {
     let app = api::router(state);
     let ep = format!("0.0.0.0:{port}");
@@ -83,19 +125,35 @@ async fn get_app_listener(port: u16, state: AppState) -> anyhow::Result<(TcpList
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let state = AppState::new(Path::new("trade_store")).await?;
+    let cli = Cli::parse();
+    let instruments = match &cli.instruments {
+        Some(path) => InstrumentRegistry::load(path)?,
+        None => InstrumentRegistry::default(),
+    };
+    let generated_admin_token = cli.admin_token.is_none();
+    let admin_token = cli
+        .admin_token
+        .unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+    let state = AppState::with_instruments(Path::new("trade_store"), instruments)
+        .await?
+        .with_default_slippage(cli.max_slippage_bps.map(MaxSlippage::Bps))
+        .with_client_order_id_window(Duration::from_secs(cli.client_order_id_window_secs))
+        .with_admin_token(admin_token.clone());
     let token = shutdown_token();
     let server_token = token.clone();
     let mm_token = token.clone();
     let sim_token = token.clone();
+    let sweeper_token = token.clone();
     // Setup tracing
     let subscriber = FmtSubscriber::builder()
         .with_max_level(Level::TRACE)
         .finish();
     tracing::subscriber::set_global_default(subscriber)?;
+    if generated_admin_token {
+        tracing::warn!(%admin_token, "no --admin-token given; generated one for this run");
+    }
     // The base URL our clients (Market Maker & Simulator) will use
 
-    let cli = Cli::parse();
     let base = "http://127.0.0.1".to_string();
     match cli.command {
         //runs system with market_maker bot && client
@@ -103,6 +161,7 @@ async fn main() -> anyhow::Result<()> {
             let secs = secs.unwrap_or_default();
             let mut handlers = tokio::task::JoinSet::new();
             let (listener, app) = get_app_listener(port, state.clone()).await?;
+            handlers.spawn(state.clone().run_expiry_sweeper(SWEEP_EVERY, sweeper_token));
             tracing::warn!("spawning the server task, port: {}, {}", port, secs);
             handlers.spawn(async move {
                 tracing::info!(
@@ -118,17 +177,19 @@ async fn main() -> anyhow::Result<()> {
             let ep = format!("{}:{}", base.clone(), port);
             tracing::info!("end_point: {}", ep);
             wait_for_server(&ep).await?;
-            seed_book(&ep).await?;
+            seed_book(&ep, &open_demo_account(&state, "seed").await?).await?;
             let pair = Pair::crypto_usd(instrument::Asset::BTC);
             //start market maker
             let mm = ep.clone();
+            let mm_key = open_demo_account(&state, "market-maker").await?;
             handlers.spawn(async move {
-                if let Err(e) = market_maker::run_market_maker(&mm, pair, mm_token).await {
+                if let Err(e) = market_maker::run_market_maker(&mm, pair, mm_key, mm_token).await {
                     tracing::error!("Market maker exited: {:?}", e);
                 }
             });
             //start simulator
             let sm = ep.clone();
+            let sim_key = open_demo_account(&state, "simulator").await?;
             handlers.spawn(async move {
                 if let Err(e) = simulate::run_simulation(
                     simulate::SimConfig {
@@ -138,6 +199,7 @@ async fn main() -> anyhow::Result<()> {
                         mean_qty: 5.0,
                         attack_rate_hz: 5_f64,
                         pair: Pair::crypto_usd(Asset::BTC),
+                        credentials: sim_key,
                     },
                     sim_token,
                 )
@@ -150,6 +212,7 @@ async fn main() -> anyhow::Result<()> {
         }
         Commands::Serve { port } => {
             let (listener, app) = get_app_listener(port, state.clone()).await?;
+            tokio::spawn(state.clone().run_expiry_sweeper(SWEEP_EVERY, sweeper_token));
             let svh = tokio::spawn(async move {
                 tracing::info!(
                     "HTTP/WS server listening on {}",
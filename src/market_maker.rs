@@ -8,10 +8,11 @@ use tokio_tungstenite::{connect_async, tungstenite::protocol::Message as WsMsg};
 use tokio_util::sync::CancellationToken;
 
 use crate::{
+    accounts::ApiCredentials,
     api::{OrderAck, WsFrame},
     errors,
     orderbook::BookSnapshot,
-    orders::{OrderType, Side},
+    orders::{OrderType, PostOnly, SelfTradePrevention, Side},
 };
 
 // # Market Maker Bot
@@ -30,36 +31,44 @@ use crate::{
 //   earns a tiny bit each time someone hits its quote.
 //
 // ## How It Works (Technical)
-// 1. **Connect** to your engine’s WebSocket feed (`/ws`) and receive `BookSnapshot { pair, bids, asks }`.
+// 1. **Connect** to your engine’s WebSocket feed (`/ws`), receive a `BookSnapshot { pair, seq, bids, asks }`
+//    and keep it current with the `BookDelta` frames that follow.
 // 2. **Compute** the mid-price:
 //    ```text
 //    mid = (best_bid + best_ask) / 2
 //    ```
 // 3. **Every PACE_MS milliseconds** (default 500 ms), *if* the midpoint has changed since last time:
-//    - **Cancel** previously posted buy & sell orders to avoid stale quotes.
-//    - **Place** two fresh **limit** orders via REST:
+//    - **Amend** the previously posted buy & sell quotes in place (`PATCH`) to the new prices,
+//      one round trip per side instead of a cancel plus a re-post.
+//    - **Place** a fresh **post-only limit** order via REST for any side that has no live quote
+//      (first cycle, or the old quote was filled). Post-only quotes are re-priced passive rather
+//      than crossing a stale mid, so the bot never pays taker prices:
 //      - **Buy** at `(mid_price - SPREAD)`
 //      - **Sell** at `(mid_price + SPREAD)`
-//    - **Remember** their order IDs so you can cancel them cleanly on the next cycle.
+//    - **Remember** their order IDs so they can be amended on the next cycle.
 //
 // ## Key Parameters
 // - `SPREAD: u64` — how far from the midpoint to quote.
 //   - Larger → greater profit per fill, but fewer fills.
 //   - Smaller → tighter market, but slimmer profit.
 // - `PACE_MS: u64` — how often (ms) to refresh quotes.
-//   - Faster → ultra-fresh quotes, but more cancellations/posts (and API calls).
+//   - Faster → ultra-fresh quotes, but more amendments (and API calls).
 //   - Slower → less chatter, but you may miss rapid market moves.
 //
 // ## Why It Works
 // - **Two-Sided Liquidity:** Always having both bid and ask visible narrows spreads and attracts flow.
-// - **Efficient Churn:** Only react to real mid-price moves, avoiding needless cancel/post cycles.
+// - **Efficient Churn:** Only react to real mid-price moves, amending quotes instead of needless
+//   cancel/post cycles.
 // - **Simple Model:** Fixed spread and interval make P&L predictable and coding straightforward.
 //
+// - Orders are signed with the bot's API key (`ApiCredentials`), so its quotes are booked
+//   under its own account and never trade with each other.
+//
 // ## Under the Hood
-// - A **WebSocket** task parses `BookSnapshot` frames and sends midpoint updates into a
-//   `tokio::watch` channel.
-// - A **Quoting** loop ticks on a `tokio::time::interval`; it reads the latest mid-price, cancels
-//   old orders, and posts new ones with `reqwest`.
+// - A **WebSocket** task applies `BookSnapshot` and `BookDelta` frames to a local book, refetching
+//   `GET /book/{pair}` if a delta is missing, and sends midpoint updates into a `tokio::watch` channel.
+// - A **Quoting** loop ticks on a `tokio::time::interval`; it reads the latest mid-price and amends
+//   (or re-posts) its quotes with `reqwest`.
 // - All HTTP and WS errors are wrapped in `MarketMakerError` for clean upstream handling.
 //
 
@@ -76,28 +85,124 @@ struct NewOrder {
     price: Option<u64>,
     quantity: u64,
     symbol: String,
+    post_only: Option<PostOnly>,
+    self_trade_prevention: SelfTradePrevention,
+}
+
+// A small helper to serialize quote amendments
+#[derive(Deserialize, Serialize)]
+struct AmendOrder {
+    price: u64,
+    quantity: u64,
 }
 
-/// Starts the market maker loop against a REST+WS API at `api_base`.
+/// Moves the live quote `existing` to `price` via `PATCH /orders/{pair}/{id}`,
+/// or posts a fresh post-only quote if there is none or it is gone (`404`:
+/// filled or cancelled).
+///
+/// If the amend fails for another reason the old quote may still rest, so it
+/// is cancelled before a new one is posted; if that fails too, it is kept.
+///
+/// Returns the ID of the live quote, if any.
+async fn requote(
+    client: &reqwest::Client,
+    credentials: &ApiCredentials,
+    api_base: &str,
+    pair: &Pair,
+    existing: Option<u128>,
+    side: Side,
+    price: u64,
+) -> Option<u128> {
+    if let Some(id) = existing {
+        let path = format!("/orders/{}/{}", pair.code(), id);
+        let amended = credentials
+            .request(
+                client,
+                reqwest::Method::PATCH,
+                api_base,
+                &path,
+                &AmendOrder { price, quantity: 1 },
+            )
+            .send()
+            .await
+            .map(|resp| resp.status());
+        match amended {
+            Ok(status) if status.is_success() => return Some(id),
+            Ok(reqwest::StatusCode::NOT_FOUND) => {
+                tracing::info!(id, "quote no longer live; posting a new one");
+            }
+            failed => {
+                tracing::warn!(id, ?failed, "amending quote failed; cancelling it");
+                let cancelled = credentials
+                    .delete(client, api_base, &path)
+                    .send()
+                    .await
+                    .map(|resp| resp.status());
+                match cancelled {
+                    Ok(status)
+                        if status.is_success() || status == reqwest::StatusCode::NOT_FOUND => {}
+                    failed => {
+                        tracing::warn!(id, ?failed, "cancelling quote failed; keeping it");
+                        return Some(id);
+                    }
+                }
+            }
+        }
+    }
+    let resp = credentials
+        .request(
+            client,
+            reqwest::Method::POST,
+            api_base,
+            "/orders",
+            &NewOrder {
+                side,
+                order_type: OrderType::Limit,
+                price: Some(price),
+                quantity: 1,
+                symbol: pair.code(),
+                post_only: Some(PostOnly::Reprice),
+                self_trade_prevention: SelfTradePrevention::CancelOldest,
+            },
+        )
+        .send()
+        .await
+        .ok()?;
+    resp.json::<OrderAck>().await.ok().map(|ack| ack.order_id)
+}
+
+/// Fetches a snapshot of the book from `GET /book/{pair}`; deltas it already
+/// includes are ignored when applied on top of it.
+async fn fetch_book(client: &reqwest::Client, url: &str) -> Option<BookSnapshot> {
+    let resp = client.get(url).send().await.ok()?;
+    resp.json::<BookSnapshot>().await.ok()
+}
+
+/// Starts the market maker loop against a REST+WS API at `api_base`, signing
+/// its orders with `credentials`.
 ///
 /// 1. Establishes a WebSocket connection to `ws://{api_base}/ws`.
-/// 2. Spawns a background task that listens for `BookSnapshot` frames:
-///    - Parses best bid & best ask from each snapshot
-///    - Computes and broadcasts the mid-price via a `tokio::watch` channel
+/// 2. Spawns a background task that keeps a local book from `BookSnapshot`
+///    and `BookDelta` frames:
+///    - Resyncs from `GET /book/{pair}` when the delta sequence skips
+///    - Computes the mid-price from the best bid & best ask after each frame
+///      and broadcasts it via a `tokio::watch` channel
 /// 3. Enters a loop, ticking every `PACE_MS` ms:
-///    - If we have a mid-price, cancel all currently outstanding quotes
-///      via `DELETE /orders/{id}`
-///    - Sends two new limit orders (size=1):
+///    - If the mid-price moved, amends each outstanding quote (size=1) via
+///      `PATCH /orders/{pair}/{id}`, or posts a new one if it is gone (see
+///      [`requote`]):
 ///      - **Buy** at `(mid_price - SPREAD)` buy low
 ///      - **Sell** at `(mid_price + SPREAD)` sell high
-///    - Records the returned `order_id`s so they can be cancelled on the
+///    - Records the live `order_id`s so they can be amended on the
 ///      next iteration.
+/// 4. On shutdown, cancels its quotes in the pair with one `DELETE /orders`.
 ///
 /// Errors from the WebSocket connection or HTTP client are wrapped in
 /// `MarketMakerError` for upstream handling.
 pub async fn run_market_maker(
     api_base: &str,
     target_pair: Pair,
+    credentials: ApiCredentials,
     token: CancellationToken,
 ) -> Result<(), MarketMakerError> {
     //use pair-specific websocket URL
@@ -126,8 +231,9 @@ pub async fn run_market_maker(
     // watch channel for mid_price
     let (mid_tx, mid_rx) = watch::channel(None::<u64>);
 
-    // 2) Spawn task: parse snapshots → update `mid_tx`
+    // 2) Spawn task: maintain the book from snapshots and deltas → update `mid_tx`
     let v = target_pair.clone();
+    let book_url = format!("{}/book/{}", api_base, target_pair.code());
 
     let frames = read.filter_map(|msg| async move {
         match msg {
@@ -143,88 +249,69 @@ pub async fn run_market_maker(
     });
     tokio::spawn(async move {
         tokio::pin!(frames);
+        let client = reqwest::Client::new();
+        let mut book: Option<BookSnapshot> = None;
         while let Some(frame) = frames.next().await {
-            if let WsFrame::BookSnapshot(BookSnapshot { pair, bids, asks }) = frame {
-                if pair != v {
-                    continue;
+            match frame {
+                WsFrame::BookSnapshot(snap) if snap.pair == v => book = Some(snap),
+                WsFrame::BookDelta(delta) if delta.pair == v => {
+                    if !book.as_mut().is_some_and(|local| local.apply(&delta)) {
+                        tracing::warn!(
+                            seq = delta.seq,
+                            "market maker: missed a book delta; resyncing"
+                        );
+                        book = fetch_book(&client, &book_url).await;
+                    }
                 }
+                _ => continue,
+            }
 
-                if let (Some((bb, _)), Some((aa, _))) = (bids.first(), asks.first()) {
-                    let mid = (bb + aa) / 2;
-                    let _ = mid_tx.send(Some(mid));
-                }
-            };
+            if let Some(BookSnapshot { bids, asks, .. }) = &book
+                && let (Some((bb, _)), Some((aa, _))) = (bids.first(), asks.first())
+            {
+                let mid = (bb + aa) / 2;
+                let _ = mid_tx.send(Some(mid));
+            }
         }
     });
 
     // 3) Every PACE_MS: if the mid‐price has changed since our last quote,
-    //    cancel the old bid/ask and post fresh ones around the new mid.
+    //    move the bid/ask around the new mid.
     let client = reqwest::Client::new();
-    let mut outstanding: Vec<u128> = Vec::new();
+    let mut bid_id: Option<u128> = None;
+    let mut ask_id: Option<u128> = None;
     let mut interval = time::interval(time::Duration::from_millis(PACE_MS));
     let mut last_mid = None;
     loop {
         tokio::select! {
-                //cancellation wins instantly
-                _ = token.cancelled() => {
-                    tracing::info!("market makerL shutdown requested, tearing down...");
-                    break;
+            //cancellation wins instantly
+            _ = token.cancelled() => {
+                tracing::info!("market maker shutdown requested, tearing down...");
+                break;
+            }
+            _ = interval.tick() => {
+                // Only quote once we have a mid-price, and only when it moved
+                let mid_opt: Option<u64> = *mid_rx.borrow();
+                let Some(mid_price) = mid_opt else { continue };
+                if Some(mid_price) == last_mid {
+                    continue;
                 }
-                _ = interval.tick() => {
-                            // Only quote once we have a mid-price
+                let bid_price = mid_price.saturating_sub(SPREAD);
+                tracing::info!(bid_price, "quoting bid");
+                bid_id = requote(&client, &credentials, api_base, &target_pair, bid_id, Side::Buy, bid_price).await;
 
-            let mid_opt: Option<u64> = *mid_rx.borrow();
-            if let Some(mid_price) = mid_opt {
-                if Some(mid_price) != last_mid {
-                    //market has moved, cancel & place new orders, and update mid price
-                    // Cancel all previous orders
-                    for id in outstanding.drain(..) {
-                        let _ = client
-                            .delete(format!("{}/orders/{}/{}", api_base, target_pair.code(), id))
-                            .send()
-                            .await;
-                    }
-                    tracing::info!(bid_price = mid_price.saturating_sub(SPREAD), "placing bid");
-                    // Post a new bid
-                    if let Ok(resp) = client
-                        .post(format!("{}/orders", api_base))
-                        .json(&NewOrder {
-                            side: Side::Buy,
-                            order_type: OrderType::Limit,
-                            price: Some(mid_price.saturating_sub(SPREAD)),
-                            quantity: 1,
-                            symbol: target_pair.code(),
-                        })
-                        .send()
-                        .await
-                    {
-                        if let Ok(ack) = resp.json::<OrderAck>().await {
-                            outstanding.push(ack.order_id);
-                        }
-                    }
-                    tracing::info!(bid_price = mid_price.saturating_add(SPREAD), "placing ask");
-                    // Post a new ask
-                    if let Ok(resp) = client
-                        .post(format!("{}/orders", api_base))
-                        .json(&NewOrder {
-                            side: Side::Sell,
-                            order_type: OrderType::Limit,
-                            price: Some(mid_price.saturating_add(SPREAD)),
-                            quantity: 1,
-                            symbol: target_pair.code(),
-                        })
-                        .send()
-                        .await
-                    {
-                        if let Ok(ack) = resp.json::<OrderAck>().await {
-                            outstanding.push(ack.order_id);
-                        }
-                    }
-                    last_mid = Some(mid_price);
-                }
+                let ask_price = mid_price.saturating_add(SPREAD);
+                tracing::info!(ask_price, "quoting ask");
+                ask_id = requote(&client, &credentials, api_base, &target_pair, ask_id, Side::Sell, ask_price).await;
+                last_mid = Some(mid_price);
             }
         }
-                }
+    }
+    // 4) Pull both quotes in one request instead of leaving them resting
+    let path = format!("/orders?pair={}", target_pair.code());
+    match credentials.delete(&client, api_base, &path).send().await {
+        Ok(resp) => tracing::info!(status = %resp.status(), "market maker: pulled quotes"),
+        Err(e) => tracing::warn!("market maker: could not pull quotes: {}", e),
     }
     Ok(())
 }
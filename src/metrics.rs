@@ -0,0 +1,43 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use crate::errors::RiskViolation;
+
+/// Counters exported by `GET /metrics` in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    risk_rejections: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Metrics {
+    /// Counts an order rejected by the pre-trade risk checks under its code.
+    pub fn count_risk_rejection(&self, violation: &RiskViolation) {
+        *self.rejections().entry(violation.code()).or_default() += 1;
+    }
+
+    /// Orders rejected so far with the risk violation `code`.
+    pub fn risk_rejections(&self, code: &str) -> u64 {
+        self.rejections().get(code).copied().unwrap_or(0)
+    }
+
+    /// Every counter in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::from(
+            "# HELP risk_rejections_total Orders rejected by pre-trade risk checks.\n\
+             # TYPE risk_rejections_total counter\n",
+        );
+        for (code, count) in self.rejections().iter() {
+            let _ = writeln!(out, "risk_rejections_total{{code=\"{code}\"}} {count}");
+        }
+        out
+    }
+
+    fn rejections(&self) -> MutexGuard<'_, BTreeMap<&'static str, u64>> {
+        self.risk_rejections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -1,15 +1,28 @@
 use crate::{
-    instrument::Pair,
-    orders::{Order, OrderType, Side},
+    auction::{self, AuctionQuote},
+    balances::{Funds, SharedBalances, affordable_quantity, max_cost},
+    depth::BookDelta,
+    errors::OrderError,
+    fees::{FeeLedger, FeeReport},
+    instrument::{InstrumentSpec, Pair, PriceBands},
+    orders::{
+        Order, OrderRecord, OrderStatus, OrderType, PostOnly, SelfTradePrevention, Side,
+        TimeInForce,
+    },
+    positions::{PositionLedger, PositionReport},
+    session::TradingPhase,
     trade::Trade,
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BTreeMap, VecDeque},
-    time::SystemTime,
+    collections::{BTreeMap, HashMap, VecDeque},
+    time::{Duration, SystemTime},
 };
 use tracing::{info, warn};
 
+/// How many closed order records a book keeps by default.
+pub const CLOSED_RECORD_LIMIT: usize = 10_000;
+
 /// An [`OrderBook`] stores **active** buy and sell orders in two separate
 /// [`BTreeMap`]s:
 /// - `bids` (buy orders)  
@@ -17,6 +30,31 @@ use tracing::{info, warn};
 ///
 /// Each price level (key) has a FIFO queue of orders stored in a [`VecDeque`]
 /// to maintain **price-time** priority.
+///
+/// Untriggered `Stop`/`StopLimit` orders live in a separate trigger book
+/// (`buy_stops` / `sell_stops`), keyed by trigger price, and never show up
+/// in the visible book until the last trade price fires them.
+///
+/// An `id → location` index is kept in sync with every queue so cancels,
+/// amends and lookups find their price level in constant time instead of
+/// scanning every level on both sides.
+///
+/// Every order the book has accepted also keeps an [`OrderRecord`], which
+/// outlives its queue entry so the order's final status and fills can still
+/// be looked up once it has been filled, cancelled or expired. Only the most
+/// recent closed records are kept (see [`CLOSED_RECORD_LIMIT`]); older ones
+/// are dropped, oldest first, as new orders arrive.
+///
+/// The book's [`TradingPhase`] decides whether incoming orders match: during a
+/// call phase they only rest, and the book is uncrossed in a call auction when
+/// the phase ends.
+///
+/// Its [`PriceBands`] keep continuous trading within a range around the
+/// reference and last trade prices; a breach starts a volatility auction.
+///
+/// A book given a [`crate::balances::BalanceLedger`] funds its owners' orders
+/// from it: each order holds what it may spend while it is open (see [`Funds`]),
+/// and every fill settles between the two accounts.
 #[derive(Clone, Debug)]
 pub struct OrderBook {
     /// Buy orders, keyed by price in ascending order.
@@ -28,6 +66,114 @@ pub struct OrderBook {
     ///
     /// For matching, we iterate **forwards** to find the lowest ask first.
     pub asks: BTreeMap<u64, VecDeque<Order>>,
+
+    /// Untriggered buy stops, keyed by trigger price in ascending order.
+    pub buy_stops: BTreeMap<u64, VecDeque<Order>>,
+
+    /// Untriggered sell stops, keyed by trigger price in ascending order.
+    pub sell_stops: BTreeMap<u64, VecDeque<Order>>,
+
+    /// Price of the most recent trade; drives stop triggering.
+    pub last_trade_price: Option<u64>,
+
+    /// Anchor of the static price band: the last auction price, or the first trade.
+    pub reference_price: Option<u64>,
+
+    /// Current trading phase; only changed through [`OrderBook::set_phase`].
+    phase: TradingPhase,
+
+    /// Circuit breaker limits, copied from the pair's instrument spec.
+    bands: PriceBands,
+
+    /// The pair's tick size: the grid post-only reprices stay on, and the unit
+    /// of market order protection given in ticks.
+    tick_size: u64,
+
+    /// Charges the pair's fee schedule on every trade and keeps fee totals.
+    fees: FeeLedger,
+
+    /// When the running volatility auction reopens the book, if one is running.
+    auction_ends_at: Option<SystemTime>,
+
+    /// Where every live order currently sits, keyed by order ID.
+    index: HashMap<u128, OrderLocation>,
+
+    /// Lifecycle record of every open order and of the most recently closed ones.
+    records: OrderRecords,
+
+    /// What every open order holds in the balance ledger, if the book has one.
+    funds: Funds,
+
+    /// Every account's position in the pair, from its fills.
+    positions: PositionLedger,
+}
+
+/// Where a live order sits inside an [`OrderBook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderLocation {
+    /// Resting in `bids`/`asks` at this price.
+    Resting(Side, u64),
+    /// Parked in `buy_stops`/`sell_stops` at this trigger price.
+    Trigger(Side, u64),
+}
+
+/// The [`OrderRecord`]s of a book, keyed by order ID.
+///
+/// Closed records are queued in the order they closed; once there are more
+/// than `limit`, [`OrderRecords::prune`] drops the oldest.
+#[derive(Clone, Debug)]
+struct OrderRecords {
+    by_id: HashMap<u128, OrderRecord>,
+    closed: VecDeque<u128>,
+    limit: usize,
+}
+
+impl OrderRecords {
+    fn new(limit: usize) -> Self {
+        Self {
+            by_id: HashMap::new(),
+            closed: VecDeque::new(),
+            limit,
+        }
+    }
+
+    fn get(&self, order_id: &u128) -> Option<&OrderRecord> {
+        self.by_id.get(order_id)
+    }
+
+    fn get_mut(&mut self, order_id: &u128) -> Option<&mut OrderRecord> {
+        self.by_id.get_mut(order_id)
+    }
+
+    /// Starts a record for `order` unless it already has one.
+    fn track(&mut self, order: &Order) {
+        self.by_id
+            .entry(order.id)
+            .or_insert_with(|| OrderRecord::new(order));
+    }
+
+    fn remove(&mut self, order_id: &u128) {
+        self.by_id.remove(order_id);
+    }
+
+    /// Moves `order_id`'s record to a final `status` if it is still open.
+    fn close(&mut self, order_id: u128, status: OrderStatus) {
+        if let Some(record) = self.by_id.get_mut(&order_id)
+            && record.status.is_open()
+        {
+            record.close(status);
+            self.closed.push_back(order_id);
+        }
+    }
+
+    /// Drops the oldest closed records beyond the limit.
+    fn prune(&mut self) {
+        while self.closed.len() > self.limit {
+            if let Some(order_id) = self.closed.pop_front() {
+                self.by_id.remove(&order_id);
+            }
+        }
+    }
 }
 
 /// Internal enum to unify forward (`IterMut`) and reverse (`Rev<IterMut>`) BTreeMap iteration.
@@ -60,7 +206,11 @@ impl<'a> Iterator for EitherIter<'a> {
 /// - `incoming`: the incoming [`Order`] to be matched.
 /// - `book_side`: a mutable reference to the [`BTreeMap`] representing the relevant side
 ///   of the book (e.g., `asks` for a buy, `bids` for a sell).
-/// - `reversed`: indicates whether to iterate in descending (`true`) or ascending (`false`) order.
+/// - `index`: the book's order index; entries are dropped for every resting order removed.
+/// - `records`: the book's order records; fills and closed resting orders are recorded here.
+/// - `fees`: the book's fee ledger; every trade is charged before it is recorded.
+/// - `funds`: the book's order holds; every trade is settled out of them.
+/// - `price_limit`: the worst price the incoming order may trade at, if any.
 ///
 /// # Returns
 /// A [`Vec<Trade>`] describing all the partial or full matches that occurred.
@@ -73,6 +223,14 @@ impl<'a> Iterator for EitherIter<'a> {
 ///   - Correct matching (only fill what’s available on both sides)
 ///   - Preventing negative quantities or overflows
 ///   - Supporting realistic order book behavior (e.g., partial matches over multiple price levels)
+/// - Resting GTD orders whose deadline has passed are dropped instead of matched.
+/// - Only the visible slice of a resting iceberg trades per pass. Once it is consumed the
+///   order replenishes from its hidden reserve and moves to the **back** of its level,
+///   losing time priority.
+/// - Before each fill, a resting order from the incoming order's own account triggers
+///   the incoming order's [`SelfTradePrevention`] mode instead of a trade.
+/// - A funded buy only takes what its hold can pay for; once that runs out matching stops,
+///   or, for a resting buy, the resting order is cancelled.
 ///
 /// # Example
 /// - A market buy for 10 units encounters a sell (ask) order for 6 units.
@@ -80,15 +238,19 @@ impl<'a> Iterator for EitherIter<'a> {
 fn match_incoming_side(
     incoming: &mut Order,
     book_side: &mut BTreeMap<u64, VecDeque<Order>>,
-    reversed: bool,
+    index: &mut HashMap<u128, OrderLocation>,
+    records: &mut OrderRecords,
+    fees: &mut FeeLedger,
+    funds: &mut Funds,
     price_limit: Option<u64>,
 ) -> Vec<Trade> {
     info!("matching incoming order: {:?}", incoming);
     let mut trades = Vec::new();
     let mut levels_to_remove = Vec::new();
+    let now = SystemTime::now();
 
-    // Choose iterator direction based on `reversed`
-    let iter = if reversed {
+    // Sells meet the highest bids first, buys the lowest asks
+    let iter = if incoming.side == Side::Sell {
         EitherIter::Rev(book_side.iter_mut().rev())
     } else {
         EitherIter::Fwd(book_side.iter_mut())
@@ -104,27 +266,108 @@ fn match_incoming_side(
             }
         }
         while let Some(order) = orders_at_price.front_mut() {
+            // Expired GTD orders are no longer live; drop them and move on
+            if order.is_expired(now) {
+                info!("dropping expired order {}", order.id);
+                index.remove(&order.id);
+                close_record(records, funds, order.id, OrderStatus::Expired);
+                orders_at_price.pop_front();
+                if orders_at_price.is_empty() {
+                    levels_to_remove.push(price);
+                }
+                continue;
+            }
+            // Self-trade prevention: an account never trades with itself
+            if incoming.is_self_trade_with(order) {
+                let mode = incoming.self_trade_prevention;
+                info!(
+                    "self-trade prevented between {} and {} ({:?})",
+                    incoming.id, order.id, mode
+                );
+                let cancel_resting = match mode {
+                    SelfTradePrevention::CancelNewest => {
+                        incoming.quantity = 0;
+                        false
+                    }
+                    SelfTradePrevention::CancelOldest => true,
+                    SelfTradePrevention::CancelBoth => {
+                        incoming.quantity = 0;
+                        true
+                    }
+                    SelfTradePrevention::DecrementAndCancel => {
+                        let qty = incoming.quantity.min(order.remaining_quantity());
+                        incoming.quantity -= qty;
+                        order.decrement(qty);
+                        order.remaining_quantity() == 0
+                    }
+                };
+                if incoming.quantity == 0 {
+                    close_record(records, funds, incoming.id, OrderStatus::Cancelled);
+                }
+                if cancel_resting {
+                    index.remove(&order.id);
+                    close_record(records, funds, order.id, OrderStatus::Cancelled);
+                    orders_at_price.pop_front();
+                }
+                if orders_at_price.is_empty() {
+                    levels_to_remove.push(price);
+                }
+                if incoming.quantity == 0 {
+                    break 'outer;
+                }
+                continue;
+            }
             warn!("emitting trades...");
             // Determine how many units to fill in this match
-            let trade_qty = incoming.quantity.min(order.quantity);
+            let mut trade_qty = incoming.quantity.min(order.quantity);
+            if let Some(affordable) = funds.affordable(incoming, price, fees.max_bps()) {
+                if affordable == 0 {
+                    info!("order {} has used up its funds", incoming.id);
+                    break 'outer;
+                }
+                trade_qty = trade_qty.min(affordable);
+            }
+            // A resting buy can run short too, once fees have been raised under it
+            if let Some(affordable) = funds.affordable(order, price, fees.max_bps()) {
+                if affordable == 0 {
+                    info!("cancelling order {}; it has used up its funds", order.id);
+                    index.remove(&order.id);
+                    close_record(records, funds, order.id, OrderStatus::Cancelled);
+                    orders_at_price.pop_front();
+                    if orders_at_price.is_empty() {
+                        levels_to_remove.push(price);
+                    }
+                    continue;
+                }
+                trade_qty = trade_qty.min(affordable);
+            }
 
-            trades.push(Trade {
+            let mut trade = Trade {
                 price,
                 quantity: trade_qty,
                 maker_id: order.id,
                 taker_id: incoming.id,
                 timestamp: SystemTime::now(),
                 symbol: order.pair.code(),
-            });
+                maker_fee: 0,
+                taker_fee: 0,
+            };
+            fees.charge(&mut trade, order.owner.as_ref(), incoming.owner.as_ref());
+            funds.settle(&trade, order, incoming);
+            for id in [trade.maker_id, trade.taker_id] {
+                if let Some(record) = records.get_mut(&id) {
+                    record.record_fill(&trade);
+                }
+            }
+            trades.push(trade);
 
             // Update the quantities on both orders
             incoming.quantity -= trade_qty;
             order.quantity -= trade_qty;
 
-            // Remove the fully filled resting order from the queue front
-            if order.quantity == 0 {
-                orders_at_price.pop_front();
-            }
+            // Remove the fully filled resting order from the queue front;
+            // an iceberg with reserve left is replenished and re-queued at the back
+            settle_front(orders_at_price, index, records, funds);
 
             // If all orders at this price were consumed, mark the level for cleanup
             if orders_at_price.is_empty() {
@@ -146,350 +389,2497 @@ fn match_incoming_side(
     trades
 }
 
+/// Pops the front order of `level` once its visible quantity is used up:
+/// an iceberg with reserve left is replenished and re-queued at the back,
+/// anything else leaves the book as `Filled`.
+fn settle_front(
+    level: &mut VecDeque<Order>,
+    index: &mut HashMap<u128, OrderLocation>,
+    records: &mut OrderRecords,
+    funds: &mut Funds,
+) {
+    if level.front().is_some_and(|o| o.quantity == 0)
+        && let Some(mut exhausted) = level.pop_front()
+    {
+        if exhausted.replenish() {
+            info!("replenishing iceberg order {}", exhausted.id);
+            level.push_back(exhausted);
+        } else {
+            index.remove(&exhausted.id);
+            close_record(records, funds, exhausted.id, OrderStatus::Filled);
+        }
+    }
+}
+
+/// Moves `order_id`'s record to a final `status`, if it is still open, and
+/// returns whatever the order still holds to its account.
+fn close_record(
+    records: &mut OrderRecords,
+    funds: &mut Funds,
+    order_id: u128,
+    status: OrderStatus,
+) {
+    records.close(order_id, status);
+    funds.release(order_id);
+}
+
 impl OrderBook {
     /// Creates a new, empty [`OrderBook`], with no active bids or asks.
     pub fn new() -> Self {
         Self {
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
+            buy_stops: BTreeMap::new(),
+            sell_stops: BTreeMap::new(),
+            last_trade_price: None,
+            reference_price: None,
+            phase: TradingPhase::default(),
+            bands: PriceBands::default(),
+            tick_size: 1,
+            fees: FeeLedger::default(),
+            auction_ends_at: None,
+            index: HashMap::new(),
+            records: OrderRecords::new(CLOSED_RECORD_LIMIT),
+            funds: Funds::default(),
+            positions: PositionLedger::default(),
         }
     }
 
-    /// Adds a **limit** order to the order book (buy or sell).  
-    ///
-    /// If it's a market order (`price == None`), we print a warning and do not add it
-    /// since market orders match immediately and do not rest in the book.
-    pub fn add_order(&mut self, order: Order) {
-        if let Some(price) = order.price {
-            let book_side = match order.side {
-                Side::Buy => &mut self.bids,
-                Side::Sell => &mut self.asks,
-            };
-            book_side
-                .entry(price)
-                .or_insert_with(VecDeque::new)
-                .push_back(order);
-        } else {
-            eprintln!("Warning: Attempting to add a market order to the book. Ignoring...");
-        }
+    /// Creates an empty book that follows `spec`'s price bands and tick size.
+    pub fn for_instrument(spec: &InstrumentSpec) -> Self {
+        let mut book = Self::new();
+        book.apply_instrument(spec);
+        book
     }
 
-    /// Matches an incoming **market** order against the order book.
-    ///
-    /// # Behavior
-    /// - If `incoming.side` is `Buy`, we match against the `asks` from lowest to highest.
-    /// - If `incoming.side` is `Sell`, we match against the `bids` from highest to lowest.
-    ///
-    /// Returns a [`Vec<Trade>`] describing all executed trades.
-    ///
-    /// *Note:* For **limit** orders, you’d typically match what can be matched, then
-    /// rest the remainder in the book.  
-    /// Currently, this function is specialized for market orders or the "matching" portion
-    /// of a limit order.
-    pub fn match_order(&mut self, mut incoming: Order) -> Vec<Trade> {
-        let incoming_price = incoming.price;
-        let trades = match (incoming.side, incoming.order_type) {
-            (Side::Buy, OrderType::Market) => {
-                match_incoming_side(&mut incoming, &mut self.asks, false, None)
-            }
-            (Side::Buy, OrderType::Limit) => {
-                match_incoming_side(&mut incoming, &mut self.asks, false, incoming_price)
-            }
-            (Side::Sell, OrderType::Market) => {
-                match_incoming_side(&mut incoming, &mut self.bids, true, None)
-            }
-
-            (Side::Sell, OrderType::Limit) => {
-                match_incoming_side(&mut incoming, &mut self.bids, true, incoming_price)
-            }
-        };
-        //After matching , if its a limit order with leftover qty, insert into book
-        if incoming.order_type == OrderType::Limit && incoming.quantity > 0 {
-            warn!("adding (partially or not filled) limit order to book");
-            self.add_order(incoming);
-        };
-        trades
+    /// Funds the orders of owned accounts from `ledger` from now on.
+    pub fn with_balances(mut self, ledger: SharedBalances) -> Self {
+        self.funds = Funds::new(ledger);
+        self
     }
 
-    /// Attempts to cancel an order by ID.
-    ///
-    /// Returns `true` if the order was found *and* removed;
-    /// also prunes the price level if it becomes empty.
-    pub fn cancel_order(&mut self, order_id: u128) -> bool {
-        for book_side in [&mut self.bids, &mut self.asks] {
-            let mut price_to_prune: Option<u64> = None;
-            let mut found = false;
-            for (price, queue) in book_side.iter_mut() {
-                if let Some(pos) = queue.iter().position(|o| o.id == order_id) {
-                    queue.remove(pos);
-                    found = true;
-                    if queue.is_empty() {
-                        price_to_prune = Some(*price);
-                    }
-                    break;
-                }
-            }
-            if found {
-                //prune the price level if needed
-                if let Some(price) = price_to_prune {
-                    println!("cleaning empty price levels");
-                    book_side.remove(&price);
-                }
-                return true;
-            }
-        }
-        false
+    /// Keeps at most `limit` closed order records instead of [`CLOSED_RECORD_LIMIT`].
+    pub fn with_closed_record_limit(mut self, limit: usize) -> Self {
+        self.records.limit = limit;
+        self
     }
-}
 
-impl Default for OrderBook {
-    fn default() -> Self {
-        Self::new()
+    /// Picks up the parts of `spec` the engine enforces itself: price bands,
+    /// tick size and fee schedule.
+    pub fn apply_instrument(&mut self, spec: &InstrumentSpec) {
+        self.bands = spec.price_bands;
+        self.tick_size = spec.tick_size;
+        self.fees.set_schedule(spec.fees.clone());
     }
-}
-
-//SnapShot
-/// returned as a Response payload for `GET /book`.
-///
-/// - `bids`: list of `(price, total_quantity)` in descending order  
-/// - `asks`: list of `(price, total_quantity)` in ascending order
-/// - `pair`: the market this snapshot belongs to.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BookSnapshot {
-    ///Which pair this is for
-    pub pair: Pair,
-    ///List of `(price, total_quantity)` in descending order
-    pub bids: Vec<(u64, u64)>,
-    ///List of `(price, total_quantity)` in ascending order
-    pub asks: Vec<(u64, u64)>,
-}
 
-impl BookSnapshot {
-    pub fn empty(pair: Pair) -> Self {
-        BookSnapshot {
-            pair,
-            bids: Vec::new(),
-            asks: Vec::new(),
-        }
+    /// Fees charged on this book's trades, overall and per account.
+    pub fn fee_report(&self, pair: Pair) -> FeeReport {
+        self.fees.report(pair)
     }
-    pub fn for_pair(pair: Pair, book: &OrderBook) -> Self {
-        let bids = book
-            .bids
-            .iter()
-            .rev()
-            .map(|(p, orders)| (*p, orders.iter().map(|o| o.quantity).sum()))
-            .collect();
-        let asks = book
-            .asks
-            .iter()
-            .map(|(p, orders)| (*p, orders.iter().map(|o| o.quantity).sum()))
-            .collect();
 
-        BookSnapshot { pair, bids, asks }
+    /// `account`'s position in the pair, its unrealized P&L marked to the book mid;
+    /// `None` if the account never traded here.
+    pub fn position_report(&self, pair: Pair, account: &str) -> Option<PositionReport> {
+        let position = self.positions.get(account)?;
+        Some(PositionReport::new(pair, position, self.mid_price()))
     }
-}
-
-//tests
-#[cfg(test)]
-mod tests {
-    use crate::instrument::{BTC_USD, ETH_USD};
 
-    use super::*;
+    /// `account`'s net base quantity in the pair; `0` if it never traded it.
+    pub fn net_position(&self, account: &str) -> i64 {
+        self.positions.get(account).map_or(0, |p| p.quantity)
+    }
 
-    fn sample_limit_order(id: u128, side: Side, price: u64, quantity: u64) -> Order {
-        Order {
-            id,
-            side,
-            order_type: OrderType::Limit,
-            price: Some(price),
-            quantity,
-            timestamp: SystemTime::now(),
-            pair: ETH_USD,
+    /// Adds `trades` to the positions of the accounts that own their orders.
+    fn track_positions(&mut self, trades: &[Trade]) {
+        for trade in trades {
+            for (id, fee) in [
+                (trade.maker_id, trade.maker_fee),
+                (trade.taker_id, trade.taker_fee),
+            ] {
+                if let Some(record) = self.records.get(&id)
+                    && let Some(owner) = &record.owner
+                {
+                    self.positions
+                        .apply_fill(owner, record.side, trade.price, trade.quantity, fee);
+                }
+            }
         }
     }
 
-    fn sample_market_order(id: u128, side: Side, quantity: u64) -> Order {
-        Order {
-            id,
-            side,
-            order_type: OrderType::Market,
-            price: None,
-            quantity,
-            timestamp: SystemTime::now(),
-            pair: BTC_USD,
-        }
+    /// The book's current trading phase.
+    pub fn phase(&self) -> TradingPhase {
+        self.phase
     }
 
-    /// Tests a market buy order that partially fills against multiple sell orders.
-    #[test]
-    fn test_partial_fill_market_buy() {
-        let mut ob = OrderBook::new();
+    /// Moves the book to `next`, if the phase state machine allows it.
+    ///
+    /// Leaving a call phase (or a halt) for continuous trading, and closing
+    /// from `PreClose`, runs a call auction (see [`OrderBook::uncross`]).
+    /// Entering a volatility auction schedules its end after the bands'
+    /// `auction_secs`. Returns the trades this produced.
+    pub fn set_phase(&mut self, next: TradingPhase) -> Result<Vec<Trade>, OrderError> {
+        let from = self.phase;
+        if !from.can_transition_to(next) {
+            return Err(OrderError::InvalidPhaseTransition { from, to: next });
+        }
+        info!("trading phase {:?} -> {:?}", from, next);
+        self.phase = next;
+        self.auction_ends_at = (next == TradingPhase::VolatilityAuction)
+            .then(|| SystemTime::now() + Duration::from_secs(self.bands.auction_secs));
+        if from.auctions_into(next) {
+            let trades = self.uncross();
+            self.track_positions(&trades);
+            return Ok(trades);
+        }
+        Ok(Vec::new())
+    }
 
-        ob.add_order(sample_limit_order(1, Side::Sell, 101, 5));
-        ob.add_order(sample_limit_order(2, Side::Sell, 102, 3));
+    /// The book's circuit breaker limits.
+    pub fn price_bands(&self) -> PriceBands {
+        self.bands
+    }
 
-        let market_buy = sample_market_order(100, Side::Buy, 6);
-        let trades = ob.match_order(market_buy);
+    /// Replaces the circuit breaker limits; applies to orders and trades from now on.
+    pub fn set_price_bands(&mut self, bands: PriceBands) {
+        self.bands = bands;
+    }
 
-        assert_eq!(trades.len(), 2);
-        assert_eq!(trades[0].quantity, 5);
-        assert_eq!(trades[0].price, 101);
-        assert_eq!(trades[1].quantity, 1);
-        assert_eq!(trades[1].price, 102);
+    /// The `(low, high)` range continuous trading is currently confined to, if any.
+    pub fn band_limits(&self) -> Option<(u64, u64)> {
+        self.bands
+            .limits(self.reference_price, self.last_trade_price)
+    }
 
-        let remaining = ob.asks.get(&102).unwrap();
-        assert_eq!(remaining[0].quantity, 2);
+    /// When the running volatility auction is scheduled to end.
+    pub fn volatility_auction_ends_at(&self) -> Option<SystemTime> {
+        self.auction_ends_at
     }
 
-    /// Tests a market sell order that partially fills against a smaller bid.
-    #[test]
-    fn test_partial_fill_market_sell() {
-        let mut ob = OrderBook::new();
+    /// Returns `true` if a volatility auction is running and its period is over at `now`.
+    pub fn volatility_auction_due(&self, now: SystemTime) -> bool {
+        self.phase == TradingPhase::VolatilityAuction
+            && self.auction_ends_at.is_some_and(|end| end <= now)
+    }
 
-        ob.add_order(sample_limit_order(1, Side::Buy, 100, 4));
+    /// The auction price and volume if the book were uncrossed now;
+    /// `None` while bids and asks do not cross.
+    pub fn indicative_auction(&self) -> Option<AuctionQuote> {
+        auction::equilibrium(&self.bids, &self.asks, self.last_trade_price)
+    }
 
-        let market_sell = sample_market_order(200, Side::Sell, 10);
-        let trades = ob.match_order(market_sell);
+    /// Uncrosses the book in one call auction: every bid at or above the
+    /// equilibrium price trades with every ask at or below it, all at that price.
+    ///
+    /// Orders meet in price-time priority and, within a trade, whichever order
+    /// arrived first is the maker. If both belong to the same account the newer
+    /// one is cancelled instead. Icebergs replenish and re-queue as in matching,
+    /// and funded bids trade only what their hold pays for.
+    /// Stops fired by the auction price are matched if the book is now trading.
+    fn uncross(&mut self) -> Vec<Trade> {
+        self.expire_orders(SystemTime::now());
+        let Some(quote) = self.indicative_auction() else {
+            return Vec::new();
+        };
+        info!(
+            "uncrossing {} at {} (imbalance {})",
+            quote.volume,
+            quote.price,
+            quote.imbalance()
+        );
+        let price = quote.price;
+        let mut trades = Vec::new();
+        while let (Some(mut bids), Some(mut asks)) = (
+            self.bids.last_entry().filter(|level| *level.key() >= price),
+            self.asks
+                .first_entry()
+                .filter(|level| *level.key() <= price),
+        ) {
+            let bid = bids.get_mut().front_mut().expect("levels are never empty");
+            let ask = asks.get_mut().front_mut().expect("levels are never empty");
+            let bid_is_newer = bid.timestamp > ask.timestamp;
+            let affordable = self.funds.affordable(bid, price, self.fees.max_bps());
+            if bid.is_self_trade_with(ask) {
+                let newer = if bid_is_newer { &mut bids } else { &mut asks };
+                if let Some(cancelled) = newer.get_mut().pop_front() {
+                    info!(
+                        "self-trade prevented in auction; cancelling {}",
+                        cancelled.id
+                    );
+                    self.index.remove(&cancelled.id);
+                    close_record(
+                        &mut self.records,
+                        &mut self.funds,
+                        cancelled.id,
+                        OrderStatus::Cancelled,
+                    );
+                }
+            } else if affordable == Some(0) {
+                if let Some(cancelled) = bids.get_mut().pop_front() {
+                    info!(
+                        "cancelling order {}; it has used up its funds",
+                        cancelled.id
+                    );
+                    self.index.remove(&cancelled.id);
+                    close_record(
+                        &mut self.records,
+                        &mut self.funds,
+                        cancelled.id,
+                        OrderStatus::Cancelled,
+                    );
+                }
+            } else {
+                let (maker, taker) = if bid_is_newer {
+                    (&*ask, &*bid)
+                } else {
+                    (&*bid, &*ask)
+                };
+                let mut trade = Trade {
+                    price,
+                    quantity: bid
+                        .quantity
+                        .min(ask.quantity)
+                        .min(affordable.unwrap_or(u64::MAX)),
+                    maker_id: maker.id,
+                    taker_id: taker.id,
+                    timestamp: SystemTime::now(),
+                    symbol: bid.pair.code(),
+                    maker_fee: 0,
+                    taker_fee: 0,
+                };
+                self.fees
+                    .charge(&mut trade, maker.owner.as_ref(), taker.owner.as_ref());
+                self.funds.settle(&trade, maker, taker);
+                for id in [trade.maker_id, trade.taker_id] {
+                    if let Some(record) = self.records.get_mut(&id) {
+                        record.record_fill(&trade);
+                    }
+                }
+                bid.quantity -= trade.quantity;
+                ask.quantity -= trade.quantity;
+                trades.push(trade);
+                for level in [&mut bids, &mut asks] {
+                    settle_front(
+                        level.get_mut(),
+                        &mut self.index,
+                        &mut self.records,
+                        &mut self.funds,
+                    );
+                }
+            }
+            if bids.get().is_empty() {
+                bids.remove();
+            }
+            if asks.get().is_empty() {
+                asks.remove();
+            }
+        }
+        if !trades.is_empty() {
+            self.last_trade_price = Some(price);
+            self.reference_price = Some(price);
+        }
+        self.fire_stops(&mut trades);
+        trades
+    }
 
-        assert_eq!(trades.len(), 1);
-        assert_eq!(trades[0].quantity, 4);
-        assert_eq!(trades[0].price, 100);
-        assert!(!ob.bids.contains_key(&100));
+    /// The worst price a protected market order may trade at, from the
+    /// opposite best price right now; `None` for unprotected orders.
+    fn protection_limit(&self, order: &Order) -> Option<u64> {
+        let slippage = order.max_slippage?;
+        let best = match order.side {
+            Side::Buy => self.best_ask(),
+            Side::Sell => self.best_bid(),
+        }?;
+        let limit = slippage.limit_price(order.side, best, self.tick_size);
+        info!(
+            "protecting market order {} at {} ({:?} from {})",
+            order.id, limit, slippage, best
+        );
+        Some(limit)
     }
 
-    /// Tests that a market order does not match when there is no liquidity.
-    #[test]
-    fn test_no_match_for_market_order() {
-        let mut ob = OrderBook::new();
+    /// Rejects a priced order outside the current bands while trading continuously.
+    fn check_band(&self, order: &Order) -> Result<(), OrderError> {
+        let priced = matches!(order.order_type, OrderType::Limit | OrderType::StopLimit);
+        match (order.price, self.band_limits()) {
+            (Some(price), Some((low, high)))
+                if priced && self.phase.matches() && !(low..=high).contains(&price) =>
+            {
+                Err(OrderError::PriceOutsideBand { price, low, high })
+            }
+            _ => Ok(()),
+        }
+    }
 
-        let market_buy = sample_market_order(300, Side::Buy, 10);
-        let trades = ob.match_order(market_buy);
+    /// Returns where the live order `order_id` sits, if anywhere.
+    pub fn location(&self, order_id: u128) -> Option<OrderLocation> {
+        self.index.get(&order_id).copied()
+    }
 
-        assert!(trades.is_empty());
-        assert!(ob.asks.is_empty());
+    /// Looks up a live (resting or parked) order by ID.
+    pub fn order(&self, order_id: u128) -> Option<&Order> {
+        let location = self.location(order_id)?;
+        let (_, price) = location.side_and_price();
+        self.levels(location)
+            .get(&price)?
+            .iter()
+            .find(|o| o.id == order_id)
     }
 
-    /// Tests a market order that exactly matches an available quantity.
-    #[test]
-    fn test_exact_match_market_order() {
-        let mut ob = OrderBook::new();
+    /// Looks up the lifecycle record of any order the book has accepted,
+    /// including ones that are no longer live.
+    pub fn order_record(&self, order_id: u128) -> Option<&OrderRecord> {
+        self.records.get(&order_id)
+    }
 
-        ob.add_order(sample_limit_order(1, Side::Sell, 100, 5));
-        let market_buy = sample_market_order(400, Side::Buy, 5);
-        assert!(ob.asks.len() == 1);
-        let trades = ob.match_order(market_buy);
+    /// Checks that the order exists and belongs to `account`.
+    pub fn check_owner(&self, order_id: u128, account: &str) -> Result<(), OrderError> {
+        let record = self
+            .records
+            .get(&order_id)
+            .ok_or(OrderError::UnknownOrder)?;
+        if record.owner.as_deref() != Some(account) {
+            return Err(OrderError::NotOwner);
+        }
+        Ok(())
+    }
 
-        assert_eq!(trades.len(), 1);
-        assert_eq!(trades[0].quantity, 5);
-        assert!(ob.asks.is_empty());
+    /// Quantity `order_id` still has working in the book (visible and hidden);
+    /// `0` once the order is no longer live.
+    pub fn remaining_quantity(&self, order_id: u128) -> u64 {
+        self.order(order_id).map_or(0, Order::remaining_quantity)
     }
 
-    /// Tests a limit buy order that partially fills and rests the remainder.
-    #[test]
-    fn test_limit_order_partial_match_and_remainder() {
-        let mut ob = OrderBook::new();
+    /// Every live order: bids best-first, then asks best-first, then
+    /// untriggered buy and sell stops by trigger price.
+    pub fn open_orders(&self) -> impl Iterator<Item = &Order> {
+        self.bids
+            .values()
+            .rev()
+            .chain(self.asks.values())
+            .chain(self.buy_stops.values())
+            .chain(self.sell_stops.values())
+            .flatten()
+    }
 
-        ob.add_order(sample_limit_order(1, Side::Sell, 100, 5));
+    /// Starts an [`OrderRecord`] for `order` unless it already has one, first
+    /// dropping the closed records beyond the book's limit.
+    fn track(&mut self, order: &Order) {
+        self.records.prune();
+        self.records.track(order);
+    }
 
-        let limit_buy = sample_limit_order(2, Side::Buy, 101, 10);
-        let trades = ob.match_order(limit_buy);
+    /// The price-level map an order at `location` lives in.
+    fn levels(&self, location: OrderLocation) -> &BTreeMap<u64, VecDeque<Order>> {
+        match location {
+            OrderLocation::Resting(Side::Buy, _) => &self.bids,
+            OrderLocation::Resting(Side::Sell, _) => &self.asks,
+            OrderLocation::Trigger(Side::Buy, _) => &self.buy_stops,
+            OrderLocation::Trigger(Side::Sell, _) => &self.sell_stops,
+        }
+    }
 
-        assert_eq!(trades.len(), 1);
-        assert_eq!(trades[0].quantity, 5);
-        assert_eq!(ob.bids.len(), 1);
-        assert_eq!(ob.bids.get(&101).unwrap()[0].quantity, 5);
+    /// Mutable counterpart of [`OrderBook::levels`].
+    fn levels_mut(&mut self, location: OrderLocation) -> &mut BTreeMap<u64, VecDeque<Order>> {
+        match location {
+            OrderLocation::Resting(Side::Buy, _) => &mut self.bids,
+            OrderLocation::Resting(Side::Sell, _) => &mut self.asks,
+            OrderLocation::Trigger(Side::Buy, _) => &mut self.buy_stops,
+            OrderLocation::Trigger(Side::Sell, _) => &mut self.sell_stops,
+        }
+    }
+
+    /// Adds a **limit** order to the order book (buy or sell).  
+    ///
+    /// `Stop`/`StopLimit` orders are parked in the trigger book under their
+    /// trigger price instead.
+    ///
+    /// If it's a market order (`price == None`), we print a warning and do not add it
+    /// since market orders match immediately and do not rest in the book.
+    pub fn add_order(&mut self, order: Order) {
+        self.track(&order);
+        if order.is_stop() {
+            if let Some(trigger) = order.trigger_price {
+                let location = OrderLocation::Trigger(order.side, trigger);
+                self.index.insert(order.id, location);
+                self.levels_mut(location)
+                    .entry(trigger)
+                    .or_default()
+                    .push_back(order);
+            } else {
+                warn!("stop order {} has no trigger price. Ignoring...", order.id);
+            }
+        } else if let Some(price) = order.price {
+            let mut order = order;
+            order.conceal_reserve();
+            let location = OrderLocation::Resting(order.side, price);
+            self.index.insert(order.id, location);
+            self.levels_mut(location)
+                .entry(price)
+                .or_default()
+                .push_back(order);
+        } else {
+            eprintln!("Warning: Attempting to add a market order to the book. Ignoring...");
+        }
+    }
+
+    /// Matches an incoming order against the order book.
+    ///
+    /// # Behavior
+    /// - If `incoming.side` is `Buy`, we match against the `asks` from lowest to highest.
+    /// - If `incoming.side` is `Sell`, we match against the `bids` from highest to lowest.
+    /// - Limit orders never trade through their limit price.
+    ///
+    /// Time-in-force is applied around the matching step:
+    /// - `FOK` orders are checked against available liquidity first and produce no
+    ///   trades (leaving the book untouched) unless they can be filled completely.
+    /// - `GTC`/`GTD` limit orders rest any leftover quantity in the book.
+    /// - `IOC` (and `FOK`) leftovers are cancelled; market orders never rest.
+    ///
+    /// Post-only limit orders that would cross are either rejected with
+    /// [`OrderError::PostOnlyWouldCross`] or re-priced one tick passive,
+    /// depending on their [`PostOnly`] mode.
+    ///
+    /// `Stop`/`StopLimit` orders are parked in the trigger book unless the last
+    /// trade price already fires them. After every batch of trades, fired stops
+    /// are converted and matched too (see [`OrderBook::fire_stops`]); their trades
+    /// are appended to the returned batch.
+    ///
+    /// Orders the current [`TradingPhase`] does not accept are rejected with
+    /// [`OrderError::NotAcceptedInPhase`]; during a call phase, accepted orders rest
+    /// without matching. While trading continuously, limit prices outside the
+    /// [`PriceBands`] are rejected with [`OrderError::PriceOutsideBand`].
+    ///
+    /// On a funded book, an order its owner cannot cover is rejected with
+    /// [`OrderError::Funds`].
+    ///
+    /// Every accepted order gets an [`OrderRecord`]; rejected orders leave none.
+    ///
+    /// Returns a [`Vec<Trade>`] describing all executed trades.
+    pub fn match_order(&mut self, incoming: Order) -> Result<Vec<Trade>, OrderError> {
+        self.phase.check_order(&incoming)?;
+        self.check_band(&incoming)?;
+        self.funds.reserve(&incoming, self.fees.max_bps())?;
+        let order_id = incoming.id;
+        self.track(&incoming);
+        let result = if incoming.is_stop() {
+            match self.last_trade_price {
+                Some(last) if incoming.is_triggered_at(last) => {
+                    info!("stop order {} triggered on arrival", incoming.id);
+                    self.execute(incoming.into_triggered())
+                }
+                _ => {
+                    info!("parking stop order {} in trigger book", incoming.id);
+                    self.add_order(incoming);
+                    Ok(Vec::new())
+                }
+            }
+        } else {
+            self.execute(incoming)
+        };
+        let mut trades = result.inspect_err(|_| {
+            self.records.remove(&order_id);
+            self.funds.release(order_id);
+        })?;
+        self.fire_stops(&mut trades);
+        self.track_positions(&trades);
+        Ok(trades)
+    }
+
+    /// Matches a (non-stop) order and rests or cancels its remainder according
+    /// to its time-in-force. Updates `last_trade_price` from the resulting trades.
+    ///
+    /// A market order with `max_slippage` becomes a protected order: it never
+    /// trades worse than its slippage limit from the opposite best price on
+    /// arrival, and whatever it cannot fill within that limit is cancelled.
+    ///
+    /// Matching never goes past the price bands. If the order could otherwise
+    /// keep trading beyond them, the book enters a volatility auction; the
+    /// remainder then rests or is cancelled as usual.
+    ///
+    /// Orders not yet holding funds (fired stop buys) reserve them first.
+    fn execute(&mut self, mut incoming: Order) -> Result<Vec<Trade>, OrderError> {
+        self.funds.reserve(&incoming, self.fees.max_bps())?;
+        self.admit(&mut incoming)?;
+        Ok(self.execute_admitted(incoming))
+    }
+
+    /// Checks `incoming` against the trading phase and applies its post-only
+    /// instruction. Changes nothing in the book, so it can run before anything
+    /// that would have to be undone.
+    fn admit(&self, incoming: &mut Order) -> Result<(), OrderError> {
+        if !self.phase.matches() {
+            return self.phase.check_order(incoming);
+        }
+        if incoming.post_only.is_some() {
+            self.apply_post_only(incoming)?;
+        }
+        Ok(())
+    }
+
+    /// The part of [`OrderBook::execute`] after the order was admitted; cannot fail.
+    fn execute_admitted(&mut self, mut incoming: Order) -> Vec<Trade> {
+        if !self.phase.matches() {
+            info!("market not open; resting order {} unmatched", incoming.id);
+            self.add_order(incoming);
+            return Vec::new();
+        }
+        if let Some(record) = self.records.get_mut(&incoming.id) {
+            record.price = incoming.price;
+        }
+        let own_limit = match incoming.order_type {
+            OrderType::Limit => incoming.price,
+            OrderType::Market => self.protection_limit(&incoming),
+            _ => None,
+        };
+        let band = self.band_limits();
+        let price_limit = match (incoming.side, band) {
+            (Side::Buy, Some((_, high))) => Some(own_limit.map_or(high, |p| p.min(high))),
+            (Side::Sell, Some((low, _))) => Some(own_limit.map_or(low, |p| p.max(low))),
+            (_, None) => own_limit,
+        };
+        if incoming.time_in_force == TimeInForce::FOK
+            && self.fillable_quantity(&incoming, price_limit) < incoming.quantity
+        {
+            info!(
+                "FOK order {} cannot be filled in full; killing it",
+                incoming.id
+            );
+            close_record(
+                &mut self.records,
+                &mut self.funds,
+                incoming.id,
+                OrderStatus::Cancelled,
+            );
+            return Vec::new();
+        }
+        let book_side = match incoming.side {
+            Side::Buy => &mut self.asks,
+            Side::Sell => &mut self.bids,
+        };
+        let trades = match_incoming_side(
+            &mut incoming,
+            book_side,
+            &mut self.index,
+            &mut self.records,
+            &mut self.fees,
+            &mut self.funds,
+            price_limit,
+        );
+        if let Some(last) = trades.last() {
+            self.last_trade_price = Some(last.price);
+            self.reference_price.get_or_insert(last.price);
+        }
+        if incoming.quantity > 0
+            && let Some((low, high)) = band
+        {
+            let beyond_band = match incoming.side {
+                Side::Buy => self
+                    .best_ask()
+                    .filter(|&ask| ask > high && own_limit.is_none_or(|limit| ask <= limit)),
+                Side::Sell => self
+                    .best_bid()
+                    .filter(|&bid| bid < low && own_limit.is_none_or(|limit| bid >= limit)),
+            };
+            if let Some(price) = beyond_band {
+                warn!(
+                    "order {} would trade at {} outside band [{}, {}]; starting volatility auction",
+                    incoming.id, price, low, high
+                );
+                self.set_phase(TradingPhase::VolatilityAuction)
+                    .expect("continuous trading can always enter a volatility auction");
+            }
+        }
+        //After matching , if its a resting limit order with leftover qty, insert into book
+        let rests = matches!(incoming.time_in_force, TimeInForce::GTC | TimeInForce::GTD);
+        if incoming.quantity == 0 {
+            close_record(
+                &mut self.records,
+                &mut self.funds,
+                incoming.id,
+                OrderStatus::Filled,
+            );
+        } else if incoming.order_type == OrderType::Limit && rests {
+            warn!("adding (partially or not filled) limit order to book");
+            self.add_order(incoming);
+        } else {
+            info!(
+                "cancelling unfilled remainder {} of {:?} order {}",
+                incoming.quantity, incoming.time_in_force, incoming.id
+            );
+            close_record(
+                &mut self.records,
+                &mut self.funds,
+                incoming.id,
+                OrderStatus::Cancelled,
+            );
+        }
+        trades
+    }
+
+    /// Fires every stop triggered by `last_trade_price`, converting and matching
+    /// them, and appends their trades to `trades`.
+    ///
+    /// Repeats until no more stops fire, since triggered stops can move the
+    /// price and cascade. Within a round the order is deterministic: buy stops
+    /// by ascending trigger price, then sell stops by descending trigger price,
+    /// FIFO within a trigger level.
+    ///
+    /// Stops stay parked while the book is not trading continuously.
+    fn fire_stops(&mut self, trades: &mut Vec<Trade>) {
+        while self.phase.matches()
+            && let Some(last) = self.last_trade_price
+        {
+            let fired = self.take_triggered_stops(last);
+            if fired.is_empty() {
+                break;
+            }
+            for stop in fired {
+                let id = stop.id;
+                info!("stop order {} triggered at last trade price {}", id, last);
+                match self.execute(stop.into_triggered()) {
+                    Ok(stop_trades) => trades.extend(stop_trades),
+                    Err(e) => {
+                        warn!("triggered stop order {} rejected: {}", id, e);
+                        close_record(
+                            &mut self.records,
+                            &mut self.funds,
+                            id,
+                            OrderStatus::Cancelled,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes and returns the stops triggered at `last`, in firing order.
+    fn take_triggered_stops(&mut self, last: u64) -> Vec<Order> {
+        let buy_levels: Vec<u64> = self.buy_stops.range(..=last).map(|(p, _)| *p).collect();
+        let sell_levels: Vec<u64> = self
+            .sell_stops
+            .range(last..)
+            .rev()
+            .map(|(p, _)| *p)
+            .collect();
+        let mut fired = Vec::new();
+        for trigger in buy_levels {
+            fired.extend(self.buy_stops.remove(&trigger).unwrap_or_default());
+        }
+        for trigger in sell_levels {
+            fired.extend(self.sell_stops.remove(&trigger).unwrap_or_default());
+        }
+        for stop in &fired {
+            self.index.remove(&stop.id);
+        }
+        fired
+    }
+
+    /// Highest live bid price, skipping levels that only hold expired GTD orders.
+    pub fn best_bid(&self) -> Option<u64> {
+        best_live_price(self.bids.iter().rev())
+    }
+
+    /// Lowest live ask price, skipping levels that only hold expired GTD orders.
+    pub fn best_ask(&self) -> Option<u64> {
+        best_live_price(self.asks.iter())
+    }
+
+    /// Midpoint of the best bid and ask, rounded down; `None` unless both sides are live.
+    pub fn mid_price(&self) -> Option<u64> {
+        let (bid, ask) = (self.best_bid()?, self.best_ask()?);
+        Some(bid / 2 + ask / 2 + (bid % 2 + ask % 2) / 2)
+    }
+
+    /// Makes sure a post-only order cannot take liquidity.
+    ///
+    /// If the order's price would cross the opposite best price it is either
+    /// rejected or moved to the nearest price on the tick grid passive of that
+    /// price (`Reprice`). Orders that cannot be re-priced to a valid (non-zero,
+    /// non-crossing) price are rejected.
+    fn apply_post_only(&self, incoming: &mut Order) -> Result<(), OrderError> {
+        let Some(price) = incoming.price else {
+            return Err(OrderError::PostOnlyWouldCross);
+        };
+        let tick = self.tick_size;
+        let (best_bid, best_ask) = (self.best_bid(), self.best_ask());
+        let passive_price = match incoming.side {
+            Side::Buy => match best_ask {
+                Some(ask) if price >= ask => Some(ask.saturating_sub(1) / tick * tick),
+                _ => None,
+            },
+            Side::Sell => match best_bid {
+                Some(bid) if price <= bid => {
+                    Some((bid / tick).saturating_add(1).saturating_mul(tick))
+                }
+                _ => None,
+            },
+        };
+        let crosses = |p: u64| match incoming.side {
+            Side::Buy => best_ask.is_some_and(|ask| p >= ask),
+            Side::Sell => best_bid.is_some_and(|bid| p <= bid),
+        };
+        match (passive_price, incoming.post_only) {
+            (None, _) => Ok(()),
+            (Some(new_price), Some(PostOnly::Reprice)) if new_price > 0 && !crosses(new_price) => {
+                info!(
+                    "re-pricing post-only order {} from {} to {}",
+                    incoming.id, price, new_price
+                );
+                incoming.price = Some(new_price);
+                Ok(())
+            }
+            (Some(_), _) => Err(OrderError::PostOnlyWouldCross),
+        }
+    }
+
+    /// Returns how much of `incoming` could fill right now, without mutating
+    /// the book. Used to pre-check `FOK` orders.
+    ///
+    /// Walks the opposite side best-price-first, stopping at `price_limit`
+    /// and ignoring expired GTD orders. Resting orders from the same account
+    /// are skipped under `CancelOldest` and end the walk under any other
+    /// self-trade prevention mode, mirroring what matching would do.
+    ///
+    /// Funded buys, incoming or resting, count only what their holds pay for.
+    fn fillable_quantity(&self, incoming: &Order, price_limit: Option<u64>) -> u64 {
+        let (side, quantity) = (incoming.side, incoming.quantity);
+        let now = SystemTime::now();
+        let max_bps = self.fees.max_bps();
+        let mut budget = self.funds.spendable(incoming);
+        let levels: Box<dyn Iterator<Item = (&u64, &VecDeque<Order>)>> = match side {
+            Side::Buy => Box::new(self.asks.iter()),
+            Side::Sell => Box::new(self.bids.iter().rev()),
+        };
+        let mut available = 0u64;
+        for (&price, orders) in levels {
+            let beyond_limit = match (side, price_limit) {
+                (Side::Buy, Some(limit)) => price > limit,
+                (Side::Sell, Some(limit)) => price < limit,
+                (_, None) => false,
+            };
+            if beyond_limit {
+                break;
+            }
+            for order in orders.iter().filter(|o| !o.is_expired(now)) {
+                if incoming.is_self_trade_with(order) {
+                    match incoming.self_trade_prevention {
+                        SelfTradePrevention::CancelOldest => continue,
+                        _ => return available,
+                    }
+                }
+                let mut fillable = order.remaining_quantity();
+                if let Some(affordable) = self.funds.affordable(order, price, max_bps) {
+                    fillable = fillable.min(affordable);
+                }
+                if let Some(budget) = budget.as_mut() {
+                    fillable = fillable.min(affordable_quantity(*budget, price, max_bps));
+                    *budget -= max_cost(price, fillable, max_bps);
+                }
+                available = available.saturating_add(fillable);
+                if available >= quantity {
+                    return available;
+                }
+            }
+        }
+        available
+    }
+
+    /// Removes every resting or parked GTD order whose deadline is at or before `now`.
+    ///
+    /// Returns the IDs of the expired orders; empty price levels are pruned.
+    pub fn expire_orders(&mut self, now: SystemTime) -> Vec<u128> {
+        let mut expired = Vec::new();
+        for book_side in [
+            &mut self.bids,
+            &mut self.asks,
+            &mut self.buy_stops,
+            &mut self.sell_stops,
+        ] {
+            book_side.retain(|_, queue| {
+                queue.retain(|o| {
+                    if o.is_expired(now) {
+                        expired.push(o.id);
+                        false
+                    } else {
+                        true
+                    }
+                });
+                !queue.is_empty()
+            });
+        }
+        for id in &expired {
+            self.index.remove(id);
+            close_record(
+                &mut self.records,
+                &mut self.funds,
+                *id,
+                OrderStatus::Expired,
+            );
+        }
+        expired
+    }
+
+    /// Atomically amends a resting limit order's price and/or total open quantity.
+    ///
+    /// - A quantity decrease at the same price is applied in place and keeps time priority.
+    /// - A price change or quantity increase re-queues the order at the back of its
+    ///   (new) level. If the new price crosses, the order matches like a new incoming
+    ///   order first (post-only rules still apply).
+    /// - An amended quantity of `0` cancels the order.
+    ///
+    /// Amends are only accepted while orders are (see [`TradingPhase::check_order`]).
+    /// The order's hold follows its new size; an amend its owner cannot cover is
+    /// rejected with [`OrderError::Funds`].
+    ///
+    /// On error the book is left untouched. Returns any trades produced by the
+    /// re-priced order, plus trades from stops they fire.
+    pub fn amend_order(
+        &mut self,
+        order_id: u128,
+        new_price: Option<u64>,
+        new_quantity: Option<u64>,
+    ) -> Result<Vec<Trade>, OrderError> {
+        let (side, price, pos) = self
+            .locate_resting(order_id)
+            .ok_or(OrderError::UnknownOrder)?;
+        if !self.phase.matches() && !self.phase.is_call() {
+            return Err(OrderError::NotAcceptedInPhase(self.phase));
+        }
+        let book_side = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        };
+        let queue = book_side.get_mut(&price).expect("located level exists");
+        let remaining = queue[pos].remaining_quantity();
+        let target_price = new_price.unwrap_or(price);
+        let target_quantity = new_quantity.unwrap_or(remaining);
+
+        if target_quantity == 0 {
+            self.cancel_order(order_id);
+            return Ok(Vec::new());
+        }
+        if target_price == price && target_quantity <= remaining {
+            info!(
+                "amending order {} in place to {}",
+                order_id, target_quantity
+            );
+            queue[pos].decrement(remaining - target_quantity);
+            self.funds.trim(&queue[pos], self.fees.max_bps());
+            return Ok(Vec::new());
+        }
+
+        let mut amended = queue[pos].clone();
+        amended.price = Some(target_price);
+        amended.quantity = target_quantity;
+        amended.hidden_quantity = 0;
+        amended.timestamp = SystemTime::now();
+        // Reject before touching the book so a failed amend keeps the original order
+        self.check_band(&amended)?;
+        self.admit(&mut amended)?;
+        self.funds.rebook(&amended, self.fees.max_bps())?;
+        info!(
+            "re-queueing amended order {} at {:?} for {}",
+            order_id, amended.price, target_quantity
+        );
+        self.remove_order(order_id);
+        let mut trades = self.execute_admitted(amended);
+        self.fire_stops(&mut trades);
+        self.track_positions(&trades);
+        Ok(trades)
+    }
+
+    /// Finds a resting order, returning its side, price level and queue position.
+    fn locate_resting(&self, order_id: u128) -> Option<(Side, u64, usize)> {
+        let location @ OrderLocation::Resting(side, price) = self.location(order_id)? else {
+            return None;
+        };
+        let pos = self
+            .levels(location)
+            .get(&price)?
+            .iter()
+            .position(|o| o.id == order_id)?;
+        Some((side, price, pos))
+    }
+
+    /// Attempts to cancel an order by ID, including untriggered stops.
+    ///
+    /// The index resolves the order's price level directly; only that level's
+    /// queue is searched.
+    ///
+    /// Returns `true` if the order was found *and* removed;
+    /// also prunes the price level if it becomes empty.
+    pub fn cancel_order(&mut self, order_id: u128) -> bool {
+        if self.remove_order(order_id).is_none() {
+            return false;
+        }
+        close_record(
+            &mut self.records,
+            &mut self.funds,
+            order_id,
+            OrderStatus::Cancelled,
+        );
+        true
+    }
+
+    /// Cancels every live order `account` owns (resting and untriggered stops),
+    /// or only those on `side`. Returns the IDs cancelled.
+    pub fn cancel_orders_of(&mut self, account: &str, side: Option<Side>) -> Vec<u128> {
+        let ids: Vec<u128> = self
+            .open_orders()
+            .filter(|o| o.owner.as_deref() == Some(account) && side.is_none_or(|s| s == o.side))
+            .map(|o| o.id)
+            .collect();
+        ids.into_iter()
+            .filter(|&id| self.cancel_order(id))
+            .collect()
+    }
+
+    /// Takes a live order out of the book without touching its record,
+    /// pruning its price level if it becomes empty.
+    fn remove_order(&mut self, order_id: u128) -> Option<Order> {
+        let location = self.index.remove(&order_id)?;
+        let (_, price) = location.side_and_price();
+        let levels = self.levels_mut(location);
+        let queue = levels.get_mut(&price)?;
+        let pos = queue.iter().position(|o| o.id == order_id)?;
+        let order = queue.remove(pos);
+        //prune the price level if needed
+        if queue.is_empty() {
+            info!("cleaning empty price levels");
+            levels.remove(&price);
+        }
+        order
+    }
+}
+
+impl OrderLocation {
+    /// The order's side and its price (limit price or trigger price).
+    pub fn side_and_price(&self) -> (Side, u64) {
+        match *self {
+            OrderLocation::Resting(side, price) | OrderLocation::Trigger(side, price) => {
+                (side, price)
+            }
+        }
+    }
+}
+
+/// Returns the first price level (in iteration order) holding at least one live order.
+fn best_live_price<'a>(
+    mut levels: impl Iterator<Item = (&'a u64, &'a VecDeque<Order>)>,
+) -> Option<u64> {
+    let now = SystemTime::now();
+    levels
+        .find(|(_, orders)| orders.iter().any(|o| !o.is_expired(now)))
+        .map(|(price, _)| *price)
+}
+
+impl Default for OrderBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//SnapShot
+/// returned as a Response payload for `GET /book`.
+///
+/// - `bids`: list of `(price, total_quantity)` in descending order  
+/// - `asks`: list of `(price, total_quantity)` in ascending order
+/// - `pair`: the market this snapshot belongs to.
+/// - `seq`: the depth sequence number the levels are as of, see
+///   [`DepthFeed`](crate::depth::DepthFeed)
+///
+/// Quantities only include the **visible** slice of iceberg orders;
+/// hidden reserves are never published.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookSnapshot {
+    ///Which pair this is for
+    pub pair: Pair,
+    ///Depth sequence number; `BookDelta`s after it apply on top
+    #[serde(default)]
+    pub seq: u64,
+    ///List of `(price, total_quantity)` in descending order
+    pub bids: Vec<(u64, u64)>,
+    ///List of `(price, total_quantity)` in ascending order
+    pub asks: Vec<(u64, u64)>,
+}
+
+impl BookSnapshot {
+    pub fn empty(pair: Pair) -> Self {
+        BookSnapshot {
+            pair,
+            seq: 0,
+            bids: Vec::new(),
+            asks: Vec::new(),
+        }
+    }
+    /// The visible levels of `book`, not yet numbered (`seq` 0);
+    /// [`DepthFeed::publish`](crate::depth::DepthFeed::publish) numbers them.
+    pub fn for_pair(pair: Pair, book: &OrderBook) -> Self {
+        // `Order::quantity` is the visible slice; `hidden_quantity` stays private
+        let bids = book
+            .bids
+            .iter()
+            .rev()
+            .map(|(p, orders)| (*p, orders.iter().map(|o| o.quantity).sum()))
+            .collect();
+        let asks = book
+            .asks
+            .iter()
+            .map(|(p, orders)| (*p, orders.iter().map(|o| o.quantity).sum()))
+            .collect();
+
+        BookSnapshot {
+            pair,
+            seq: 0,
+            bids,
+            asks,
+        }
+    }
+
+    /// Applies `delta`, the next one for this pair, to the levels.
+    ///
+    /// Deltas at or before `seq` are ignored. Returns `false`, leaving the
+    /// snapshot as it was, if deltas in between are missing: the copy is out of
+    /// sync and a new snapshot is needed.
+    pub fn apply(&mut self, delta: &BookDelta) -> bool {
+        if delta.seq <= self.seq {
+            return true;
+        }
+        if delta.seq != self.seq + 1 {
+            return false;
+        }
+        for level in &delta.changes {
+            let (levels, position) = match level.side {
+                Side::Buy => {
+                    let at = self.bids.binary_search_by(|(p, _)| level.price.cmp(p));
+                    (&mut self.bids, at)
+                }
+                Side::Sell => {
+                    let at = self.asks.binary_search_by(|(p, _)| p.cmp(&level.price));
+                    (&mut self.asks, at)
+                }
+            };
+            match (position, level.quantity) {
+                (Ok(i), 0) => {
+                    levels.remove(i);
+                }
+                (Ok(i), quantity) => levels[i].1 = quantity,
+                (Err(_), 0) => {}
+                (Err(i), quantity) => levels.insert(i, (level.price, quantity)),
+            }
+        }
+        self.seq = delta.seq;
+        true
+    }
+}
+
+//tests
+#[cfg(test)]
+mod tests {
+    use crate::{
+        balances::FEE_ACCOUNT,
+        fees::FeeSchedule,
+        instrument::{BTC_USD, ETH_USD},
+        orders::MaxSlippage,
+    };
+
+    use super::*;
+
+    fn sample_limit_order(id: u128, side: Side, price: u64, quantity: u64) -> Order {
+        Order::limit(id, side, price, quantity, ETH_USD)
+    }
+
+    fn sample_market_order(id: u128, side: Side, quantity: u64) -> Order {
+        Order::market(id, side, quantity, BTC_USD)
+    }
+
+    /// Tests a market buy order that partially fills against multiple sell orders.
+    #[test]
+    fn test_partial_fill_market_buy() {
+        let mut ob = OrderBook::new();
+
+        ob.add_order(sample_limit_order(1, Side::Sell, 101, 5));
+        ob.add_order(sample_limit_order(2, Side::Sell, 102, 3));
+
+        let market_buy = sample_market_order(100, Side::Buy, 6);
+        let trades = ob.match_order(market_buy).unwrap();
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].quantity, 5);
+        assert_eq!(trades[0].price, 101);
+        assert_eq!(trades[1].quantity, 1);
+        assert_eq!(trades[1].price, 102);
+
+        let remaining = ob.asks.get(&102).unwrap();
+        assert_eq!(remaining[0].quantity, 2);
+    }
+
+    /// Tests a market sell order that partially fills against a smaller bid.
+    #[test]
+    fn test_partial_fill_market_sell() {
+        let mut ob = OrderBook::new();
+
+        ob.add_order(sample_limit_order(1, Side::Buy, 100, 4));
+
+        let market_sell = sample_market_order(200, Side::Sell, 10);
+        let trades = ob.match_order(market_sell).unwrap();
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 4);
+        assert_eq!(trades[0].price, 100);
+        assert!(!ob.bids.contains_key(&100));
+    }
+
+    /// Tests that a market order does not match when there is no liquidity.
+    #[test]
+    fn test_no_match_for_market_order() {
+        let mut ob = OrderBook::new();
+
+        let market_buy = sample_market_order(300, Side::Buy, 10);
+        let trades = ob.match_order(market_buy).unwrap();
+
+        assert!(trades.is_empty());
+        assert!(ob.asks.is_empty());
+    }
+
+    /// Tests a market order that exactly matches an available quantity.
+    #[test]
+    fn test_exact_match_market_order() {
+        let mut ob = OrderBook::new();
+
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 5));
+        let market_buy = sample_market_order(400, Side::Buy, 5);
+        assert!(ob.asks.len() == 1);
+        let trades = ob.match_order(market_buy).unwrap();
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 5);
+        assert!(ob.asks.is_empty());
+    }
+
+    /// Tests a limit buy order that partially fills and rests the remainder.
+    #[test]
+    fn test_limit_order_partial_match_and_remainder() {
+        let mut ob = OrderBook::new();
+
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 5));
+
+        let limit_buy = sample_limit_order(2, Side::Buy, 101, 10);
+        let trades = ob.match_order(limit_buy).unwrap();
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 5);
+        assert_eq!(ob.bids.len(), 1);
+        assert_eq!(ob.bids.get(&101).unwrap()[0].quantity, 5);
+    }
+
+    /// Tests a limit buy order that finds no match and gets added to the book.
+    #[test]
+    fn test_limit_order_no_match_goes_to_book() {
+        let mut ob = OrderBook::new();
+
+        let limit_buy = sample_limit_order(10, Side::Buy, 90, 8);
+        let trades = ob.match_order(limit_buy).unwrap();
+
+        assert!(trades.is_empty());
+        assert_eq!(ob.bids.len(), 1);
+        assert_eq!(ob.bids.get(&90).unwrap()[0].quantity, 8);
+    }
+
+    /// Tests that FIFO order is respected for multiple orders at the same price.
+    #[test]
+    fn test_queue_fairness_fifo_fill_order() {
+        let mut ob = OrderBook::new();
+
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 4));
+        ob.add_order(sample_limit_order(2, Side::Sell, 100, 6));
+
+        let market_buy = sample_market_order(3, Side::Buy, 9);
+        let trades = ob.match_order(market_buy).unwrap();
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].maker_id, 1);
+        assert_eq!(trades[0].quantity, 4);
+        assert_eq!(trades[1].maker_id, 2);
+        assert_eq!(trades[1].quantity, 5);
+
+        let remaining = ob.asks.get(&100).unwrap();
+        assert_eq!(remaining[0].quantity, 1);
+    }
+
+    /// Tests that a limit buy above the ask price matches immediately (crossing).
+    #[test]
+    fn test_price_level_collision_limit_buy_matches_instead_of_resting() {
+        let mut ob = OrderBook::new();
+
+        ob.add_order(sample_limit_order(1, Side::Sell, 105, 5));
+
+        let crossing_buy = sample_limit_order(2, Side::Buy, 110, 3);
+        let trades = ob.match_order(crossing_buy).unwrap();
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].price, 105);
+        assert_eq!(trades[0].quantity, 3);
+
+        let remaining = ob.asks.get(&105).unwrap();
+        assert_eq!(remaining[0].quantity, 2);
+        assert!(!ob.bids.contains_key(&110));
+    }
+
+    /// Tests that a limit sell below the bid price matches immediately (crossing).
+    #[test]
+    fn test_price_level_collision_limit_sell_matches_instead_of_resting() {
+        let mut ob = OrderBook::new();
+
+        ob.add_order(sample_limit_order(1, Side::Buy, 100, 5));
+
+        let crossing_sell = sample_limit_order(2, Side::Sell, 90, 4);
+        let trades = ob.match_order(crossing_sell).unwrap();
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].price, 100);
+        assert_eq!(trades[0].quantity, 4);
+
+        let remaining = ob.bids.get(&100).unwrap();
+        assert_eq!(remaining[0].quantity, 1);
+        assert!(!ob.asks.contains_key(&90));
+    }
+
+    #[test]
+    fn test_cancel_existing_order() {
+        let mut ob = OrderBook::new();
+        let order = sample_limit_order(42, Side::Buy, 101, 10);
+        ob.add_order(order.clone());
+
+        let was_cancelled = ob.cancel_order(order.id);
+
+        assert!(was_cancelled);
+        assert!(!ob.bids.contains_key(&101)); //price level should have been cleared after order was cancelled
+    }
+
+    #[test]
+    fn test_cancel_nonexistent_order() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 99, 5));
+
+        let result = ob.cancel_order(999);
+        assert!(!result);
+    }
+
+    #[test]
+    fn test_cancel_orders_of_one_account_and_side() {
+        let mut ob = OrderBook::new();
+        for (id, owner, side, price) in [
+            (1, "alice", Side::Buy, 99),
+            (2, "alice", Side::Buy, 98),
+            (3, "alice", Side::Sell, 105),
+            (4, "bob", Side::Buy, 99),
+        ] {
+            let mut order = sample_limit_order(id, side, price, 5);
+            order.owner = Some(owner.to_string());
+            ob.add_order(order);
+        }
+
+        let mut cancelled = ob.cancel_orders_of("alice", Some(Side::Buy));
+        cancelled.sort();
+        assert_eq!(cancelled, vec![1, 2]);
+        assert!(!ob.bids.contains_key(&98));
+        assert_eq!(ob.bids.get(&99).unwrap()[0].id, 4);
+        assert_eq!(ob.order_record(1).unwrap().status, OrderStatus::Cancelled);
+
+        assert_eq!(ob.cancel_orders_of("alice", None), vec![3]);
+        assert!(ob.asks.is_empty());
+        assert!(ob.cancel_orders_of("alice", None).is_empty());
     }
 
-    /// Tests a limit buy order that finds no match and gets added to the book.
     #[test]
-    fn test_limit_order_no_match_goes_to_book() {
+    fn test_ioc_limit_cancels_remainder() {
         let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 3));
 
-        let limit_buy = sample_limit_order(10, Side::Buy, 90, 8);
-        let trades = ob.match_order(limit_buy);
+        let mut ioc_buy = sample_limit_order(2, Side::Buy, 100, 5);
+        ioc_buy.time_in_force = TimeInForce::IOC;
+        let trades = ob.match_order(ioc_buy).unwrap();
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 3);
+        assert!(ob.asks.is_empty());
+        assert!(ob.bids.is_empty(), "IOC remainder must not rest");
+    }
+
+    #[test]
+    fn test_fok_without_enough_liquidity_leaves_book_untouched() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 3));
+        ob.add_order(sample_limit_order(2, Side::Sell, 105, 3));
+
+        // 6 units exist, but only 3 within the limit price
+        let mut fok_buy = sample_limit_order(3, Side::Buy, 100, 6);
+        fok_buy.time_in_force = TimeInForce::FOK;
+        let trades = ob.match_order(fok_buy).unwrap();
 
         assert!(trades.is_empty());
-        assert_eq!(ob.bids.len(), 1);
-        assert_eq!(ob.bids.get(&90).unwrap()[0].quantity, 8);
+        assert_eq!(ob.asks.get(&100).unwrap()[0].quantity, 3);
+        assert_eq!(ob.asks.get(&105).unwrap()[0].quantity, 3);
+        assert!(ob.bids.is_empty());
     }
 
-    /// Tests that FIFO order is respected for multiple orders at the same price.
     #[test]
-    fn test_queue_fairness_fifo_fill_order() {
+    fn test_fok_fills_completely_across_levels() {
         let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 3));
+        ob.add_order(sample_limit_order(2, Side::Sell, 101, 3));
 
-        ob.add_order(sample_limit_order(1, Side::Sell, 100, 4));
-        ob.add_order(sample_limit_order(2, Side::Sell, 100, 6));
+        let mut fok_buy = sample_market_order(3, Side::Buy, 5);
+        fok_buy.time_in_force = TimeInForce::FOK;
+        let trades = ob.match_order(fok_buy).unwrap();
 
-        let market_buy = sample_market_order(3, Side::Buy, 9);
-        let trades = ob.match_order(market_buy);
+        assert_eq!(trades.iter().map(|t| t.quantity).sum::<u64>(), 5);
+        assert_eq!(ob.asks.get(&101).unwrap()[0].quantity, 1);
+    }
+
+    #[test]
+    fn test_gtd_orders_expire() {
+        let mut ob = OrderBook::new();
+        let now = SystemTime::now();
+        let mut gtd = sample_limit_order(1, Side::Buy, 99, 5);
+        gtd.time_in_force = TimeInForce::GTD;
+        gtd.expire_at = Some(now + std::time::Duration::from_secs(60));
+        ob.add_order(gtd);
+        ob.add_order(sample_limit_order(2, Side::Buy, 98, 5));
+
+        assert!(ob.expire_orders(now).is_empty());
+        let expired = ob.expire_orders(now + std::time::Duration::from_secs(61));
+        assert_eq!(expired, vec![1]);
+        assert!(!ob.bids.contains_key(&99));
+        assert!(ob.bids.contains_key(&98));
+    }
+
+    #[test]
+    fn test_expired_gtd_order_is_not_matched() {
+        let mut ob = OrderBook::new();
+        let mut stale = sample_limit_order(1, Side::Sell, 100, 5);
+        stale.time_in_force = TimeInForce::GTD;
+        stale.expire_at = Some(SystemTime::now() - std::time::Duration::from_secs(1));
+        ob.add_order(stale);
+        ob.add_order(sample_limit_order(2, Side::Sell, 101, 5));
+
+        let trades = ob
+            .match_order(sample_market_order(3, Side::Buy, 2))
+            .unwrap();
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].maker_id, 2);
+        assert!(!ob.asks.contains_key(&100));
+    }
+
+    #[test]
+    fn test_post_only_reject_when_crossing() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 5));
+
+        let mut maker_buy = sample_limit_order(2, Side::Buy, 100, 5);
+        maker_buy.post_only = Some(PostOnly::Reject);
+
+        assert_eq!(
+            ob.match_order(maker_buy).unwrap_err(),
+            OrderError::PostOnlyWouldCross
+        );
+        assert_eq!(ob.asks.get(&100).unwrap()[0].quantity, 5);
+        assert!(ob.bids.is_empty());
+    }
+
+    #[test]
+    fn test_post_only_reprice_rests_one_tick_passive() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Buy, 100, 5));
+
+        let mut maker_sell = sample_limit_order(2, Side::Sell, 95, 5);
+        maker_sell.post_only = Some(PostOnly::Reprice);
+        let trades = ob.match_order(maker_sell).unwrap();
+
+        assert!(trades.is_empty());
+        assert_eq!(ob.asks.get(&101).unwrap()[0].id, 2);
+        assert_eq!(ob.bids.get(&100).unwrap()[0].quantity, 5);
+    }
+
+    #[test]
+    fn test_post_only_reprice_stays_on_the_tick_grid() {
+        let mut ob = OrderBook::for_instrument(&InstrumentSpec {
+            tick_size: 5,
+            ..InstrumentSpec::new(ETH_USD)
+        });
+        ob.add_order(sample_limit_order(1, Side::Buy, 100, 5));
+        ob.add_order(sample_limit_order(2, Side::Sell, 110, 5));
+
+        let mut maker_sell = sample_limit_order(3, Side::Sell, 95, 1);
+        maker_sell.post_only = Some(PostOnly::Reprice);
+        assert!(ob.match_order(maker_sell).unwrap().is_empty());
+        assert_eq!(ob.asks.get(&105).unwrap()[0].id, 3);
+
+        let mut maker_buy = sample_limit_order(4, Side::Buy, 120, 1);
+        maker_buy.post_only = Some(PostOnly::Reprice);
+        assert!(ob.match_order(maker_buy).unwrap().is_empty());
+        assert_eq!(ob.bids.get(&100).unwrap()[1].id, 4);
+
+        // Below the lowest ask there is no price left on the grid
+        let mut low = OrderBook::for_instrument(&InstrumentSpec {
+            tick_size: 5,
+            ..InstrumentSpec::new(ETH_USD)
+        });
+        low.add_order(sample_limit_order(5, Side::Sell, 5, 1));
+        let mut maker_buy = sample_limit_order(6, Side::Buy, 5, 1);
+        maker_buy.post_only = Some(PostOnly::Reprice);
+        assert_eq!(
+            low.match_order(maker_buy).unwrap_err(),
+            OrderError::PostOnlyWouldCross
+        );
+        assert!(low.bids.is_empty());
+        assert_index_consistent(&ob);
+    }
+
+    #[test]
+    fn test_post_only_not_crossing_rests_unchanged() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 5));
+
+        let mut maker_buy = sample_limit_order(2, Side::Buy, 99, 5);
+        maker_buy.post_only = Some(PostOnly::Reject);
+        let trades = ob.match_order(maker_buy).unwrap();
+
+        assert!(trades.is_empty());
+        assert_eq!(ob.bids.get(&99).unwrap()[0].id, 2);
+    }
 
+    fn sample_stop_order(id: u128, side: Side, trigger: u64, quantity: u64) -> Order {
+        Order {
+            order_type: OrderType::Stop,
+            trigger_price: Some(trigger),
+            ..sample_market_order(id, side, quantity)
+        }
+    }
+
+    #[test]
+    fn test_stop_parks_until_triggered() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 5));
+        ob.add_order(sample_limit_order(2, Side::Sell, 105, 5));
+
+        let trades = ob
+            .match_order(sample_stop_order(10, Side::Buy, 100, 3))
+            .unwrap();
+        assert!(trades.is_empty(), "no last trade yet, stop must park");
+        assert_eq!(ob.buy_stops.get(&100).unwrap()[0].id, 10);
+        assert_eq!(ob.asks.get(&100).unwrap()[0].quantity, 5);
+
+        // A trade at 100 fires the buy stop, which then buys at market
+        let trades = ob
+            .match_order(sample_market_order(11, Side::Buy, 1))
+            .unwrap();
         assert_eq!(trades.len(), 2);
-        assert_eq!(trades[0].maker_id, 1);
-        assert_eq!(trades[0].quantity, 4);
-        assert_eq!(trades[1].maker_id, 2);
-        assert_eq!(trades[1].quantity, 5);
+        assert_eq!(trades[0].taker_id, 11);
+        assert_eq!(trades[1].taker_id, 10);
+        assert_eq!((trades[1].price, trades[1].quantity), (100, 3));
+        assert_eq!(ob.asks.get(&100).unwrap()[0].quantity, 1);
+        assert_eq!(ob.last_trade_price, Some(100));
+        assert!(ob.buy_stops.is_empty());
+    }
 
-        let remaining = ob.asks.get(&100).unwrap();
-        assert_eq!(remaining[0].quantity, 1);
+    #[test]
+    fn test_stop_limit_rests_as_limit_and_cascades() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Buy, 100, 2));
+        ob.add_order(sample_limit_order(2, Side::Buy, 98, 2));
+
+        // Sell stop-limit at trigger 100, limit 99: fires on a trade at 100
+        let mut stop_limit = sample_stop_order(10, Side::Sell, 100, 4);
+        stop_limit.order_type = OrderType::StopLimit;
+        stop_limit.price = Some(99);
+        // Sell stop at trigger 98: only fires once the price trades down to 98
+        let cascade = sample_stop_order(11, Side::Sell, 98, 1);
+        assert!(ob.match_order(stop_limit).unwrap().is_empty());
+        assert!(ob.match_order(cascade).unwrap().is_empty());
+
+        let trades = ob
+            .match_order(sample_market_order(12, Side::Sell, 1))
+            .unwrap();
+
+        // 12 hits 100, the stop-limit takes the last unit at 100 and rests 3 @ 99;
+        // the price never reaches 98, so the second stop stays parked.
+        assert_eq!(trades.len(), 2);
+        assert_eq!(
+            (trades[1].taker_id, trades[1].price, trades[1].quantity),
+            (10, 100, 1)
+        );
+        assert_eq!(ob.asks.get(&99).unwrap()[0].quantity, 3);
+        assert_eq!(ob.sell_stops.get(&98).unwrap()[0].id, 11);
+
+        // Cancelling reaches into the trigger book too
+        assert!(ob.cancel_order(11));
+        assert!(ob.sell_stops.is_empty());
     }
 
-    /// Tests that a limit buy above the ask price matches immediately (crossing).
     #[test]
-    fn test_price_level_collision_limit_buy_matches_instead_of_resting() {
+    fn test_triggered_stops_fire_in_deterministic_order() {
         let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 1));
+        ob.add_order(sample_limit_order(2, Side::Sell, 101, 10));
+        ob.match_order(sample_stop_order(20, Side::Buy, 100, 1))
+            .unwrap();
+        ob.match_order(sample_stop_order(21, Side::Buy, 99, 1))
+            .unwrap();
+        ob.match_order(sample_stop_order(22, Side::Buy, 99, 1))
+            .unwrap();
+
+        let trades = ob
+            .match_order(sample_market_order(3, Side::Buy, 1))
+            .unwrap();
+        let takers: Vec<u128> = trades.iter().map(|t| t.taker_id).collect();
+        assert_eq!(takers, vec![3, 21, 22, 20]);
+    }
 
-        ob.add_order(sample_limit_order(1, Side::Sell, 105, 5));
+    fn sample_iceberg_order(
+        id: u128,
+        side: Side,
+        price: u64,
+        quantity: u64,
+        display: u64,
+    ) -> Order {
+        Order {
+            display_quantity: Some(display),
+            ..sample_limit_order(id, side, price, quantity)
+        }
+    }
 
-        let crossing_buy = sample_limit_order(2, Side::Buy, 110, 3);
-        let trades = ob.match_order(crossing_buy);
+    #[test]
+    fn test_iceberg_only_shows_display_quantity() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_iceberg_order(1, Side::Sell, 100, 10, 2));
+        ob.add_order(sample_limit_order(2, Side::Sell, 100, 3));
+
+        let snap = BookSnapshot::for_pair(ETH_USD, &ob);
+        assert_eq!(snap.asks, vec![(100, 5)]);
+        let iceberg = &ob.asks.get(&100).unwrap()[0];
+        assert_eq!((iceberg.quantity, iceberg.hidden_quantity), (2, 8));
+    }
+
+    #[test]
+    fn test_iceberg_replenishes_and_loses_priority() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_iceberg_order(1, Side::Sell, 100, 10, 2));
+        ob.add_order(sample_limit_order(2, Side::Sell, 100, 3));
+
+        // Consumes the visible slice of 1, then 1 unit of order 2
+        let trades = ob
+            .match_order(sample_market_order(3, Side::Buy, 3))
+            .unwrap();
+        assert_eq!(trades.len(), 2);
+        assert_eq!((trades[0].maker_id, trades[0].quantity), (1, 2));
+        assert_eq!((trades[1].maker_id, trades[1].quantity), (2, 1));
+
+        // The iceberg replenished a new slice and now sits behind order 2
+        let level = ob.asks.get(&100).unwrap();
+        assert_eq!(level[0].id, 2);
+        assert_eq!(level[1].id, 1);
+        assert_eq!((level[1].quantity, level[1].hidden_quantity), (2, 6));
+        assert_eq!(BookSnapshot::for_pair(ETH_USD, &ob).asks, vec![(100, 4)]);
+    }
+
+    #[test]
+    fn test_iceberg_alone_on_level_fills_through_reserve() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_iceberg_order(1, Side::Buy, 100, 5, 2));
+
+        let trades = ob
+            .match_order(sample_market_order(2, Side::Sell, 5))
+            .unwrap();
+
+        // Slices of 2, 2 and the final 1 are each traded separately
+        let fills: Vec<u64> = trades.iter().map(|t| t.quantity).collect();
+        assert_eq!(fills, vec![2, 2, 1]);
+        assert!(ob.bids.is_empty());
+    }
+
+    #[test]
+    fn test_fok_counts_hidden_reserve() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_iceberg_order(1, Side::Sell, 100, 6, 1));
+
+        let mut fok_buy = sample_limit_order(2, Side::Buy, 100, 6);
+        fok_buy.time_in_force = TimeInForce::FOK;
+        let trades = ob.match_order(fok_buy).unwrap();
+
+        assert_eq!(trades.iter().map(|t| t.quantity).sum::<u64>(), 6);
+        assert!(ob.asks.is_empty());
+    }
+
+    fn owned(mut order: Order, owner: &str, mode: SelfTradePrevention) -> Order {
+        order.owner = Some(owner.to_string());
+        order.self_trade_prevention = mode;
+        order
+    }
+
+    #[test]
+    fn test_stp_cancel_newest_keeps_resting_order() {
+        let mut ob = OrderBook::new();
+        ob.add_order(owned(
+            sample_limit_order(1, Side::Sell, 100, 5),
+            "alice",
+            SelfTradePrevention::CancelNewest,
+        ));
+
+        let buy = owned(
+            sample_limit_order(2, Side::Buy, 100, 5),
+            "alice",
+            SelfTradePrevention::CancelNewest,
+        );
+        let trades = ob.match_order(buy).unwrap();
+
+        assert!(trades.is_empty());
+        assert_eq!(ob.asks.get(&100).unwrap()[0].id, 1);
+        assert!(
+            ob.bids.is_empty(),
+            "incoming remainder is cancelled, not rested"
+        );
+    }
+
+    #[test]
+    fn test_stp_cancel_oldest_removes_resting_and_continues() {
+        let mut ob = OrderBook::new();
+        ob.add_order(owned(
+            sample_limit_order(1, Side::Sell, 100, 5),
+            "alice",
+            SelfTradePrevention::CancelNewest,
+        ));
+        ob.add_order(owned(
+            sample_limit_order(2, Side::Sell, 100, 5),
+            "bob",
+            SelfTradePrevention::CancelNewest,
+        ));
+
+        let buy = owned(
+            sample_market_order(3, Side::Buy, 3),
+            "alice",
+            SelfTradePrevention::CancelOldest,
+        );
+        let trades = ob.match_order(buy).unwrap();
 
         assert_eq!(trades.len(), 1);
-        assert_eq!(trades[0].price, 105);
-        assert_eq!(trades[0].quantity, 3);
+        assert_eq!((trades[0].maker_id, trades[0].quantity), (2, 3));
+        let level = ob.asks.get(&100).unwrap();
+        assert_eq!(level.len(), 1);
+        assert_eq!(level[0].id, 2);
+    }
 
-        let remaining = ob.asks.get(&105).unwrap();
-        assert_eq!(remaining[0].quantity, 2);
-        assert!(!ob.bids.contains_key(&110));
+    #[test]
+    fn test_stp_cancel_both() {
+        let mut ob = OrderBook::new();
+        ob.add_order(owned(
+            sample_limit_order(1, Side::Buy, 100, 5),
+            "alice",
+            SelfTradePrevention::CancelNewest,
+        ));
+        ob.add_order(sample_limit_order(2, Side::Buy, 99, 5));
+
+        let sell = owned(
+            sample_limit_order(3, Side::Sell, 99, 8),
+            "alice",
+            SelfTradePrevention::CancelBoth,
+        );
+        let trades = ob.match_order(sell).unwrap();
+
+        assert!(trades.is_empty());
+        assert!(!ob.bids.contains_key(&100));
+        assert!(ob.asks.is_empty());
+        assert_eq!(ob.bids.get(&99).unwrap()[0].quantity, 5);
     }
 
-    /// Tests that a limit sell below the bid price matches immediately (crossing).
     #[test]
-    fn test_price_level_collision_limit_sell_matches_instead_of_resting() {
+    fn test_stp_decrement_and_cancel() {
         let mut ob = OrderBook::new();
+        ob.add_order(owned(
+            sample_limit_order(1, Side::Sell, 100, 5),
+            "alice",
+            SelfTradePrevention::CancelNewest,
+        ));
+        ob.add_order(sample_limit_order(2, Side::Sell, 101, 5));
+
+        let buy = owned(
+            sample_limit_order(3, Side::Buy, 101, 7),
+            "alice",
+            SelfTradePrevention::DecrementAndCancel,
+        );
+        let trades = ob.match_order(buy).unwrap();
+
+        // 5 units are decremented against alice's own ask, the other 2 trade with order 2
+        assert_eq!(trades.len(), 1);
+        assert_eq!((trades[0].maker_id, trades[0].quantity), (2, 2));
+        assert!(!ob.asks.contains_key(&100));
+        assert_eq!(ob.asks.get(&101).unwrap()[0].quantity, 3);
+        assert!(ob.bids.is_empty());
+    }
 
-        ob.add_order(sample_limit_order(1, Side::Buy, 100, 5));
+    #[test]
+    fn test_fok_does_not_count_own_liquidity() {
+        let mut ob = OrderBook::new();
+        ob.add_order(owned(
+            sample_limit_order(1, Side::Sell, 100, 5),
+            "alice",
+            SelfTradePrevention::CancelNewest,
+        ));
+        ob.add_order(sample_limit_order(2, Side::Sell, 100, 5));
+
+        let mut fok = owned(
+            sample_limit_order(3, Side::Buy, 100, 6),
+            "alice",
+            SelfTradePrevention::CancelOldest,
+        );
+        fok.time_in_force = TimeInForce::FOK;
+        let trades = ob.match_order(fok).unwrap();
 
-        let crossing_sell = sample_limit_order(2, Side::Sell, 90, 4);
-        let trades = ob.match_order(crossing_sell);
+        assert!(trades.is_empty());
+        assert_eq!(ob.asks.get(&100).unwrap().len(), 2, "book untouched");
+    }
+
+    #[test]
+    fn test_amend_quantity_down_keeps_priority() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 5));
+        ob.add_order(sample_limit_order(2, Side::Sell, 100, 5));
+
+        let trades = ob.amend_order(1, None, Some(2)).unwrap();
+
+        assert!(trades.is_empty());
+        let level = ob.asks.get(&100).unwrap();
+        assert_eq!((level[0].id, level[0].quantity), (1, 2));
+    }
+
+    #[test]
+    fn test_amend_quantity_up_loses_priority() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 5));
+        ob.add_order(sample_limit_order(2, Side::Sell, 100, 5));
+
+        ob.amend_order(1, None, Some(8)).unwrap();
+
+        let level = ob.asks.get(&100).unwrap();
+        assert_eq!(level[0].id, 2);
+        assert_eq!((level[1].id, level[1].quantity), (1, 8));
+    }
+
+    #[test]
+    fn test_amend_price_crossing_trades() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Buy, 95, 5));
+        ob.add_order(sample_limit_order(2, Side::Sell, 100, 3));
+
+        let trades = ob.amend_order(1, Some(100), None).unwrap();
 
         assert_eq!(trades.len(), 1);
-        assert_eq!(trades[0].price, 100);
-        assert_eq!(trades[0].quantity, 4);
+        assert_eq!((trades[0].maker_id, trades[0].taker_id), (2, 1));
+        assert_eq!(trades[0].quantity, 3);
+        assert!(!ob.bids.contains_key(&95));
+        assert_eq!(ob.bids.get(&100).unwrap()[0].quantity, 2);
+    }
 
-        let remaining = ob.bids.get(&100).unwrap();
-        assert_eq!(remaining[0].quantity, 1);
-        assert!(!ob.asks.contains_key(&90));
+    #[test]
+    fn test_amend_rejected_post_only_keeps_original() {
+        let mut ob = OrderBook::new();
+        let mut maker = sample_limit_order(1, Side::Buy, 95, 5);
+        maker.post_only = Some(PostOnly::Reject);
+        ob.add_order(maker);
+        ob.add_order(sample_limit_order(2, Side::Sell, 100, 3));
+
+        assert_eq!(
+            ob.amend_order(1, Some(100), None).unwrap_err(),
+            OrderError::PostOnlyWouldCross
+        );
+        assert_eq!(ob.bids.get(&95).unwrap()[0].id, 1);
+        assert_eq!(
+            ob.amend_order(99, Some(100), None).unwrap_err(),
+            OrderError::UnknownOrder
+        );
     }
 
     #[test]
-    fn test_cancel_existing_order() {
+    fn test_amend_rejected_for_funds_keeps_original_and_its_hold() {
+        use crate::{
+            balances::{Balance, BalanceLedger},
+            instrument::Asset,
+        };
+        use std::sync::{Arc, Mutex};
+
+        let ledger = Arc::new(Mutex::new(BalanceLedger::default()));
+        ledger
+            .lock()
+            .unwrap()
+            .deposit("alice", &Asset::USD, 1_000)
+            .unwrap();
+        let mut ob =
+            OrderBook::for_instrument(&InstrumentSpec::new(ETH_USD)).with_balances(ledger.clone());
+        let any = SelfTradePrevention::CancelNewest;
+        ob.match_order(owned(
+            sample_limit_order(1, Side::Buy, 100, 5),
+            "alice",
+            any,
+        ))
+        .unwrap();
+        ob.add_order(sample_limit_order(2, Side::Sell, 110, 5));
+
+        assert!(matches!(
+            ob.amend_order(1, Some(110), Some(20)).unwrap_err(),
+            OrderError::Funds(_)
+        ));
+        let original = &ob.bids.get(&100).unwrap()[0];
+        assert_eq!((original.id, original.quantity), (1, 5));
+        assert_eq!(ob.location(1), Some(OrderLocation::Resting(Side::Buy, 100)));
+        assert_eq!(ob.order_record(1).unwrap().price, Some(100));
+        assert_eq!(ob.asks.get(&110).unwrap()[0].quantity, 5, "nothing traded");
+        assert_eq!(
+            ledger.lock().unwrap().balance("alice", &Asset::USD),
+            Balance {
+                available: 500,
+                reserved: 500
+            }
+        );
+        assert_index_consistent(&ob);
+    }
+
+    /// Checks that the index lists exactly the orders held in the book.
+    fn assert_index_consistent(ob: &OrderBook) {
+        let mut held = 0;
+        for (location_of, levels) in [
+            (
+                OrderLocation::Resting as fn(Side, u64) -> OrderLocation,
+                [&ob.bids, &ob.asks],
+            ),
+            (OrderLocation::Trigger, [&ob.buy_stops, &ob.sell_stops]),
+        ] {
+            for (side, book_side) in [Side::Buy, Side::Sell].into_iter().zip(levels) {
+                for (price, queue) in book_side {
+                    for order in queue {
+                        held += 1;
+                        assert_eq!(ob.location(order.id), Some(location_of(side, *price)));
+                    }
+                }
+            }
+        }
+        assert_eq!(ob.index.len(), held);
+    }
+
+    #[test]
+    fn test_index_tracks_matching_cancels_and_stops() {
         let mut ob = OrderBook::new();
-        let order = sample_limit_order(42, Side::Buy, 101, 10);
-        ob.add_order(order.clone());
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 2));
+        ob.add_order(sample_iceberg_order(2, Side::Sell, 101, 6, 2));
+        ob.add_order(sample_limit_order(3, Side::Buy, 90, 5));
+        ob.match_order(sample_stop_order(4, Side::Buy, 105, 1))
+            .unwrap();
+        assert_index_consistent(&ob);
+        assert_eq!(ob.location(4), Some(OrderLocation::Trigger(Side::Buy, 105)));
+
+        // Fills order 1 and part of the iceberg, which replenishes in place
+        ob.match_order(sample_market_order(5, Side::Buy, 3))
+            .unwrap();
+        assert_eq!(ob.location(1), None);
+        assert_eq!(ob.order(2).unwrap().remaining_quantity(), 5);
+        assert_index_consistent(&ob);
+
+        assert!(ob.cancel_order(3));
+        assert!(!ob.cancel_order(3));
+        assert!(ob.cancel_order(4));
+        assert_index_consistent(&ob);
+
+        ob.amend_order(2, Some(95), None).unwrap();
+        assert_eq!(ob.location(2), Some(OrderLocation::Resting(Side::Sell, 95)));
+        assert_index_consistent(&ob);
+    }
 
-        let was_cancelled = ob.cancel_order(order.id);
+    #[test]
+    fn test_order_records_follow_fills_and_outlive_the_book() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 4));
+        ob.add_order(sample_limit_order(2, Side::Sell, 102, 2));
+        ob.add_order(sample_limit_order(5, Side::Sell, 103, 4));
+
+        ob.match_order(sample_market_order(3, Side::Buy, 2))
+            .unwrap();
+        let maker = ob.order_record(1).unwrap();
+        assert_eq!(maker.status, OrderStatus::PartiallyFilled);
+        assert_eq!(ob.remaining_quantity(1), 2);
+
+        // IOC takes 1's last 2 @ 100 and 2 @ 102, the last unit is cancelled
+        let mut ioc = sample_limit_order(4, Side::Buy, 102, 5);
+        ioc.time_in_force = TimeInForce::IOC;
+        ob.match_order(ioc).unwrap();
+
+        let maker = ob.order_record(1).unwrap();
+        assert_eq!(maker.status, OrderStatus::Filled);
+        assert_eq!(maker.fills.len(), 2);
+        assert_eq!(ob.remaining_quantity(1), 0);
+        let taker = ob.order_record(4).unwrap();
+        assert_eq!(taker.status, OrderStatus::Cancelled);
+        assert_eq!(taker.filled_quantity(), 4);
+        assert_eq!(taker.average_fill_price(), Some(101.0));
+        assert_eq!(ob.order_record(3).unwrap().status, OrderStatus::Filled);
+        assert_eq!(ob.order_record(2).unwrap().status, OrderStatus::Filled);
+        assert_eq!(ob.open_orders().map(|o| o.id).collect::<Vec<_>>(), vec![5]);
+    }
 
-        assert!(was_cancelled);
-        assert!(!ob.bids.contains_key(&101)); //price level should have been cleared after order was cancelled
+    #[test]
+    fn test_order_records_for_cancel_expiry_amend_and_rejects() {
+        let mut ob = OrderBook::new();
+        let mut gtd = sample_limit_order(1, Side::Buy, 99, 5);
+        gtd.time_in_force = TimeInForce::GTD;
+        gtd.expire_at = Some(SystemTime::now() + std::time::Duration::from_secs(60));
+        ob.add_order(gtd);
+        ob.add_order(sample_limit_order(2, Side::Buy, 98, 5));
+        ob.add_order(sample_limit_order(3, Side::Sell, 105, 5));
+
+        ob.expire_orders(SystemTime::now() + std::time::Duration::from_secs(61));
+        assert_eq!(ob.order_record(1).unwrap().status, OrderStatus::Expired);
+
+        // Re-queueing on amend is not a cancel
+        ob.amend_order(2, Some(97), None).unwrap();
+        let amended = ob.order_record(2).unwrap();
+        assert_eq!(
+            (amended.status, amended.price),
+            (OrderStatus::New, Some(97))
+        );
+        assert!(ob.cancel_order(2));
+        assert_eq!(ob.order_record(2).unwrap().status, OrderStatus::Cancelled);
+
+        let mut maker_buy = sample_limit_order(4, Side::Buy, 105, 5);
+        maker_buy.post_only = Some(PostOnly::Reject);
+        assert!(ob.match_order(maker_buy).is_err());
+        assert!(
+            ob.order_record(4).is_none(),
+            "rejected orders are not recorded"
+        );
     }
 
     #[test]
-    fn test_cancel_nonexistent_order() {
+    fn test_closed_order_records_are_pruned_oldest_first() {
+        let mut ob = OrderBook::new().with_closed_record_limit(2);
+        for id in 1..=3 {
+            ob.add_order(sample_limit_order(id, Side::Buy, 100, 1));
+            assert!(ob.cancel_order(id));
+        }
+        ob.add_order(sample_limit_order(4, Side::Buy, 100, 1));
+
+        // Arriving order 4 drops the oldest closed record; open ones are kept
+        assert!(ob.order_record(1).is_none());
+        assert_eq!(ob.order_record(2).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(ob.order_record(3).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(ob.order_record(4).unwrap().status, OrderStatus::New);
+        assert_eq!(ob.check_owner(1, "alice"), Err(OrderError::UnknownOrder));
+    }
+
+    #[test]
+    fn test_pre_open_collects_then_uncrosses_on_open() {
         let mut ob = OrderBook::new();
-        ob.add_order(sample_limit_order(1, Side::Sell, 99, 5));
+        ob.set_phase(TradingPhase::Halted).unwrap();
+        ob.set_phase(TradingPhase::PreOpen).unwrap();
+
+        ob.match_order(sample_limit_order(1, Side::Sell, 100, 5))
+            .unwrap();
+        let trades = ob
+            .match_order(sample_limit_order(2, Side::Buy, 102, 3))
+            .unwrap();
+        assert!(trades.is_empty(), "nothing matches before the open");
+        assert_eq!(ob.best_bid(), Some(102));
+        assert_eq!(
+            ob.match_order(sample_market_order(3, Side::Buy, 1))
+                .unwrap_err(),
+            OrderError::NotAcceptedInPhase(TradingPhase::PreOpen)
+        );
+
+        let trades = ob.set_phase(TradingPhase::Continuous).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(
+            (trades[0].maker_id, trades[0].taker_id, trades[0].price),
+            (1, 2, 100)
+        );
+        assert_eq!(ob.asks.get(&100).unwrap()[0].quantity, 2);
+        assert!(ob.bids.is_empty());
+        assert_index_consistent(&ob);
+    }
 
-        let result = ob.cancel_order(999);
-        assert!(!result);
+    #[test]
+    fn test_opening_auction_trades_everything_at_one_price() {
+        let mut ob = OrderBook::new();
+        ob.set_phase(TradingPhase::Halted).unwrap();
+        ob.set_phase(TradingPhase::PreOpen).unwrap();
+        for order in [
+            sample_limit_order(1, Side::Sell, 100, 4),
+            sample_limit_order(2, Side::Sell, 101, 4),
+            sample_limit_order(3, Side::Buy, 103, 5),
+            sample_limit_order(4, Side::Buy, 102, 5),
+            sample_limit_order(5, Side::Sell, 104, 3),
+        ] {
+            ob.match_order(order).unwrap();
+        }
+        // 101 and 102 both trade 8 with a buy surplus of 2; buy pressure picks 102
+        let quote = ob.indicative_auction().unwrap();
+        assert_eq!((quote.price, quote.volume, quote.imbalance()), (102, 8, 2));
+
+        let trades = ob.set_phase(TradingPhase::Continuous).unwrap();
+        let fills: Vec<_> = trades
+            .iter()
+            .map(|t| (t.maker_id, t.taker_id, t.price, t.quantity))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![(1, 3, 102, 4), (2, 3, 102, 1), (2, 4, 102, 3)],
+            "earlier orders are makers, every trade at the auction price"
+        );
+        assert_eq!(ob.last_trade_price, Some(102));
+        assert_eq!(ob.best_bid(), Some(102));
+        assert_eq!(ob.remaining_quantity(4), 2);
+        assert_eq!(ob.best_ask(), Some(104));
+        assert!(ob.indicative_auction().is_none());
+        for id in [1, 2, 3] {
+            assert_eq!(ob.order_record(id).unwrap().status, OrderStatus::Filled);
+        }
+        assert_eq!(
+            ob.order_record(4).unwrap().status,
+            OrderStatus::PartiallyFilled
+        );
+        assert_index_consistent(&ob);
+    }
+
+    #[test]
+    fn test_closing_auction_cancels_self_trades_and_keeps_stops_parked() {
+        let mut ob = OrderBook::new();
+        ob.match_order(sample_stop_order(9, Side::Sell, 100, 1))
+            .unwrap();
+        ob.set_phase(TradingPhase::PreClose).unwrap();
+        assert_eq!(
+            ob.match_order(sample_market_order(8, Side::Buy, 1))
+                .unwrap_err(),
+            OrderError::NotAcceptedInPhase(TradingPhase::PreClose)
+        );
+
+        let own = SelfTradePrevention::CancelNewest;
+        ob.match_order(owned(sample_limit_order(1, Side::Buy, 101, 5), "a", own))
+            .unwrap();
+        ob.match_order(owned(sample_limit_order(2, Side::Sell, 100, 5), "a", own))
+            .unwrap();
+        ob.match_order(sample_limit_order(3, Side::Sell, 100, 3))
+            .unwrap();
+
+        let trades = ob.set_phase(TradingPhase::Closed).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(
+            (trades[0].maker_id, trades[0].taker_id, trades[0].price),
+            (1, 3, 100)
+        );
+        assert_eq!(
+            ob.order_record(2).unwrap().status,
+            OrderStatus::Cancelled,
+            "the newer side of a self-trade is cancelled"
+        );
+        assert_eq!(ob.remaining_quantity(1), 2);
+        assert!(
+            ob.order(9).is_some(),
+            "stops do not fire once the market is closed"
+        );
+        assert_index_consistent(&ob);
+    }
+
+    #[test]
+    fn test_price_band_breach_starts_volatility_auction() {
+        let mut ob = OrderBook::new();
+        ob.set_price_bands(PriceBands {
+            static_bps: None,
+            dynamic_bps: Some(500),
+            auction_secs: 0,
+        });
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 2));
+        ob.add_order(sample_limit_order(2, Side::Sell, 104, 2));
+        ob.add_order(sample_limit_order(3, Side::Sell, 120, 5));
+        assert_eq!(ob.band_limits(), None, "no band before the first trade");
+        ob.match_order(sample_limit_order(4, Side::Buy, 100, 1))
+            .unwrap();
+        assert_eq!(ob.band_limits(), Some((95, 105)));
+        assert_eq!(ob.reference_price, Some(100));
+
+        assert_eq!(
+            ob.match_order(sample_limit_order(5, Side::Buy, 110, 1))
+                .unwrap_err(),
+            OrderError::PriceOutsideBand {
+                price: 110,
+                low: 95,
+                high: 105
+            }
+        );
+
+        let trades = ob
+            .match_order(sample_market_order(6, Side::Buy, 10))
+            .unwrap();
+        let prices: Vec<_> = trades.iter().map(|t| (t.price, t.quantity)).collect();
+        assert_eq!(prices, vec![(100, 1), (104, 2)], "nothing trades past 105");
+        assert_eq!(ob.phase(), TradingPhase::VolatilityAuction);
+        assert_eq!(ob.order_record(6).unwrap().status, OrderStatus::Cancelled);
+        assert!(ob.volatility_auction_due(SystemTime::now()));
+
+        // Price discovery during the auction is not banded
+        ob.match_order(sample_limit_order(7, Side::Buy, 125, 5))
+            .unwrap();
+        let trades = ob.set_phase(TradingPhase::Continuous).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!((trades[0].price, trades[0].quantity), (120, 5));
+        assert_eq!(ob.reference_price, Some(120));
+        assert_eq!(ob.volatility_auction_ends_at(), None);
+        assert_index_consistent(&ob);
+    }
+
+    #[test]
+    fn test_market_orders_with_max_slippage_stop_at_their_limit() {
+        let mut ob = OrderBook::for_instrument(&InstrumentSpec {
+            tick_size: 5,
+            ..InstrumentSpec::new(ETH_USD)
+        });
+        ob.add_order(sample_limit_order(1, Side::Sell, 100, 2));
+        ob.add_order(sample_limit_order(2, Side::Sell, 105, 2));
+        ob.add_order(sample_limit_order(3, Side::Sell, 115, 2));
+
+        let mut buy = sample_market_order(4, Side::Buy, 10);
+        buy.max_slippage = Some(MaxSlippage::Ticks(1));
+        let trades = ob.match_order(buy).unwrap();
+        let fills: Vec<_> = trades.iter().map(|t| (t.price, t.quantity)).collect();
+        assert_eq!(
+            fills,
+            vec![(100, 2), (105, 2)],
+            "one tick above the best ask"
+        );
+        assert_eq!(ob.best_ask(), Some(115));
+        let record = ob.order_record(4).unwrap();
+        assert_eq!(record.status, OrderStatus::Cancelled);
+        assert_eq!(record.filled_quantity(), 4);
+
+        ob.add_order(sample_limit_order(5, Side::Buy, 200, 1));
+        ob.add_order(sample_limit_order(6, Side::Buy, 196, 1));
+        ob.add_order(sample_limit_order(7, Side::Buy, 190, 1));
+        let mut sell = sample_market_order(8, Side::Sell, 5);
+        sell.max_slippage = Some(MaxSlippage::Bps(200));
+        let trades = ob.match_order(sell).unwrap();
+        let prices: Vec<_> = trades.iter().map(|t| t.price).collect();
+        assert_eq!(prices, vec![200, 196], "2% below the best bid");
+        assert_eq!(ob.best_bid(), Some(190));
+        assert_index_consistent(&ob);
+    }
+
+    #[test]
+    fn test_trades_carry_maker_and_taker_fees() {
+        let mut ob = OrderBook::for_instrument(&InstrumentSpec {
+            fees: FeeSchedule {
+                maker_bps: -10,
+                taker_bps: 30,
+                tiers: Vec::new(),
+            },
+            ..InstrumentSpec::new(ETH_USD)
+        });
+        let any = SelfTradePrevention::CancelNewest;
+        ob.add_order(owned(
+            sample_limit_order(1, Side::Sell, 1_000, 5),
+            "mm",
+            any,
+        ));
+        let trades = ob
+            .match_order(owned(sample_market_order(2, Side::Buy, 5), "taker", any))
+            .unwrap();
+        assert_eq!((trades[0].maker_fee, trades[0].taker_fee), (-5, 15));
+        assert_eq!(
+            ob.order_record(2).unwrap().fills[0].taker_fee,
+            15,
+            "recorded fills carry the fees too"
+        );
+
+        let report = ob.fee_report(ETH_USD);
+        assert_eq!((report.volume, report.net_fees), (5_000, 10));
+        assert_eq!(report.accounts["mm"].maker_fees, -5);
+        assert_eq!(report.accounts["taker"].taker_fees, 15);
+    }
+
+    #[test]
+    fn test_funded_orders_reserve_settle_and_release() {
+        use crate::{
+            balances::{Balance, BalanceLedger},
+            errors::BalanceError,
+            instrument::Asset,
+        };
+        use std::sync::{Arc, Mutex};
+
+        let ledger = Arc::new(Mutex::new(BalanceLedger::default()));
+        ledger
+            .lock()
+            .unwrap()
+            .deposit("alice", &Asset::USD, 10_000)
+            .unwrap();
+        ledger
+            .lock()
+            .unwrap()
+            .deposit("bob", &Asset::ETH, 10)
+            .unwrap();
+        let balance = |account: &str, asset: &Asset| ledger.lock().unwrap().balance(account, asset);
+        let mut ob = OrderBook::for_instrument(&InstrumentSpec {
+            fees: FeeSchedule {
+                maker_bps: -10,
+                taker_bps: 30,
+                tiers: Vec::new(),
+            },
+            ..InstrumentSpec::new(ETH_USD)
+        })
+        .with_balances(ledger.clone());
+        let any = SelfTradePrevention::CancelNewest;
+
+        // Notional plus the highest fee the pair charges
+        ob.match_order(owned(
+            sample_limit_order(1, Side::Buy, 1_000, 5),
+            "alice",
+            any,
+        ))
+        .unwrap();
+        assert_eq!(
+            balance("alice", &Asset::USD),
+            Balance {
+                available: 4_985,
+                reserved: 5_015
+            }
+        );
+
+        // The buyer pays 3000 less its 3 rebate, the seller gets 3000 less its 9 fee
+        ob.match_order(owned(
+            sample_limit_order(2, Side::Sell, 1_000, 3),
+            "bob",
+            any,
+        ))
+        .unwrap();
+        assert_eq!(balance("alice", &Asset::USD).reserved, 5_015 - 2_997);
+        assert_eq!(balance("alice", &Asset::ETH).available, 3);
+        assert_eq!(
+            balance("bob", &Asset::ETH),
+            Balance {
+                available: 7,
+                reserved: 0
+            }
+        );
+        assert_eq!(balance("bob", &Asset::USD).available, 2_991);
+
+        assert!(ob.cancel_order(1));
+        assert_eq!(
+            balance("alice", &Asset::USD),
+            Balance {
+                available: 7_003,
+                reserved: 0
+            }
+        );
+
+        assert_eq!(
+            ob.match_order(owned(
+                sample_limit_order(3, Side::Sell, 1_000, 10),
+                "alice",
+                any
+            ))
+            .unwrap_err(),
+            OrderError::Funds(BalanceError::InsufficientFunds {
+                asset: Asset::ETH,
+                required: 10,
+                available: 3
+            })
+        );
+        assert!(ob.order_record(3).is_none());
+
+        // A market buy takes what its funds cover and cancels the rest
+        ob.match_order(owned(
+            sample_limit_order(4, Side::Sell, 1_000, 7),
+            "bob",
+            any,
+        ))
+        .unwrap();
+        let mut market = owned(sample_market_order(5, Side::Buy, 10), "alice", any);
+        market.pair = ETH_USD;
+        let trades = ob.match_order(market).unwrap();
+        assert_eq!(trades.iter().map(|t| t.quantity).sum::<u64>(), 6);
+        assert_eq!(ob.order_record(5).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(
+            balance("alice", &Asset::USD),
+            Balance {
+                available: 7_003 - 6_018,
+                reserved: 0
+            }
+        );
+        assert_eq!(balance("bob", &Asset::ETH).reserved, 1);
+
+        // Fees move to the fee account, so no quote is created or lost
+        assert_eq!(balance(FEE_ACCOUNT, &Asset::USD).available, 6 + 12);
+        let quote_total: u64 = ["alice", "bob", FEE_ACCOUNT]
+            .iter()
+            .map(|account| balance(account, &Asset::USD).total())
+            .sum();
+        assert_eq!(quote_total, 10_000);
+    }
+
+    #[test]
+    fn test_underfunded_fok_buy_is_killed_without_trading() {
+        use crate::{balances::BalanceLedger, instrument::Asset};
+        use std::sync::{Arc, Mutex};
+
+        let ledger = Arc::new(Mutex::new(BalanceLedger::default()));
+        ledger
+            .lock()
+            .unwrap()
+            .deposit("alice", &Asset::USD, 3_000)
+            .unwrap();
+        ledger
+            .lock()
+            .unwrap()
+            .deposit("bob", &Asset::ETH, 5)
+            .unwrap();
+        let mut ob =
+            OrderBook::for_instrument(&InstrumentSpec::new(ETH_USD)).with_balances(ledger.clone());
+        let any = SelfTradePrevention::CancelNewest;
+        ob.match_order(owned(
+            sample_limit_order(1, Side::Sell, 1_000, 5),
+            "bob",
+            any,
+        ))
+        .unwrap();
+
+        // Five units are offered, but alice's funds cover only three
+        let mut fok_buy = owned(sample_market_order(2, Side::Buy, 5), "alice", any);
+        fok_buy.pair = ETH_USD;
+        fok_buy.time_in_force = TimeInForce::FOK;
+        assert!(ob.match_order(fok_buy).unwrap().is_empty());
+        assert_eq!(ob.order_record(2).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(ob.asks.get(&1_000).unwrap()[0].quantity, 5);
+        let balance = ledger.lock().unwrap().balance("alice", &Asset::USD);
+        assert_eq!((balance.available, balance.reserved), (3_000, 0));
+
+        let mut fok_buy = owned(sample_market_order(3, Side::Buy, 3), "alice", any);
+        fok_buy.pair = ETH_USD;
+        fok_buy.time_in_force = TimeInForce::FOK;
+        let trades = ob.match_order(fok_buy).unwrap();
+        assert_eq!(trades.iter().map(|t| t.quantity).sum::<u64>(), 3);
+    }
+
+    #[test]
+    fn test_resting_buy_short_of_raised_fees_is_capped_then_cancelled() {
+        use crate::{balances::BalanceLedger, instrument::Asset};
+        use std::sync::{Arc, Mutex};
+
+        let ledger = Arc::new(Mutex::new(BalanceLedger::default()));
+        ledger
+            .lock()
+            .unwrap()
+            .deposit("alice", &Asset::USD, 5_000)
+            .unwrap();
+        ledger
+            .lock()
+            .unwrap()
+            .deposit("bob", &Asset::ETH, 5)
+            .unwrap();
+        let balance = |account: &str, asset: &Asset| ledger.lock().unwrap().balance(account, asset);
+        let mut ob =
+            OrderBook::for_instrument(&InstrumentSpec::new(ETH_USD)).with_balances(ledger.clone());
+        let any = SelfTradePrevention::CancelNewest;
+        ob.match_order(owned(
+            sample_limit_order(1, Side::Buy, 1_000, 5),
+            "alice",
+            any,
+        ))
+        .unwrap();
+        assert_eq!(balance("alice", &Asset::USD).reserved, 5_000);
+
+        ob.apply_instrument(&InstrumentSpec {
+            fees: FeeSchedule {
+                maker_bps: 100,
+                taker_bps: 100,
+                tiers: Vec::new(),
+            },
+            ..InstrumentSpec::new(ETH_USD)
+        });
+        let trades = ob
+            .match_order(owned(
+                sample_limit_order(2, Side::Sell, 1_000, 5),
+                "bob",
+                any,
+            ))
+            .unwrap();
+        assert_eq!(trades.iter().map(|t| t.quantity).sum::<u64>(), 4);
+        let record = ob.order_record(1).unwrap();
+        assert_eq!(record.status, OrderStatus::Cancelled);
+        assert_eq!(record.filled_quantity(), 4);
+        assert_eq!(ob.best_ask(), Some(1_000), "the seller's remainder rests");
+
+        // Alice paid 4000 plus her 40 fee and got the rest of her hold back
+        assert_eq!(balance("alice", &Asset::USD).total(), 960);
+        assert_eq!(balance("alice", &Asset::USD).reserved, 0);
+        assert_eq!(balance("bob", &Asset::USD).available, 4_000 - 40);
+        assert_eq!(balance(FEE_ACCOUNT, &Asset::USD).available, 80);
+        assert_index_consistent(&ob);
+    }
+
+    #[test]
+    fn test_fills_build_positions_marked_to_mid() {
+        let mut ob = OrderBook::for_instrument(&InstrumentSpec {
+            fees: FeeSchedule {
+                maker_bps: 0,
+                taker_bps: 100,
+                tiers: Vec::new(),
+            },
+            ..InstrumentSpec::new(ETH_USD)
+        });
+        let any = SelfTradePrevention::CancelNewest;
+        ob.add_order(owned(sample_limit_order(1, Side::Sell, 100, 5), "mm", any));
+        ob.match_order(owned(
+            sample_limit_order(2, Side::Buy, 100, 3),
+            "taker",
+            any,
+        ))
+        .unwrap();
+        assert_eq!(ob.position_report(ETH_USD, "nobody"), None);
+
+        // No bids, so no mid to mark to
+        let taker = ob.position_report(ETH_USD, "taker").unwrap();
+        assert_eq!(
+            (taker.quantity, taker.realized_pnl, taker.unrealized_pnl),
+            (3, -3, None)
+        );
+
+        ob.add_order(sample_limit_order(3, Side::Buy, 90, 1));
+        assert_eq!(ob.mid_price(), Some(95));
+        let maker = ob.position_report(ETH_USD, "mm").unwrap();
+        assert_eq!(maker.quantity, -3);
+        assert_eq!(maker.average_entry_price, Some(100.0));
+        assert_eq!(maker.unrealized_pnl, Some(15));
+        let taker = ob.position_report(ETH_USD, "taker").unwrap();
+        assert_eq!(taker.unrealized_pnl, Some(-15));
+    }
+
+    #[test]
+    fn test_halted_and_closed_reject_orders_and_bad_transitions() {
+        let mut ob = OrderBook::new();
+        ob.add_order(sample_limit_order(1, Side::Buy, 100, 5));
+        ob.set_phase(TradingPhase::Halted).unwrap();
+
+        assert_eq!(
+            ob.match_order(sample_limit_order(2, Side::Sell, 100, 5))
+                .unwrap_err(),
+            OrderError::NotAcceptedInPhase(TradingPhase::Halted)
+        );
+        assert!(ob.amend_order(1, Some(101), None).is_err());
+        assert!(ob.cancel_order(1));
+
+        ob.set_phase(TradingPhase::Closed).unwrap();
+        assert_eq!(
+            ob.set_phase(TradingPhase::Continuous).unwrap_err(),
+            OrderError::InvalidPhaseTransition {
+                from: TradingPhase::Closed,
+                to: TradingPhase::Continuous
+            }
+        );
+        assert_eq!(ob.phase(), TradingPhase::Closed);
     }
 }
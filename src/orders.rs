@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
 
-use crate::instrument::Pair;
+use crate::{instrument::Pair, trade::Trade};
 
 /// Represents which side of the market the order is on.
 ///
@@ -20,20 +23,201 @@ pub enum Side {
     Sell, // Ask
 }
 
-/// Specifies whether an order is a Limit or Market order.
+/// Specifies the kind of order.
 ///
 /// - `Limit`: Executes at a specific price or better
 /// - `Market`: Executes immediately at the best available price
+/// - `Stop`: Parked until the last trade price reaches `trigger_price`, then becomes a `Market` order
+/// - `StopLimit`: Parked until the last trade price reaches `trigger_price`, then becomes a
+///   `Limit` order at `price`
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum OrderType {
     Limit,
     Market,
+    Stop,
+    StopLimit,
+}
+
+/// Specifies how long an order stays working before the engine cancels it.
+///
+/// - `GTC`: Good-till-cancelled; any unfilled remainder rests in the book
+/// - `IOC`: Immediate-or-cancel; fills what it can, the remainder is cancelled
+/// - `FOK`: Fill-or-kill; fills completely in one go or produces no trades at all
+/// - `GTD`: Good-till-date; rests like `GTC` until `expire_at`, then expires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum TimeInForce {
+    #[default]
+    GTC,
+    IOC,
+    FOK,
+    GTD,
+}
+
+/// How a post-only (maker-only) order is handled if it would take liquidity on arrival.
+///
+/// - `Reject`: the order is rejected outright
+/// - `Reprice`: the order is re-priced one tick passive of the opposite best price, so it rests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PostOnly {
+    Reject,
+    Reprice,
+}
+
+/// Identifies the trading account that owns an order.
+pub type AccountId = String;
+
+/// What the engine does when an incoming order would match a resting order
+/// from the same account. Evaluated before each fill, using the incoming order's mode.
+///
+/// - `CancelNewest`: cancel the incoming order's remainder; the resting order stays
+/// - `CancelOldest`: cancel the resting order and keep matching the incoming one
+/// - `CancelBoth`: cancel both the resting order and the incoming remainder
+/// - `DecrementAndCancel`: reduce both by the smaller remaining size without trading;
+///   whichever reaches zero is cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum SelfTradePrevention {
+    #[default]
+    CancelNewest,
+    CancelOldest,
+    CancelBoth,
+    DecrementAndCancel,
+}
+
+/// How far from the opposite best price (at arrival) a market order may trade.
+///
+/// - `Ticks(n)`: at most `n` ticks of the pair's `tick_size`
+/// - `Bps(n)`: at most `n` basis points of the best price
+///
+/// Serialized as `{"ticks": 5}` or `{"bps": 50}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaxSlippage {
+    Ticks(u64),
+    Bps(u64),
+}
+
+impl MaxSlippage {
+    /// The worst acceptable price for a `side` order when the opposite best is `best`.
+    pub fn limit_price(self, side: Side, best: u64, tick_size: u64) -> u64 {
+        let offset = match self {
+            MaxSlippage::Ticks(ticks) => ticks.saturating_mul(tick_size),
+            MaxSlippage::Bps(bps) => (best as u128 * bps as u128 / 10_000) as u64,
+        };
+        match side {
+            Side::Buy => best.saturating_add(offset),
+            Side::Sell => best.saturating_sub(offset),
+        }
+    }
+}
+
+/// Where an order is in its lifecycle.
+///
+/// - `New`: accepted and working, nothing traded yet
+/// - `PartiallyFilled`: some quantity traded, the rest is still working
+/// - `Filled`: the whole quantity traded
+/// - `Cancelled`: removed before filling completely — by the trader, by self-trade
+///   prevention, or because an `IOC`/`FOK`/market remainder could not trade
+/// - `Expired`: a `GTD` order whose deadline passed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OrderStatus {
+    New,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Expired,
+}
+
+impl OrderStatus {
+    /// Returns `true` while the order can still trade.
+    pub fn is_open(&self) -> bool {
+        matches!(self, OrderStatus::New | OrderStatus::PartiallyFilled)
+    }
+}
+
+/// What the engine remembers about an order, live or not, so its
+/// outcome can still be queried after it leaves the book.
+///
+/// - `original_quantity` is the size the order was submitted with
+/// - `price` follows re-pricing (post-only `Reprice`, amends)
+/// - `fills` lists every trade the order took part in, as maker or taker
+#[derive(Debug, Clone)]
+pub struct OrderRecord {
+    pub id: u128,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub price: Option<u64>,
+    pub original_quantity: u64,
+    pub owner: Option<AccountId>,
+    pub client_order_id: Option<String>,
+    pub status: OrderStatus,
+    pub fills: Vec<Trade>,
+}
+
+impl OrderRecord {
+    /// Starts tracking `order` as `New`.
+    pub fn new(order: &Order) -> Self {
+        OrderRecord {
+            id: order.id,
+            side: order.side,
+            order_type: order.order_type,
+            price: order.price,
+            original_quantity: order.remaining_quantity(),
+            owner: order.owner.clone(),
+            client_order_id: order.client_order_id.clone(),
+            status: OrderStatus::New,
+            fills: Vec::new(),
+        }
+    }
+
+    /// Total quantity traded so far.
+    pub fn filled_quantity(&self) -> u64 {
+        self.fills.iter().map(|t| t.quantity).sum()
+    }
+
+    /// Volume-weighted average price of the fills, if any.
+    pub fn average_fill_price(&self) -> Option<f64> {
+        let filled = self.filled_quantity();
+        if filled == 0 {
+            return None;
+        }
+        let notional: u128 = self
+            .fills
+            .iter()
+            .map(|t| t.price as u128 * t.quantity as u128)
+            .sum();
+        Some(notional as f64 / filled as f64)
+    }
+
+    /// Adds a trade this order took part in; an open order becomes `PartiallyFilled`.
+    pub fn record_fill(&mut self, trade: &Trade) {
+        self.fills.push(trade.clone());
+        if self.status.is_open() {
+            self.status = OrderStatus::PartiallyFilled;
+        }
+    }
+
+    /// Moves the order to a final `status`. The first final status wins.
+    pub fn close(&mut self, status: OrderStatus) {
+        if self.status.is_open() {
+            self.status = status;
+        }
+    }
 }
 
 /// An order submitted by a trader.
 ///
 /// - `price` is optional for market orders
 /// - `timestamp` is used for time-priority (FIFO within price level)
+/// - `expire_at` is only set for [`TimeInForce::GTD`] orders
+/// - `post_only` marks a limit order that must never take liquidity
+/// - `trigger_price` is only set for `Stop`/`StopLimit` orders
+/// - `display_quantity` makes a resting limit order an iceberg: only `quantity`
+///   (at most `display_quantity`) is visible, the rest waits in `hidden_quantity`
+/// - `owner` is the account the order belongs to; orders with the same owner never
+///   trade with each other (see [`SelfTradePrevention`])
+/// - `max_slippage` protects a market order (or a fired stop): it never trades worse
+///   than this far from the opposite best price on arrival
+/// - `client_order_id` is the owner's own ID for the order, see [`ClientOrderIds`]
 #[derive(Debug, Clone)]
 pub struct Order {
     pub id: u128,
@@ -43,4 +227,197 @@ pub struct Order {
     pub quantity: u64,
     pub timestamp: SystemTime,
     pub pair: Pair,
+    pub time_in_force: TimeInForce,
+    pub expire_at: Option<SystemTime>,
+    pub post_only: Option<PostOnly>,
+    pub trigger_price: Option<u64>,
+    pub display_quantity: Option<u64>,
+    pub hidden_quantity: u64,
+    pub owner: Option<AccountId>,
+    pub self_trade_prevention: SelfTradePrevention,
+    pub max_slippage: Option<MaxSlippage>,
+    pub client_order_id: Option<String>,
+}
+
+impl Order {
+    /// A plain good-till-cancelled limit order, without an owner or any other
+    /// instruction, timestamped now.
+    pub fn limit(id: u128, side: Side, price: u64, quantity: u64, pair: Pair) -> Self {
+        Order {
+            id,
+            side,
+            order_type: OrderType::Limit,
+            price: Some(price),
+            quantity,
+            timestamp: SystemTime::now(),
+            pair,
+            time_in_force: TimeInForce::GTC,
+            expire_at: None,
+            post_only: None,
+            trigger_price: None,
+            display_quantity: None,
+            hidden_quantity: 0,
+            owner: None,
+            self_trade_prevention: SelfTradePrevention::CancelNewest,
+            max_slippage: None,
+            client_order_id: None,
+        }
+    }
+
+    /// Like [`Order::limit`], but a market order.
+    pub fn market(id: u128, side: Side, quantity: u64, pair: Pair) -> Self {
+        Order {
+            order_type: OrderType::Market,
+            price: None,
+            ..Order::limit(id, side, 0, quantity, pair)
+        }
+    }
+
+    /// Returns `true` if this is a GTD order whose deadline is at or before `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        matches!(self.expire_at, Some(deadline) if deadline <= now)
+    }
+
+    /// Total quantity still open, visible and hidden.
+    pub fn remaining_quantity(&self) -> u64 {
+        self.quantity + self.hidden_quantity
+    }
+
+    /// Moves everything above `display_quantity` into the hidden reserve.
+    /// Called when an iceberg starts resting in the book.
+    pub fn conceal_reserve(&mut self) {
+        if let Some(display) = self.display_quantity
+            && self.quantity > display
+        {
+            self.hidden_quantity += self.quantity - display;
+            self.quantity = display;
+        }
+    }
+
+    /// Refills an exhausted visible slice from the hidden reserve.
+    ///
+    /// Returns `true` if there was reserve left to show.
+    pub fn replenish(&mut self) -> bool {
+        if self.hidden_quantity == 0 {
+            return false;
+        }
+        let slice = self
+            .display_quantity
+            .unwrap_or(self.hidden_quantity)
+            .min(self.hidden_quantity);
+        self.quantity += slice;
+        self.hidden_quantity -= slice;
+        true
+    }
+
+    /// Reduces the open quantity by `qty` without trading, taking from the
+    /// hidden reserve first so the visible slice is kept as long as possible.
+    pub fn decrement(&mut self, qty: u64) {
+        let from_hidden = qty.min(self.hidden_quantity);
+        self.hidden_quantity -= from_hidden;
+        self.quantity = self.quantity.saturating_sub(qty - from_hidden);
+    }
+
+    /// Returns `true` if matching against `other` would be a self-trade.
+    pub fn is_self_trade_with(&self, other: &Order) -> bool {
+        self.owner.is_some() && self.owner == other.owner
+    }
+
+    /// Returns `true` for orders that wait in the trigger book (`Stop`/`StopLimit`).
+    pub fn is_stop(&self) -> bool {
+        matches!(self.order_type, OrderType::Stop | OrderType::StopLimit)
+    }
+
+    /// Returns `true` if a stop on this order's side fires at `last_trade_price`.
+    ///
+    /// Buy stops fire once the price trades **at or above** the trigger,
+    /// sell stops once it trades **at or below** it.
+    pub fn is_triggered_at(&self, last_trade_price: u64) -> bool {
+        match (self.side, self.trigger_price) {
+            (Side::Buy, Some(trigger)) => last_trade_price >= trigger,
+            (Side::Sell, Some(trigger)) => last_trade_price <= trigger,
+            (_, None) => false,
+        }
+    }
+
+    /// Converts a fired stop into the order it stands for:
+    /// `Stop` becomes `Market`, `StopLimit` becomes `Limit` at `price`.
+    pub fn into_triggered(mut self) -> Order {
+        self.order_type = match self.order_type {
+            OrderType::Stop => OrderType::Market,
+            OrderType::StopLimit => OrderType::Limit,
+            other => other,
+        };
+        self.trigger_price = None;
+        self
+    }
+}
+
+/// The first order an account placed under a client order ID.
+///
+/// - `trades` are the ones the order produced when it was placed, so a retry
+///   can be answered with the original acknowledgement
+#[derive(Debug, Clone)]
+pub struct ClientOrder {
+    pub pair: Pair,
+    pub order_id: u128,
+    pub trades: Vec<Trade>,
+    pub placed_at: SystemTime,
+}
+
+/// Client order IDs by account.
+///
+/// An ID stays reserved for its order for the idempotency window: placing it
+/// again within the window is a retry of the same order. After the window it
+/// can be reused, and then refers to the newer order; [`ClientOrderIds::prune`]
+/// forgets IDs whose window has passed.
+#[derive(Debug, Default)]
+pub struct ClientOrderIds {
+    orders: HashMap<AccountId, HashMap<String, ClientOrder>>,
+}
+
+impl ClientOrderIds {
+    /// The latest order `account` placed as `client_order_id`.
+    pub fn get(&self, account: &str, client_order_id: &str) -> Option<&ClientOrder> {
+        self.orders.get(account)?.get(client_order_id)
+    }
+
+    /// The order `account` placed as `client_order_id` less than `window` before `now`.
+    pub fn recent(
+        &self,
+        account: &str,
+        client_order_id: &str,
+        window: Duration,
+        now: SystemTime,
+    ) -> Option<&ClientOrder> {
+        self.get(account, client_order_id).filter(|order| {
+            now.duration_since(order.placed_at)
+                .is_ok_and(|age| age < window)
+        })
+    }
+
+    /// Remembers `order` as `account`'s `client_order_id`.
+    pub fn insert(&mut self, account: &str, client_order_id: String, order: ClientOrder) {
+        self.orders
+            .entry(account.to_string())
+            .or_default()
+            .insert(client_order_id, order);
+    }
+
+    /// Forgets the IDs placed `window` or more before `now`.
+    ///
+    /// Returns the number of IDs forgotten.
+    pub fn prune(&mut self, window: Duration, now: SystemTime) -> usize {
+        let mut pruned = 0;
+        for ids in self.orders.values_mut() {
+            let before = ids.len();
+            ids.retain(|_, order| {
+                now.duration_since(order.placed_at)
+                    .map_or(true, |age| age < window)
+            });
+            pruned += before - ids.len();
+        }
+        self.orders.retain(|_, ids| !ids.is_empty());
+        pruned
+    }
 }
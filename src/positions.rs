@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    instrument::Pair,
+    orders::{AccountId, Side},
+};
+
+/// An account's net position in one pair, built up from its fills.
+///
+/// - `quantity`: net base quantity, positive when long and negative when short
+/// - `realized_pnl`: quote gained or lost on quantity closed out, net of all fees paid
+///
+/// The cost of the open quantity is kept in quote units (negative when short), so
+/// the average entry price is exact and closing part of a position realizes its
+/// share of that cost.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Position {
+    pub quantity: i64,
+    pub realized_pnl: i64,
+    cost: i128,
+}
+
+impl Position {
+    /// Adds a fill of `quantity` at `price` on `side`, paying `fee` (negative for a rebate).
+    ///
+    /// A fill against the position closes it first, realizing the difference to
+    /// the average entry price; anything beyond opens a position the other way.
+    pub fn apply_fill(&mut self, side: Side, price: u64, quantity: u64, fee: i64) {
+        let price = price as i128;
+        let mut delta = match side {
+            Side::Buy => quantity as i128,
+            Side::Sell => -(quantity as i128),
+        };
+        let held = self.quantity as i128;
+        if held != 0 && held.signum() != delta.signum() {
+            let closed = held.abs().min(delta.abs()) * held.signum();
+            let closed_cost = self.cost * closed / held;
+            self.realized_pnl += (closed * price - closed_cost) as i64;
+            self.cost -= closed_cost;
+            self.quantity -= closed as i64;
+            delta += closed;
+        }
+        self.quantity += delta as i64;
+        self.cost += delta * price;
+        self.realized_pnl -= fee;
+    }
+
+    /// What the open quantity cost per unit; `None` while flat.
+    pub fn average_entry_price(&self) -> Option<f64> {
+        (self.quantity != 0).then(|| self.cost as f64 / self.quantity as f64)
+    }
+
+    /// Gain or loss if the open quantity were closed at `mark`.
+    pub fn unrealized_pnl(&self, mark: u64) -> i64 {
+        (self.quantity as i128 * mark as i128 - self.cost) as i64
+    }
+}
+
+/// The positions of every account that has traded a pair.
+#[derive(Debug, Clone, Default)]
+pub struct PositionLedger {
+    accounts: HashMap<AccountId, Position>,
+}
+
+impl PositionLedger {
+    /// Adds a fill to `account`'s position.
+    pub fn apply_fill(&mut self, account: &str, side: Side, price: u64, quantity: u64, fee: i64) {
+        self.accounts
+            .entry(account.to_string())
+            .or_default()
+            .apply_fill(side, price, quantity, fee);
+    }
+
+    /// `account`'s position, if it has ever traded the pair.
+    pub fn get(&self, account: &str) -> Option<&Position> {
+        self.accounts.get(account)
+    }
+}
+
+/// One position as reported by `GET /positions` and on the account websocket.
+///
+/// - `average_entry_price`: `null` while flat
+/// - `mark_price`: the book mid the unrealized P&L is marked to; `null`, like
+///   `unrealized_pnl`, while either side of the book is empty
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionReport {
+    pub pair: Pair,
+    pub quantity: i64,
+    pub average_entry_price: Option<f64>,
+    pub realized_pnl: i64,
+    pub unrealized_pnl: Option<i64>,
+    pub mark_price: Option<u64>,
+}
+
+impl PositionReport {
+    pub fn new(pair: Pair, position: &Position, mark_price: Option<u64>) -> Self {
+        PositionReport {
+            pair,
+            quantity: position.quantity,
+            average_entry_price: position.average_entry_price(),
+            realized_pnl: position.realized_pnl,
+            unrealized_pnl: mark_price.map(|mark| position.unrealized_pnl(mark)),
+            mark_price,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closing_realizes_against_average_entry() {
+        let mut position = Position::default();
+        position.apply_fill(Side::Buy, 100, 2, 0);
+        position.apply_fill(Side::Buy, 110, 2, 4);
+        assert_eq!(position.quantity, 4);
+        assert_eq!(position.average_entry_price(), Some(105.0));
+        assert_eq!(position.unrealized_pnl(120), 60);
+
+        position.apply_fill(Side::Sell, 120, 1, 1);
+        assert_eq!(position.realized_pnl, 15 - 4 - 1);
+        assert_eq!(position.average_entry_price(), Some(105.0));
+
+        // Selling through flat opens a short at the fill price
+        position.apply_fill(Side::Sell, 90, 5, 0);
+        assert_eq!(position.quantity, -2);
+        assert_eq!(position.realized_pnl, 10 - 45);
+        assert_eq!(position.average_entry_price(), Some(90.0));
+        assert_eq!(position.unrealized_pnl(80), 20);
+
+        position.apply_fill(Side::Buy, 80, 2, 0);
+        assert_eq!(position.quantity, 0);
+        assert_eq!(position.realized_pnl, -35 + 20);
+        assert_eq!(position.average_entry_price(), None);
+        assert_eq!(position.unrealized_pnl(1_000), 0);
+    }
+}
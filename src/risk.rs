@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    errors::RiskViolation,
+    orderbook::OrderBook,
+    orders::{AccountId, Side},
+};
+
+/// An account's pre-trade limits; a limit left out is not enforced.
+///
+/// - `max_order_quantity`: largest quantity (visible and hidden) of one order
+/// - `max_order_notional`: largest `price * quantity` of one order; market orders
+///   are valued at the best opposite price, stop orders at their trigger price
+/// - `max_open_orders`: live orders the account may have in one pair
+/// - `max_net_position`: largest absolute net position in one pair the account
+///   could reach if the order and its open orders on the same side filled in full
+/// - `max_orders_per_second`: new orders and amendments in any one-second window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RiskLimits {
+    pub max_order_quantity: Option<u64>,
+    pub max_order_notional: Option<u64>,
+    pub max_open_orders: Option<usize>,
+    pub max_net_position: Option<u64>,
+    pub max_orders_per_second: Option<u32>,
+}
+
+/// An order, or an amendment of one, as the risk checks see it.
+#[derive(Debug, Clone, Copy)]
+pub struct RiskCheck<'a> {
+    pub account: &'a str,
+    pub side: Side,
+    pub quantity: u64,
+    /// Limit or trigger price; `None` for market orders.
+    pub price: Option<u64>,
+    /// The live order an amendment replaces; it does not count as open.
+    pub replaces: Option<u128>,
+}
+
+/// Every account's risk limits and recent order times.
+#[derive(Debug, Default)]
+pub struct RiskManager {
+    limits: HashMap<AccountId, RiskLimits>,
+    recent: HashMap<AccountId, VecDeque<Instant>>,
+}
+
+impl RiskManager {
+    /// `account`'s limits; none are enforced until they are set.
+    pub fn limits(&self, account: &str) -> RiskLimits {
+        self.limits.get(account).copied().unwrap_or_default()
+    }
+
+    /// Replaces `account`'s limits.
+    pub fn set_limits(&mut self, account: &str, limits: RiskLimits) {
+        self.limits.insert(account.to_string(), limits);
+    }
+
+    /// Checks `order` against its account's limits and the account's open
+    /// orders and position in `book`. Every checked order counts towards the
+    /// account's order rate, whether or not it passes.
+    pub fn check(
+        &mut self,
+        order: &RiskCheck,
+        book: &OrderBook,
+        now: Instant,
+    ) -> Result<(), RiskViolation> {
+        let limits = self.limits(order.account);
+        if let Some(limit) = limits.max_orders_per_second {
+            self.check_rate(order.account, limit, now)?;
+        }
+        if let Some(limit) = limits.max_order_quantity
+            && order.quantity > limit
+        {
+            return Err(RiskViolation::MaxOrderQuantity {
+                quantity: order.quantity,
+                limit,
+            });
+        }
+        let price = order.price.or(match order.side {
+            Side::Buy => book.best_ask(),
+            Side::Sell => book.best_bid(),
+        });
+        if let Some(limit) = limits.max_order_notional
+            && let Some(price) = price
+        {
+            let notional = price.saturating_mul(order.quantity);
+            if notional > limit {
+                return Err(RiskViolation::MaxOrderNotional { notional, limit });
+            }
+        }
+
+        let (open, working) = book
+            .open_orders()
+            .filter(|o| o.owner.as_deref() == Some(order.account) && Some(o.id) != order.replaces)
+            .fold((0, 0u64), |(open, working), o| {
+                let same_side = if o.side == order.side {
+                    o.remaining_quantity()
+                } else {
+                    0
+                };
+                (open + 1, working.saturating_add(same_side))
+            });
+        if let Some(limit) = limits.max_open_orders
+            && order.replaces.is_none()
+            && open >= limit
+        {
+            return Err(RiskViolation::MaxOpenOrders { open, limit });
+        }
+        if let Some(limit) = limits.max_net_position {
+            let held = book.net_position(order.account);
+            let working = working.saturating_add(order.quantity) as i64;
+            let position = match order.side {
+                Side::Buy => held.saturating_add(working),
+                Side::Sell => held.saturating_sub(working),
+            };
+            // Orders that only shrink a position stay allowed even over the limit
+            if position.unsigned_abs() > limit && position.unsigned_abs() > held.unsigned_abs() {
+                return Err(RiskViolation::MaxNetPosition { position, limit });
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts an order in `account`'s last second, refusing it if `limit`
+    /// orders were already counted.
+    fn check_rate(&mut self, account: &str, limit: u32, now: Instant) -> Result<(), RiskViolation> {
+        let window = self.recent.entry(account.to_string()).or_default();
+        while window
+            .front()
+            .is_some_and(|&t| now.duration_since(t) >= Duration::from_secs(1))
+        {
+            window.pop_front();
+        }
+        if window.len() >= limit as usize {
+            return Err(RiskViolation::MaxOrderRate { limit });
+        }
+        window.push_back(now);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{instrument::BTC_USD, orders::Order};
+
+    fn limit_order(id: u128, owner: &str, side: Side, price: u64, quantity: u64) -> Order {
+        Order {
+            owner: Some(owner.to_string()),
+            ..Order::limit(id, side, price, quantity, BTC_USD)
+        }
+    }
+
+    fn buy(account: &str, price: Option<u64>, quantity: u64) -> RiskCheck<'_> {
+        RiskCheck {
+            account,
+            side: Side::Buy,
+            quantity,
+            price,
+            replaces: None,
+        }
+    }
+
+    #[test]
+    fn test_limits_are_checked_per_account() {
+        let mut book = OrderBook::new();
+        book.add_order(limit_order(1, "alice", Side::Buy, 100, 4));
+        book.add_order(limit_order(2, "bob", Side::Sell, 110, 5));
+        let mut risk = RiskManager::default();
+        risk.set_limits(
+            "alice",
+            RiskLimits {
+                max_order_quantity: Some(10),
+                max_order_notional: Some(1_000),
+                max_open_orders: Some(2),
+                max_net_position: Some(8),
+                max_orders_per_second: None,
+            },
+        );
+        let now = Instant::now();
+
+        assert_eq!(
+            risk.check(&buy("alice", Some(100), 11), &book, now),
+            Err(RiskViolation::MaxOrderQuantity {
+                quantity: 11,
+                limit: 10
+            })
+        );
+        // A market order is valued at the best ask
+        assert_eq!(
+            risk.check(&buy("alice", None, 10), &book, now),
+            Err(RiskViolation::MaxOrderNotional {
+                notional: 1_100,
+                limit: 1_000
+            })
+        );
+        // The resting buy of 4 counts towards the position
+        assert_eq!(
+            risk.check(&buy("alice", Some(90), 5), &book, now),
+            Err(RiskViolation::MaxNetPosition {
+                position: 9,
+                limit: 8
+            })
+        );
+        assert_eq!(risk.check(&buy("alice", Some(90), 4), &book, now), Ok(()));
+        book.add_order(limit_order(3, "alice", Side::Buy, 90, 4));
+        assert_eq!(
+            risk.check(&buy("alice", Some(90), 1), &book, now),
+            Err(RiskViolation::MaxOpenOrders { open: 2, limit: 2 })
+        );
+        // Amending an open order does not open another one
+        let amend = RiskCheck {
+            replaces: Some(3),
+            ..buy("alice", Some(95), 4)
+        };
+        assert_eq!(risk.check(&amend, &book, now), Ok(()));
+        // Bob has no limits
+        assert_eq!(risk.check(&buy("bob", None, 1_000), &book, now), Ok(()));
+    }
+
+    #[test]
+    fn test_order_rate_is_limited_over_a_sliding_second() {
+        let book = OrderBook::new();
+        let mut risk = RiskManager::default();
+        risk.set_limits(
+            "alice",
+            RiskLimits {
+                max_orders_per_second: Some(2),
+                ..Default::default()
+            },
+        );
+        let start = Instant::now();
+        let order = buy("alice", Some(100), 1);
+        assert_eq!(risk.check(&order, &book, start), Ok(()));
+        assert_eq!(
+            risk.check(&order, &book, start + Duration::from_millis(500)),
+            Ok(())
+        );
+        assert_eq!(
+            risk.check(&order, &book, start + Duration::from_millis(900)),
+            Err(RiskViolation::MaxOrderRate { limit: 2 })
+        );
+        assert_eq!(
+            risk.check(&order, &book, start + Duration::from_millis(1_000)),
+            Ok(())
+        );
+    }
+}
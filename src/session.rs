@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::OrderError,
+    instrument::Pair,
+    orders::{Order, OrderType, TimeInForce},
+};
+
+/// The trading phase of a single pair.
+///
+/// - `PreOpen`: orders and cancels are accepted, but nothing matches; only
+///   `GTC`/`GTD` limit orders (and stops) can be entered
+/// - `Continuous`: normal trading; incoming orders match immediately
+/// - `PreClose`: the closing call; collects orders like `PreOpen` before the close
+/// - `VolatilityAuction`: entered when a trade would breach the pair's price
+///   bands; collects orders like `PreOpen` until the auction period ends
+/// - `Halted`: trading is suspended; only cancels are accepted
+/// - `Closed`: the market is shut; neither orders nor cancels are accepted
+///
+/// `PreOpen`, `PreClose` and `VolatilityAuction` are call phases: the orders they collect are
+/// uncrossed in a single call auction when the phase ends (see [`crate::auction`]).
+///
+/// Allowed transitions:
+/// - `Closed` → `PreOpen`
+/// - `PreOpen` → `Continuous`, `Halted` or `Closed`
+/// - `Continuous` → `PreClose`, `VolatilityAuction`, `Halted` or `Closed`
+/// - `PreClose` → `Closed` or `Halted`
+/// - `VolatilityAuction` → `Continuous`, `Halted` or `Closed`
+/// - `Halted` → `PreOpen`, `Continuous` or `Closed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum TradingPhase {
+    PreOpen,
+    #[default]
+    Continuous,
+    PreClose,
+    VolatilityAuction,
+    Halted,
+    Closed,
+}
+
+impl TradingPhase {
+    /// Returns `true` if the state machine allows moving from `self` to `next`.
+    pub fn can_transition_to(self, next: TradingPhase) -> bool {
+        use TradingPhase::*;
+        matches!(
+            (self, next),
+            (Closed, PreOpen)
+                | (PreOpen, Continuous | Halted | Closed)
+                | (Continuous, PreClose | VolatilityAuction | Halted | Closed)
+                | (PreClose, Closed | Halted)
+                | (VolatilityAuction, Continuous | Halted | Closed)
+                | (Halted, PreOpen | Continuous | Closed)
+        )
+    }
+
+    /// Returns `true` for the phases that collect orders for a call auction.
+    pub fn is_call(self) -> bool {
+        matches!(
+            self,
+            TradingPhase::PreOpen | TradingPhase::PreClose | TradingPhase::VolatilityAuction
+        )
+    }
+
+    /// Returns `true` if moving from `self` to `next` runs a call auction:
+    /// (re)opening from `PreOpen`, `VolatilityAuction` or `Halted`, and closing
+    /// from `PreClose`.
+    pub fn auctions_into(self, next: TradingPhase) -> bool {
+        use TradingPhase::*;
+        matches!(
+            (self, next),
+            (PreOpen | VolatilityAuction | Halted, Continuous) | (PreClose, Closed)
+        )
+    }
+
+    /// Returns `true` if incoming orders match against the book.
+    pub fn matches(self) -> bool {
+        self == TradingPhase::Continuous
+    }
+
+    /// Returns `true` if cancels are accepted.
+    pub fn accepts_cancels(self) -> bool {
+        self != TradingPhase::Closed
+    }
+
+    /// Checks that `order` may be entered (or amended) in this phase.
+    ///
+    /// During a call phase only orders that can rest are accepted, since nothing matches.
+    pub fn check_order(self, order: &Order) -> Result<(), OrderError> {
+        match self {
+            TradingPhase::Continuous => Ok(()),
+            TradingPhase::PreOpen | TradingPhase::PreClose | TradingPhase::VolatilityAuction => {
+                let rests = matches!(order.time_in_force, TimeInForce::GTC | TimeInForce::GTD);
+                if rests && order.order_type != OrderType::Market {
+                    Ok(())
+                } else {
+                    Err(OrderError::NotAcceptedInPhase(self))
+                }
+            }
+            TradingPhase::Halted | TradingPhase::Closed => {
+                Err(OrderError::NotAcceptedInPhase(self))
+            }
+        }
+    }
+}
+
+/// Pushed to websocket subscribers whenever a pair changes phase.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PhaseChange {
+    pub pair: Pair,
+    pub from: TradingPhase,
+    pub phase: TradingPhase,
+}
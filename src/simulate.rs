@@ -2,20 +2,23 @@
 
 use rand::Rng; // for rng().random_bool()
 use rand_distr::{Distribution, Exp, Exp1, Normal};
-use reqwest::{Client, ClientBuilder};
-use serde::Deserialize;
+use reqwest::{Client, ClientBuilder, Method};
+use serde::{Deserialize, de::IgnoredAny};
 use serde_json::json;
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 
-use crate::instrument::Pair;
+use crate::{accounts::ApiCredentials, api::AccountPositions, instrument::Pair};
 
 #[derive(Clone)]
 pub struct SimConfig {
     pub api_base: String,
     pub pair: Pair,
+    /// API key the orders are signed with; its account owns them, so the
+    /// simulator's market orders never wash-trade against its resting limits.
+    pub credentials: ApiCredentials,
     pub run_secs: Option<u64>,
     pub attack_rate_hz: f64, // Poisson rate λ
     pub noise_sigma: f64,    // N(0, σ) drift applied to mid each tick
@@ -27,54 +30,66 @@ pub struct SimConfig {
 
 #[derive(Deserialize)]
 struct Ack {
-    trades: Vec<AckTrade>,
+    trades: Vec<IgnoredAny>,
 }
 
-#[derive(Deserialize)]
-struct AckTrade {
-    price: u64,
-    quantity: u64,
-}
-
-/// Fire a single **market** order of size 1, update inventory/P&L.
-/// Kept close to your original helper but now typed.
+/// Fire a single **market** order of size 1; returns how many fills it got.
+///
+/// Inventory and P&L are kept by the engine, see [`log_positions`].
 pub async fn send_one_order(
     client: &Client,
+    credentials: &ApiCredentials,
     api_base: &str,
     pair: &Pair,
-    iv: &mut i64,
-    pnl: &mut f64,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<usize> {
     let side = if rand::rng().random_bool(0.5) {
         "Buy"
     } else {
         "Sell"
     };
 
-    let resp = client
-        .post(format!("{}/orders", api_base))
-        .json(&json!({
-            "side": side,
-            "order_type": "Market",
-            "quantity": 1u64,
-            "symbol": pair.code(),
-        }))
+    let resp = credentials
+        .request(
+            client,
+            Method::POST,
+            api_base,
+            "/orders",
+            &json!({
+                "side": side,
+                "order_type": "Market",
+                "quantity": 1u64,
+                "symbol": pair.code(),
+            }),
+        )
         .send()
         .await?
         .error_for_status()?;
 
     let ack: Ack = resp.json().await?;
+    Ok(ack.trades.len())
+}
 
-    for t in ack.trades {
-        let price = t.price as f64;
-        let qty = t.quantity as f64;
-        if side == "Buy" {
-            *iv -= qty as i64; // maker sold to us
-            *pnl += price * qty;
-        } else {
-            *iv += qty as i64; // maker bought from us
-            *pnl -= price * qty;
-        }
+/// Logs the simulator account's positions as the engine reports them (`GET /positions`).
+pub async fn log_positions(
+    client: &Client,
+    credentials: &ApiCredentials,
+    api_base: &str,
+) -> anyhow::Result<()> {
+    let positions: AccountPositions = credentials
+        .get(client, api_base, "/positions")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    for p in positions.positions {
+        info!(
+            pair = %p.pair,
+            inventory = p.quantity,
+            realized_pnl = p.realized_pnl,
+            unrealized_pnl = ?p.unrealized_pnl,
+            "simulator position"
+        );
     }
     Ok(())
 }
@@ -91,8 +106,6 @@ pub async fn run_simulation(cfg: SimConfig, cancel_token: CancellationToken) ->
     let drift = Normal::new(0.0, cfg.noise_sigma).expect("noise_sigma >= 0");
     let size = Exp1;
 
-    let mut iv: i64 = 0;
-    let mut pnl: f64 = 0.0;
     let mut mid: f64 = 50.0;
     let start = Instant::now();
 
@@ -100,10 +113,10 @@ pub async fn run_simulation(cfg: SimConfig, cancel_token: CancellationToken) ->
     let spread = 1.0_f64;
     loop {
         // hard stop
-        if let Some(max_secs) = cfg.run_secs {
-            if start.elapsed().as_secs() >= max_secs {
-                break;
-            }
+        if let Some(max_secs) = cfg.run_secs
+            && start.elapsed().as_secs() >= max_secs
+        {
+            break;
         }
 
         // exponential inter-arrival
@@ -133,15 +146,21 @@ pub async fn run_simulation(cfg: SimConfig, cancel_token: CancellationToken) ->
         let price_u64 = price_u64.max(1.0).round() as u64;
 
         // place the order; on failure, warn and continue
-        match client
-            .post(format!("{}/orders", cfg.api_base))
-            .json(&json!({
-                "side": side,
-                "order_type": "Limit",
-                "price": price_u64,
-                "quantity": qty_u64,
-                "symbol": cgf_pair
-            }))
+        match cfg
+            .credentials
+            .request(
+                &client,
+                Method::POST,
+                &cfg.api_base,
+                "/orders",
+                &json!({
+                    "side": side,
+                    "order_type": "Limit",
+                    "price": price_u64,
+                    "quantity": qty_u64,
+                    "symbol": cgf_pair,
+                }),
+            )
             .send()
             .await
         {
@@ -152,25 +171,13 @@ pub async fn run_simulation(cfg: SimConfig, cancel_token: CancellationToken) ->
                 }
                 match resp.json::<Ack>().await {
                     Ok(ack) => {
-                        for t in ack.trades {
-                            let px = t.price as f64;
-                            let q = t.quantity as f64;
-                            if side == "Buy" {
-                                iv -= q as i64;
-                                pnl += px * q;
-                            } else {
-                                iv += q as i64;
-                                pnl -= px * q;
-                            }
-                        }
                         info!(
                             elapsed = format_args!("{:.1}s", start.elapsed().as_secs_f64()),
                             side,
                             qty = qty_u64,
                             price = price_u64,
                             mid = format_args!("{:.2}", mid),
-                            inventory = iv,
-                            pnl = format_args!("{:.2}", pnl),
+                            fills = ack.trades.len(),
                             "sim tick"
                         );
                     }
@@ -184,10 +191,6 @@ pub async fn run_simulation(cfg: SimConfig, cancel_token: CancellationToken) ->
         }
     }
 
-    info!(
-        inventory = iv,
-        pnl = format_args!("{:.2}", pnl),
-        "simulation done"
-    );
-    Ok(())
+    info!("simulation done");
+    log_positions(&client, &cfg.credentials, &cfg.api_base).await
 }
@@ -1,19 +1,37 @@
 use tokio::sync::{RwLock, broadcast};
 
 use crate::{
-    instrument::Pair,
-    orderbook::OrderBook,
+    accounts::{AccountRegistry, ApiCredentials},
+    balances::{Balance, BalanceLedger, SharedBalances},
+    depth::{BookDelta, DepthFeed},
+    errors::{BalanceError, OrderError, RegistryError},
+    instrument::{Asset, InstrumentRegistry, InstrumentSpec, Pair},
+    metrics::Metrics,
+    orderbook::{BookSnapshot, OrderBook},
+    orders::{AccountId, ClientOrderIds, MaxSlippage, Side},
+    positions::PositionReport,
+    risk::RiskManager,
+    session::{PhaseChange, TradingPhase},
     store::{Store, StoreResult},
     trade::Trade,
 };
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// How long client order IDs stay reserved unless configured otherwise.
+pub const DEFAULT_CLIENT_ORDER_ID_WINDOW: Duration = Duration::from_secs(300);
 
 /// Shared application state.
 ///
 /// Holds:
 ///  - `order_book` and `trade_log` behind `Arc<Mutex<…>>` for safe concurrent access  
-///  - `trade_tx` and `book_tx` broadcast channels to notify subscribers of new trades
-///    and order‐book updates  
+///  - `trade_tx`, `book_tx`, `depth_tx` and `phase_tx` broadcast channels to notify
+///    subscribers of new trades, order‐book updates, depth changes and trading phase changes  
 ///  - `db_pool` for PostgreSQL connections
 #[derive(Clone)]
 pub struct AppState {
@@ -29,26 +47,473 @@ pub struct AppState {
     /// Broadcast channel for order‐book updates.
     pub book_tx: broadcast::Sender<Pair>,
 
+    /// Broadcast channel for the changed levels of each book update, in sequence order.
+    pub depth_tx: broadcast::Sender<BookDelta>,
+
+    /// The depth last published on `depth_tx` for every pair.
+    pub depth: Arc<Mutex<DepthFeed>>,
+
+    /// Broadcast channel for trading phase changes.
+    pub phase_tx: broadcast::Sender<PhaseChange>,
+
     /// store
     pub store: Arc<RwLock<Store>>,
+
+    /// Listed pairs and their trading rules; every listed pair has a book.
+    pub instruments: Arc<RwLock<InstrumentRegistry>>,
+
+    /// Slippage protection for market and stop orders that do not set their own.
+    pub default_slippage: Option<MaxSlippage>,
+
+    /// Trading accounts and their API keys; order entry is authenticated against it.
+    pub accounts: Arc<RwLock<AccountRegistry>>,
+
+    /// Every account's balances; shared with the books, which fund orders from it.
+    pub balances: SharedBalances,
+
+    /// Per-account pre-trade risk limits, checked before orders reach a book.
+    pub risk: Arc<RwLock<RiskManager>>,
+
+    /// Counters exported by `GET /metrics`.
+    pub metrics: Arc<Metrics>,
+
+    /// How often account websockets are pinged to detect dead clients.
+    pub ws_heartbeat: Duration,
+
+    /// When each account's dead-man's switch cancels its orders, unless refreshed first.
+    pub cancel_deadlines: Arc<RwLock<HashMap<AccountId, SystemTime>>>,
+
+    /// Orders placed with a `client_order_id`, to answer retries and look them up.
+    pub client_orders: Arc<RwLock<ClientOrderIds>>,
+
+    /// How long a client order ID stays reserved; resubmitting it within
+    /// this window returns the original order instead of placing a new one.
+    pub client_order_id_window: Duration,
+
+    /// Token `/admin` requests must carry; without one the admin API is disabled.
+    pub admin_token: Option<String>,
 }
 
 impl AppState {
+    /// Opens the store and lists the built-in pairs.
     pub async fn new(store_path: impl AsRef<std::path::Path>) -> StoreResult<Self> {
+        Self::with_instruments(store_path, InstrumentRegistry::default()).await
+    }
+
+    /// Opens the store, restores balances from its ledger entries and creates
+    /// an empty book for every pair in `instruments`.
+    pub async fn with_instruments(
+        store_path: impl AsRef<std::path::Path>,
+        instruments: InstrumentRegistry,
+    ) -> StoreResult<Self> {
         let store = Store::open(store_path)?;
+        let ledger = BalanceLedger::replay(store.ledger_entries()?);
+        let balances = Arc::new(Mutex::new(ledger));
         let (trade_tx, _) = broadcast::channel(1024);
         let (book_tx, _) = broadcast::channel(16);
+        let (depth_tx, _) = broadcast::channel(1024);
+        let (phase_tx, _) = broadcast::channel(16);
         let mut books = HashMap::new();
 
-        for pair in Pair::supported() {
-            books.insert(pair.clone(), OrderBook::new());
+        for spec in instruments.specs() {
+            books.insert(
+                spec.pair.clone(),
+                OrderBook::for_instrument(spec).with_balances(balances.clone()),
+            );
         }
         Ok(Self {
             order_books: Arc::new(RwLock::new(books)),
             trade_log: Arc::new(RwLock::new(Vec::new())),
             trade_tx,
             book_tx,
+            depth_tx,
+            depth: Arc::new(Mutex::new(DepthFeed::default())),
+            phase_tx,
             store: Arc::new(RwLock::new(store)),
+            instruments: Arc::new(RwLock::new(instruments)),
+            default_slippage: None,
+            accounts: Arc::new(RwLock::new(AccountRegistry::default())),
+            balances,
+            risk: Arc::new(RwLock::new(RiskManager::default())),
+            metrics: Arc::new(Metrics::default()),
+            ws_heartbeat: Duration::from_secs(10),
+            cancel_deadlines: Arc::new(RwLock::new(HashMap::new())),
+            client_orders: Arc::new(RwLock::new(ClientOrderIds::default())),
+            client_order_id_window: DEFAULT_CLIENT_ORDER_ID_WINDOW,
+            admin_token: None,
         })
     }
+
+    /// Protects market and stop orders that do not set `max_slippage` with `slippage`.
+    pub fn with_default_slippage(mut self, slippage: Option<MaxSlippage>) -> Self {
+        self.default_slippage = slippage;
+        self
+    }
+
+    /// Pings account websockets every `every`; see [`crate::api::handle_account_socket`].
+    pub fn with_ws_heartbeat(mut self, every: Duration) -> Self {
+        self.ws_heartbeat = every;
+        self
+    }
+
+    /// Keeps client order IDs reserved for `window` after their order is placed.
+    pub fn with_client_order_id_window(mut self, window: Duration) -> Self {
+        self.client_order_id_window = window;
+        self
+    }
+
+    /// Enables the admin API for requests carrying `token`; see
+    /// [`crate::api::authenticate_admin`].
+    pub fn with_admin_token(mut self, token: impl Into<String>) -> Self {
+        self.admin_token = Some(token.into());
+        self
+    }
+
+    /// Issues a new API key for `account`, opening the account if it is new.
+    pub async fn create_api_key(&self, account: &str) -> ApiCredentials {
+        let credentials = self.accounts.write().await.create_key(account);
+        info!(account, key_id = %credentials.key_id, "issued API key");
+        credentials
+    }
+
+    /// `account`'s balance of every asset it holds.
+    pub fn balances_of(&self, account: &str) -> BTreeMap<Asset, Balance> {
+        self.ledger().balances(account)
+    }
+
+    /// Credits `amount` of `asset` to `account`. Like every balance change, the
+    /// ledger entry is persisted by the next [`AppState::persist_ledger`] or
+    /// [`AppState::persist_and_broadcast`].
+    pub fn deposit(
+        &self,
+        account: &str,
+        asset: &Asset,
+        amount: u64,
+    ) -> Result<Balance, BalanceError> {
+        let balance = self.ledger().deposit(account, asset, amount)?;
+        info!(account, asset = %asset, amount, "deposited");
+        Ok(balance)
+    }
+
+    /// Debits `amount` of `asset` from `account`'s available balance.
+    pub fn withdraw(
+        &self,
+        account: &str,
+        asset: &Asset,
+        amount: u64,
+    ) -> Result<Balance, BalanceError> {
+        let balance = self.ledger().withdraw(account, asset, amount)?;
+        info!(account, asset = %asset, amount, "withdrew");
+        Ok(balance)
+    }
+
+    /// `account`'s position in every pair it has traded, ordered by pair.
+    pub async fn positions_of(&self, account: &str) -> Vec<PositionReport> {
+        let books = self.order_books.read().await;
+        let mut positions: Vec<_> = books
+            .iter()
+            .filter_map(|(pair, book)| book.position_report(pair.clone(), account))
+            .collect();
+        positions.sort_by(|a, b| a.pair.cmp(&b.pair));
+        positions
+    }
+
+    fn ledger(&self) -> MutexGuard<'_, BalanceLedger> {
+        self.balances.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Publishes the levels of `pair`'s book that changed since it was last
+    /// published as a [`BookDelta`] on `depth_tx`, then notifies `book_tx`.
+    ///
+    /// Must be called without holding the books lock.
+    pub async fn book_changed(&self, pair: Pair) {
+        self.publish_depth(&pair).await;
+        let _ = self.book_tx.send(pair);
+    }
+
+    /// The visible depth of `pair`'s book, numbered as on `depth_tx`, after
+    /// publishing any change not yet published; `None` if the pair has no book.
+    pub async fn publish_depth(&self, pair: &Pair) -> Option<BookSnapshot> {
+        let books = self.order_books.read().await;
+        let book = books.get(pair)?;
+        let mut depth = self.depth.lock().unwrap_or_else(PoisonError::into_inner);
+        let (snapshot, delta) = depth.publish(pair, book);
+        if let Some(delta) = delta {
+            // sent under the feed lock, so deltas go out in sequence order
+            let _ = self.depth_tx.send(delta);
+        }
+        Some(snapshot)
+    }
+
+    /// Trading rules for `pair`, if it is listed.
+    pub async fn instrument(&self, pair: &Pair) -> Option<InstrumentSpec> {
+        self.instruments.read().await.get(pair).cloned()
+    }
+
+    /// Lists a new pair and opens an empty book for it.
+    pub async fn list_instrument(&self, spec: InstrumentSpec) -> Result<(), RegistryError> {
+        let mut books = self.order_books.write().await;
+        let pair = spec.pair.clone();
+        let book = OrderBook::for_instrument(&spec).with_balances(self.balances.clone());
+        self.instruments.write().await.insert(spec)?;
+        info!(pair = %pair, "listed instrument");
+        books.entry(pair).or_insert(book);
+        Ok(())
+    }
+
+    /// Replaces a listed pair's trading rules and hands the ones the engine
+    /// enforces (price bands, tick size) to its book.
+    pub async fn update_instrument(&self, spec: InstrumentSpec) -> Result<(), RegistryError> {
+        let mut books = self.order_books.write().await;
+        let pair = spec.pair.clone();
+        self.instruments.write().await.update(spec.clone())?;
+        if let Some(book) = books.get_mut(&pair) {
+            book.apply_instrument(&spec);
+        }
+        info!(pair = %pair, "updated instrument");
+        Ok(())
+    }
+
+    /// Persists `trades` and the ledger entries settling them in the store,
+    /// then broadcasts the trades on `trade_tx`.
+    pub async fn persist_and_broadcast(&self, trades: &[Trade]) -> StoreResult<()> {
+        //persist all trades in store
+        let mut store = self.store.write().await;
+        for trade in trades {
+            store.insert_trade(trade)?;
+        }
+        let entries = self.ledger().take_unsaved();
+        store.insert_ledger_entries(&entries)?;
+
+        //broadcast trades after successful persistence
+        for trade in trades {
+            let _ = self.trade_tx.send(trade.clone());
+        }
+        Ok(())
+    }
+
+    /// Persists the ledger entries recorded since they were last persisted,
+    /// such as those of deposits and withdrawals.
+    pub async fn persist_ledger(&self) -> StoreResult<()> {
+        let mut store = self.store.write().await;
+        let entries = self.ledger().take_unsaved();
+        store.insert_ledger_entries(&entries)
+    }
+
+    /// Cancels `account`'s live orders in every book, or only in `pair`, and
+    /// only on `side` if given, notifying [`AppState::book_changed`] once per pair that changed.
+    ///
+    /// Books whose trading phase refuses cancels are skipped, unless `pair`
+    /// names one, which fails with [`OrderError::CancelNotAcceptedInPhase`].
+    ///
+    /// Returns the cancelled IDs by pair.
+    pub async fn cancel_all(
+        &self,
+        account: &str,
+        pair: Option<&Pair>,
+        side: Option<Side>,
+    ) -> Result<BTreeMap<Pair, Vec<u128>>, OrderError> {
+        let mut cancelled = BTreeMap::new();
+        {
+            let mut books = self.order_books.write().await;
+            for (book_pair, book) in books.iter_mut() {
+                if pair.is_some_and(|p| p != book_pair) {
+                    continue;
+                }
+                if !book.phase().accepts_cancels() {
+                    if pair.is_some() {
+                        return Err(OrderError::CancelNotAcceptedInPhase(book.phase()));
+                    }
+                    warn!(pair = %book_pair, phase = ?book.phase(), account, "mass cancel skipped");
+                    continue;
+                }
+                let ids = book.cancel_orders_of(account, side);
+                if !ids.is_empty() {
+                    cancelled.insert(book_pair.clone(), ids);
+                }
+            }
+        }
+        for (pair, ids) in &cancelled {
+            info!(pair = %pair, account, count = ids.len(), "mass cancelled orders");
+            self.book_changed(pair.clone()).await;
+        }
+        Ok(cancelled)
+    }
+
+    /// Cancels those of `orders` that are still live and belong to `account`,
+    /// notifying [`AppState::book_changed`] once per pair that changed. Books
+    /// whose trading phase refuses cancels are skipped.
+    ///
+    /// Returns the cancelled IDs by pair.
+    pub async fn cancel_orders(
+        &self,
+        account: &str,
+        orders: &BTreeMap<Pair, BTreeSet<u128>>,
+    ) -> BTreeMap<Pair, Vec<u128>> {
+        let mut cancelled = BTreeMap::new();
+        {
+            let mut books = self.order_books.write().await;
+            for (pair, ids) in orders {
+                let Some(book) = books.get_mut(pair) else {
+                    continue;
+                };
+                if !book.phase().accepts_cancels() {
+                    warn!(pair = %pair, phase = ?book.phase(), account, "cancel of owned orders skipped");
+                    continue;
+                }
+                let ids: Vec<u128> = ids
+                    .iter()
+                    .copied()
+                    .filter(|&id| book.check_owner(id, account).is_ok() && book.cancel_order(id))
+                    .collect();
+                if !ids.is_empty() {
+                    cancelled.insert(pair.clone(), ids);
+                }
+            }
+        }
+        for pair in cancelled.keys() {
+            self.book_changed(pair.clone()).await;
+        }
+        cancelled
+    }
+
+    /// Arms `account`'s dead-man's switch: unless called again within `timeout`,
+    /// all of its orders are cancelled. A zero `timeout` disarms it.
+    ///
+    /// Returns when the orders will be cancelled, if armed.
+    pub async fn cancel_all_after(&self, account: &str, timeout: Duration) -> Option<SystemTime> {
+        let mut deadlines = self.cancel_deadlines.write().await;
+        if timeout.is_zero() {
+            deadlines.remove(account);
+            return None;
+        }
+        let deadline = SystemTime::now() + timeout;
+        deadlines.insert(account.to_string(), deadline);
+        Some(deadline)
+    }
+
+    /// Cancels every order of the accounts whose dead-man's switch ran out,
+    /// disarming it, see [`AppState::cancel_all_after`].
+    ///
+    /// Returns the number of orders cancelled.
+    pub async fn trip_cancel_deadlines(&self) -> usize {
+        let now = SystemTime::now();
+        let due: Vec<AccountId> = {
+            let mut deadlines = self.cancel_deadlines.write().await;
+            let due = deadlines
+                .iter()
+                .filter(|(_, deadline)| **deadline <= now)
+                .map(|(account, _)| account.clone())
+                .collect();
+            deadlines.retain(|_, deadline| *deadline > now);
+            due
+        };
+        let mut total = 0;
+        for account in due {
+            match self.cancel_all(&account, None, None).await {
+                Ok(cancelled) => {
+                    let count = cancelled.values().map(Vec::len).sum::<usize>();
+                    warn!(account, count, "cancel-all-after timer expired");
+                    total += count;
+                }
+                Err(e) => error!(account, "cancel-all-after failed: {}", e),
+            }
+        }
+        total
+    }
+
+    /// Forgets the client order IDs whose idempotency window has passed.
+    ///
+    /// Returns the number of IDs forgotten.
+    pub async fn prune_client_order_ids(&self) -> usize {
+        self.client_orders
+            .write()
+            .await
+            .prune(self.client_order_id_window, SystemTime::now())
+    }
+
+    /// Reopens every book whose volatility auction period is over, uncrossing
+    /// it. Auction trades are persisted and broadcast, and the phase change is
+    /// announced on `phase_tx` and through [`AppState::book_changed`].
+    ///
+    /// Returns the number of books reopened.
+    pub async fn end_volatility_auctions(&self) -> StoreResult<usize> {
+        let now = SystemTime::now();
+        let (trades, pairs) = {
+            let mut books = self.order_books.write().await;
+            let mut log = self.trade_log.write().await;
+            let mut trades = Vec::new();
+            let mut pairs = Vec::new();
+            for (pair, book) in books.iter_mut() {
+                if !book.volatility_auction_due(now) {
+                    continue;
+                }
+                match book.set_phase(TradingPhase::Continuous) {
+                    Ok(auction_trades) => {
+                        info!(pair = %pair, trades = auction_trades.len(), "volatility auction ended");
+                        log.extend(auction_trades.clone());
+                        trades.extend(auction_trades);
+                        pairs.push(pair.clone());
+                    }
+                    Err(e) => error!(pair = %pair, "could not end volatility auction: {}", e),
+                }
+            }
+            (trades, pairs)
+        };
+        self.persist_and_broadcast(&trades).await?;
+        for pair in &pairs {
+            let _ = self.phase_tx.send(PhaseChange {
+                pair: pair.clone(),
+                from: TradingPhase::VolatilityAuction,
+                phase: TradingPhase::Continuous,
+            });
+            self.book_changed(pair.clone()).await;
+        }
+        Ok(pairs.len())
+    }
+
+    /// Removes expired GTD orders from every book, notifying
+    /// [`AppState::book_changed`] for each pair that changed.
+    ///
+    /// Returns the number of orders expired.
+    pub async fn expire_gtd_orders(&self) -> usize {
+        let now = SystemTime::now();
+        let mut total = 0;
+        let mut changed = Vec::new();
+        {
+            let mut books = self.order_books.write().await;
+            for (pair, book) in books.iter_mut() {
+                let expired = book.expire_orders(now);
+                if !expired.is_empty() {
+                    info!(pair = %pair, count = expired.len(), "expired GTD orders");
+                    total += expired.len();
+                    changed.push(pair.clone());
+                }
+            }
+        }
+        for pair in changed {
+            self.book_changed(pair).await;
+        }
+        total
+    }
+
+    /// Runs [`AppState::expire_gtd_orders`], [`AppState::trip_cancel_deadlines`],
+    /// [`AppState::prune_client_order_ids`] and [`AppState::end_volatility_auctions`]
+    /// every `every` until `token` is cancelled.
+    pub async fn run_expiry_sweeper(self, every: Duration, token: CancellationToken) {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = interval.tick() => {
+                    self.expire_gtd_orders().await;
+                    self.trip_cancel_deadlines().await;
+                    self.prune_client_order_ids().await;
+                    if let Err(e) = self.end_volatility_auctions().await {
+                        error!("failed to persist volatility auction trades: {}", e);
+                    }
+                }
+            }
+        }
+    }
 }
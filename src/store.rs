@@ -12,7 +12,10 @@ use std::{
 };
 use thiserror::Error;
 
-use crate::trade::Trade;
+use crate::{balances::LedgerEntry, trade::Trade};
+
+/// Key prefix of balance ledger entries.
+const LEDGER_PREFIX: &[u8] = b"ledger:";
 
 // Versioned, opaque cursor encoded as URL-safe base64 JSON.
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -54,6 +57,9 @@ pub type StoreResult<T> = Result<T, StoreError>;
 ///
 /// This guarantees chronological ordering under each `{symbol}:` prefix with
 /// deterministic tie-breakers when timestamps collide.
+///
+/// Balance ledger entries share the column under `"ledger:" + seq(u64)`; the
+/// lowercase prefix can never clash with a pair code.
 pub struct Store {
     db: Db,
 }
@@ -81,6 +87,13 @@ impl Store {
         k
     }
 
+    #[inline]
+    fn ledger_key(seq: u64) -> Vec<u8> {
+        let mut key = LEDGER_PREFIX.to_vec();
+        key.extend_from_slice(&seq.to_be_bytes());
+        key
+    }
+
     #[inline]
     fn encode_key(symbol: &str, trade: &Trade) -> Vec<u8> {
         let mut key = Self::prefix(symbol);
@@ -229,15 +242,52 @@ impl Store {
         let mut iter = self.db.iter(0).map_err(StoreError::Parity)?;
 
         iter.seek_to_first().map_err(StoreError::Parity)?;
-        Ok(std::iter::from_fn(move || match iter.next() {
-            Ok(Some((_key, raw))) => {
-                let (decoded, _): (Trade, usize) =
-                    bincode::decode_from_slice(&raw[..], config).unwrap();
-                Some(decoded)
+        Ok(std::iter::from_fn(move || {
+            loop {
+                match iter.next() {
+                    Ok(Some((key, _))) if key.starts_with(LEDGER_PREFIX) => continue,
+                    Ok(Some((_key, raw))) => {
+                        let (decoded, _): (Trade, usize) =
+                            bincode::decode_from_slice(&raw[..], config).unwrap();
+                        return Some(decoded);
+                    }
+                    _ => return None,
+                }
             }
-            _ => None,
         }))
     }
+
+    /// Insert balance ledger entries in one commit, keyed by their `seq`.
+    pub fn insert_ledger_entries(&mut self, entries: &[LedgerEntry]) -> StoreResult<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let config = config::standard();
+        let col: ColId = 0;
+        let mut batch = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let value = bincode::encode_to_vec(entry, config)?;
+            batch.push((col, Self::ledger_key(entry.seq), Some(value)));
+        }
+        self.db.commit(batch)?;
+        Ok(())
+    }
+
+    /// Every balance ledger entry, in `seq` order.
+    pub fn ledger_entries(&self) -> StoreResult<Vec<LedgerEntry>> {
+        let col: ColId = 0;
+        let mut iter = self.db.iter(col)?;
+        iter.seek(LEDGER_PREFIX)?;
+        let mut entries = Vec::new();
+        while let Some((key, raw)) = iter.next()? {
+            if !key.starts_with(LEDGER_PREFIX) {
+                break;
+            }
+            let (entry, _): (LedgerEntry, usize) = bincode::decode_from_slice(&raw, standard())?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
 }
 
 #[cfg(test)]
@@ -259,6 +309,8 @@ mod tests {
             maker_id: 10,
             taker_id: 20,
             timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(1),
+            maker_fee: -3,
+            taker_fee: 7,
         };
         let t_new = Trade {
             symbol: "BTC-USD".into(),
@@ -267,6 +319,8 @@ mod tests {
             maker_id: 11,
             taker_id: 21,
             timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(2),
+            maker_fee: 0,
+            taker_fee: 0,
         };
         store.insert_trade(&t_old).unwrap();
         store.insert_trade(&t_new).unwrap();
@@ -275,6 +329,7 @@ mod tests {
         let (p1, c1) = store.page_trade_asc("BTC-USD", None, 1).unwrap();
         assert_eq!(p1.len(), 1);
         assert_eq!(p1[0].price, 50);
+        assert_eq!((p1[0].maker_fee, p1[0].taker_fee), (-3, 7));
         assert!(c1.is_some(), "there should be a next page");
 
         // Page 2 (last page) should have no next
@@ -297,6 +352,8 @@ mod tests {
             maker_id: 100,
             taker_id: 200,
             timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(1),
+            maker_fee: 0,
+            taker_fee: 0,
         };
         let t_eth = Trade {
             symbol: "ETH-USD".into(),
@@ -305,6 +362,8 @@ mod tests {
             maker_id: 101,
             taker_id: 201,
             timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(2),
+            maker_fee: 0,
+            taker_fee: 0,
         };
         let t_btc2 = Trade {
             symbol: "BTC-USD".into(),
@@ -313,6 +372,8 @@ mod tests {
             maker_id: 102,
             taker_id: 202,
             timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(3),
+            maker_fee: 0,
+            taker_fee: 0,
         };
         store.insert_trade(&t_btc1).unwrap();
         store.insert_trade(&t_eth).unwrap();
@@ -374,6 +435,8 @@ mod tests {
             maker_id: 10,
             taker_id: 20,
             timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(1),
+            maker_fee: 0,
+            taker_fee: 0,
         };
         store.insert_trade(&t).unwrap();
 
@@ -392,6 +455,43 @@ mod tests {
         assert!(matches!(res, Err(StoreError::BadCursor)));
     }
 
+    #[test]
+    fn test_ledger_entries_round_trip_apart_from_trades() {
+        use crate::balances::{EntryReason, LedgerEntry};
+
+        let dir = tempdir().unwrap();
+        let mut store = Store::open(dir.path()).unwrap();
+        let t = Trade {
+            symbol: "BTC-USD".into(),
+            price: 50,
+            quantity: 1,
+            maker_id: 10,
+            taker_id: 20,
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(1),
+            maker_fee: 0,
+            taker_fee: 0,
+        };
+        store.insert_trade(&t).unwrap();
+        let entries: Vec<LedgerEntry> = (0..300)
+            .map(|seq| LedgerEntry {
+                seq,
+                account: "alice".into(),
+                asset: "USD".into(),
+                delta: if seq % 2 == 0 { 100 } else { -40 },
+                reason: EntryReason::Fill { order_id: 7 },
+                timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(seq),
+            })
+            .collect();
+        store.insert_ledger_entries(&entries).unwrap();
+
+        // Ordered by seq past the byte boundary at 256
+        assert_eq!(store.ledger_entries().unwrap(), entries);
+        let trades: Vec<Trade> = store.iter_trades().unwrap().collect();
+        assert_eq!(trades.len(), 1);
+        let (page, _) = store.page_trade_asc("BTC-USD", None, 10).unwrap();
+        assert_eq!(page.len(), 1);
+    }
+
     #[test]
     fn test_bad_cursor_nonexistent_key() {
         let dir = tempdir().unwrap();
@@ -405,6 +505,8 @@ mod tests {
             maker_id: 10,
             taker_id: 20,
             timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(1),
+            maker_fee: 0,
+            taker_fee: 0,
         };
         store.insert_trade(&t).unwrap();
 
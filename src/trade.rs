@@ -15,6 +15,10 @@ use std::time::SystemTime;
 /// Example:
 /// - A market buy order (taker) matches a limit sell at 102 (maker).
 /// - A trade is created at price 102.
+///
+/// # Fees
+/// `maker_fee` and `taker_fee` are charged from the pair's fee schedule at match
+/// time, in quote units; a negative maker fee is a rebate.
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize, bincode::Encode, bincode::Decode)]
 pub struct Trade {
     pub price: u64,
@@ -23,4 +27,6 @@ pub struct Trade {
     pub taker_id: u128,
     pub timestamp: SystemTime,
     pub symbol: String,
+    pub maker_fee: i64,
+    pub taker_fee: i64,
 }
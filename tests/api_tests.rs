@@ -7,7 +7,10 @@ use axum::{
 use http_body_util::BodyExt;
 
 use order_book_engine::{
-    api::{OrderAck, router},
+    accounts::{ADMIN_TOKEN_HEADER, ApiCredentials},
+    api::{MassCancelAck, OrderAck, OrderReport, router},
+    instrument::Asset,
+    orders::{MaxSlippage, OrderStatus},
     state::AppState,
 };
 use serde_json::{Value, json};
@@ -15,10 +18,63 @@ use tempfile::tempdir;
 use tower::ServiceExt;
 use urlencoding::encode;
 
-async fn test_app() -> (Router, tempfile::TempDir) {
+/// API keys for two accounts, so tests can trade one against the other.
+struct Traders {
+    alice: ApiCredentials,
+    bob: ApiCredentials,
+}
+
+/// What each trader starts with, per asset.
+const FUNDS: u64 = 1_000_000_000;
+
+impl Traders {
+    async fn new(state: &AppState) -> Self {
+        for account in ["alice", "bob"] {
+            for asset in [Asset::BTC, Asset::ETH, Asset::USD] {
+                state.deposit(account, &asset, FUNDS).unwrap();
+            }
+        }
+        Traders {
+            alice: state.create_api_key("alice").await,
+            bob: state.create_api_key("bob").await,
+        }
+    }
+}
+
+/// The admin token of the servers under test.
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+async fn test_app() -> (Router, Traders, tempfile::TempDir) {
     let dir = tempdir().unwrap();
-    let state = AppState::new(dir.path()).await.unwrap();
-    (router(state), dir)
+    let state = AppState::new(dir.path())
+        .await
+        .unwrap()
+        .with_admin_token(ADMIN_TOKEN);
+    let traders = Traders::new(&state).await;
+    (router(state), traders, dir)
+}
+
+/// A JSON `method` request to `uri`, signed with `key`.
+fn signed(key: &ApiCredentials, method: &str, uri: &str, body: String) -> Request<Body> {
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    for (name, value) in key.headers(method, uri, body.as_bytes()) {
+        req = req.header(name, value);
+    }
+    req.body(Body::from(body)).unwrap()
+}
+
+/// A JSON `method` request to the admin route `uri`, carrying the admin token.
+fn admin(method: &str, uri: &str, body: String) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .header(ADMIN_TOKEN_HEADER, ADMIN_TOKEN)
+        .body(Body::from(body))
+        .unwrap()
 }
 
 async fn body_json(res: axum::response::Response) -> Value {
@@ -28,7 +84,7 @@ async fn body_json(res: axum::response::Response) -> Value {
 
 #[tokio::test]
 async fn test_effective_limit_on_get_trade_log() {
-    let (app, _tmp) = test_app().await;
+    let (app, _, _tmp) = test_app().await;
     let res = app
         .oneshot(
             Request::builder()
@@ -44,7 +100,7 @@ async fn test_effective_limit_on_get_trade_log() {
 }
 #[tokio::test]
 async fn pairguard_rejects_bad_pair_on_book() {
-    let (app, _tmp) = test_app().await;
+    let (app, _, _tmp) = test_app().await;
 
     let res = app
         .clone()
@@ -70,7 +126,7 @@ async fn pairguard_rejects_bad_pair_on_book() {
 
 #[tokio::test]
 async fn pairguard_rejects_bad_pair_on_trades_and_cancel() {
-    let (app, _tmp) = test_app().await;
+    let (app, _, _tmp) = test_app().await;
 
     let res = app
         .clone()
@@ -100,7 +156,7 @@ async fn pairguard_rejects_bad_pair_on_trades_and_cancel() {
 
 #[tokio::test]
 async fn create_order_rejects_zero_qty() {
-    let (app, _tmp) = test_app().await;
+    let (app, traders, _tmp) = test_app().await;
 
     let body = json!({
         "side": "Buy",
@@ -111,14 +167,7 @@ async fn create_order_rejects_zero_qty() {
     });
 
     let res = app
-        .oneshot(
-            Request::builder()
-                .method("POST")
-                .uri("/orders")
-                .header("content-type", "application/json")
-                .body(Body::from(body.to_string()))
-                .unwrap(),
-        )
+        .oneshot(signed(&traders.alice, "POST", "/orders", body.to_string()))
         .await
         .unwrap();
 
@@ -129,7 +178,7 @@ async fn create_order_rejects_zero_qty() {
 
 #[tokio::test]
 async fn create_order_invalid_symbol_yields_422_from_loggedjson() {
-    let (app, _tmp) = test_app().await;
+    let (app, traders, _tmp) = test_app().await;
 
     let body = json!({
         "side": "Buy",
@@ -140,14 +189,7 @@ async fn create_order_invalid_symbol_yields_422_from_loggedjson() {
     });
 
     let res = app
-        .oneshot(
-            Request::builder()
-                .method("POST")
-                .uri("/orders")
-                .header("content-type", "application/json")
-                .body(Body::from(body.to_string()))
-                .unwrap(),
-        )
+        .oneshot(signed(&traders.alice, "POST", "/orders", body.to_string()))
         .await
         .unwrap();
 
@@ -156,6 +198,47 @@ async fn create_order_invalid_symbol_yields_422_from_loggedjson() {
     assert!(v["error"].as_str().unwrap().contains("unsupported symbol"));
 }
 
+#[tokio::test]
+async fn create_order_for_unlisted_pair_yields_422_until_listed() {
+    let (app, traders, _tmp) = test_app().await;
+
+    let order = || {
+        let body = json!({
+            "side": "Buy",
+            "order_type": "Limit",
+            "price": 30,
+            "quantity": 1,
+            "symbol": "SOL-USD"
+        });
+        signed(&traders.alice, "POST", "/orders", body.to_string())
+    };
+
+    // Well-formed, but not in the registry
+    let res = app.clone().oneshot(order()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let v = body_json(res).await;
+    assert_eq!(v["error"], "unsupported symbol: `SOL-USD`");
+
+    let spec = json!({
+        "pair": "SOL-USD",
+        "tick_size": 1,
+        "lot_size": 1,
+        "min_quantity": 1,
+        "max_quantity": 1000,
+        "min_notional": 1,
+        "max_price": 1000
+    });
+    let res = app
+        .clone()
+        .oneshot(admin("POST", "/admin/instruments", spec.to_string()))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let res = app.oneshot(order()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
 async fn json<T: serde::de::DeserializeOwned>(res: Response) -> T {
     let bytes = to_bytes(res.into_body(), usize::MAX).await.unwrap();
     serde_json::from_slice(&bytes).unwrap()
@@ -163,7 +246,7 @@ async fn json<T: serde::de::DeserializeOwned>(res: Response) -> T {
 
 #[tokio::test]
 async fn limit_order_rests_then_cancel_removes_it() {
-    let (app, _tmp) = test_app().await;
+    let (app, traders, _tmp) = test_app().await;
 
     let create = json!({
         "side": "Buy",
@@ -175,14 +258,12 @@ async fn limit_order_rests_then_cancel_removes_it() {
 
     let res = app
         .clone()
-        .oneshot(
-            Request::builder()
-                .method("POST")
-                .uri("/orders")
-                .header("content-type", "application/json")
-                .body(Body::from(create.to_string()))
-                .unwrap(),
-        )
+        .oneshot(signed(
+            &traders.alice,
+            "POST",
+            "/orders",
+            create.to_string(),
+        ))
         .await
         .unwrap();
 
@@ -206,13 +287,12 @@ async fn limit_order_rests_then_cancel_removes_it() {
 
     let res = app
         .clone()
-        .oneshot(
-            Request::builder()
-                .method("DELETE")
-                .uri(format!("/orders/BTC-USD/{}", order_id))
-                .body(Body::empty())
-                .unwrap(),
-        )
+        .oneshot(signed(
+            &traders.alice,
+            "DELETE",
+            &format!("/orders/BTC-USD/{}", order_id),
+            String::new(),
+        ))
         .await
         .unwrap();
     assert_eq!(res.status(), StatusCode::OK);
@@ -240,7 +320,7 @@ async fn limit_order_rests_then_cancel_removes_it() {
 
 #[tokio::test]
 async fn trades_endpoint_paginates_forward() {
-    let (app, _tmp) = test_app().await;
+    let (app, traders, _tmp) = test_app().await;
 
     let seed = json!({
         "side": "Sell",
@@ -250,14 +330,7 @@ async fn trades_endpoint_paginates_forward() {
         "symbol": "BTC-USD"
     });
     app.clone()
-        .oneshot(
-            Request::builder()
-                .method("POST")
-                .uri("/orders")
-                .header("content-type", "application/json")
-                .body(Body::from(seed.to_string()))
-                .unwrap(),
-        )
+        .oneshot(signed(&traders.alice, "POST", "/orders", seed.to_string()))
         .await
         .unwrap();
 
@@ -273,14 +346,12 @@ async fn trades_endpoint_paginates_forward() {
     for _ in 0..2 {
         let _ = app
             .clone()
-            .oneshot(
-                Request::builder()
-                    .method("POST")
-                    .uri("/orders")
-                    .header("content-type", "application/json")
-                    .body(Body::from(market_buy(1).to_string()))
-                    .unwrap(),
-            )
+            .oneshot(signed(
+                &traders.bob,
+                "POST",
+                "/orders",
+                market_buy(1).to_string(),
+            ))
             .await
             .unwrap();
     }
@@ -316,7 +387,7 @@ async fn trades_endpoint_paginates_forward() {
 
 #[tokio::test]
 async fn trades_rejects_zero_limit() {
-    let (app, _tmp) = test_app().await;
+    let (app, _, _tmp) = test_app().await;
 
     let res = app
         .oneshot(
@@ -336,7 +407,7 @@ async fn trades_rejects_zero_limit() {
 
 #[tokio::test]
 async fn trades_rejects_invalid_after_cursor() {
-    let (app, _tmp) = test_app().await;
+    let (app, _, _tmp) = test_app().await;
 
     // clearly not a valid cursor (not base64/JSON)
     let res = app
@@ -353,3 +424,1452 @@ async fn trades_rejects_invalid_after_cursor() {
     let v = body_json(res).await;
     assert_eq!(v["error"], "invalid `after` cursor");
 }
+
+#[tokio::test]
+async fn ioc_limit_order_does_not_rest() {
+    let (app, traders, _tmp) = test_app().await;
+
+    let ioc = json!({
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": 48,
+        "quantity": 10,
+        "symbol": "BTC-USD",
+        "time_in_force": "IOC"
+    });
+    let res = app
+        .clone()
+        .oneshot(signed(&traders.alice, "POST", "/orders", ioc.to_string()))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = app
+        .oneshot(
+            Request::builder()
+                .uri("/book/BTC-USD")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let snap = body_json(res).await;
+    assert!(snap["bids"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn gtd_order_requires_future_expire_at() {
+    let (app, traders, _tmp) = test_app().await;
+
+    for (body, msg) in [
+        (
+            json!({
+                "side": "Buy",
+                "order_type": "Limit",
+                "price": 48,
+                "quantity": 1,
+                "symbol": "BTC-USD",
+                "time_in_force": "GTD"
+            }),
+            "GTD orders require `expire_at`",
+        ),
+        (
+            json!({
+                "side": "Buy",
+                "order_type": "Limit",
+                "price": 48,
+                "quantity": 1,
+                "symbol": "BTC-USD",
+                "time_in_force": "GTD",
+                "expire_at": 1_000
+            }),
+            "`expire_at` must be in the future",
+        ),
+    ] {
+        let res = app
+            .clone()
+            .oneshot(signed(&traders.alice, "POST", "/orders", body.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let v = body_json(res).await;
+        assert_eq!(v["error"], msg);
+    }
+}
+
+#[tokio::test]
+async fn amend_order_moves_price_and_unknown_is_404() {
+    let (app, traders, _tmp) = test_app().await;
+
+    let create = json!({
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": 48,
+        "quantity": 10,
+        "symbol": "BTC-USD"
+    });
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &traders.alice,
+            "POST",
+            "/orders",
+            create.to_string(),
+        ))
+        .await
+        .unwrap();
+    let ack: OrderAck = json(res).await;
+
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &traders.alice,
+            "PATCH",
+            &format!("/orders/BTC-USD/{}", ack.order_id),
+            json!({"price": 49, "quantity": 4}).to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/book/BTC-USD")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let snap = body_json(res).await;
+    assert_eq!(snap["bids"], json!([[49, 4]]));
+
+    let res = app
+        .oneshot(signed(
+            &traders.alice,
+            "PATCH",
+            "/orders/BTC-USD/123",
+            json!({"quantity": 1}).to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn order_status_tracks_fills_and_open_orders_lists_live_ones() {
+    let (app, traders, _tmp) = test_app().await;
+
+    let post = |key: &ApiCredentials, body: Value| signed(key, "POST", "/orders", body.to_string());
+    let res = app
+        .clone()
+        .oneshot(post(
+            &traders.alice,
+            json!({
+                "side": "Sell",
+                "order_type": "Limit",
+                "price": 52,
+                "quantity": 5,
+                "symbol": "BTC-USD",
+                "account": "alice"
+            }),
+        ))
+        .await
+        .unwrap();
+    let maker: OrderAck = json(res).await;
+    let res = app
+        .clone()
+        .oneshot(post(
+            &traders.bob,
+            json!({
+                "side": "Buy",
+                "order_type": "Market",
+                "quantity": 2,
+                "symbol": "BTC-USD"
+            }),
+        ))
+        .await
+        .unwrap();
+    let taker: OrderAck = json(res).await;
+
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &traders.alice,
+            "GET",
+            &format!("/orders/BTC-USD/{}", maker.order_id),
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let report: OrderReport = json(res).await;
+    assert_eq!(report.status, OrderStatus::PartiallyFilled);
+    assert_eq!(report.original_quantity, 5);
+    assert_eq!(report.remaining_quantity, 3);
+    assert_eq!(report.average_fill_price, Some(52.0));
+    assert_eq!(report.fills.len(), 1);
+
+    let taker_uri = format!("/orders/BTC-USD/{}", taker.order_id);
+    let res = app
+        .clone()
+        .oneshot(signed(&traders.bob, "GET", &taker_uri, String::new()))
+        .await
+        .unwrap();
+    let report = body_json(res).await;
+    assert_eq!(report["status"], "Filled");
+    assert_eq!(report["remaining_quantity"], 0);
+
+    // Order queries are signed and only show the caller's own orders
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(&taker_uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = app
+        .clone()
+        .oneshot(signed(&traders.alice, "GET", &taker_uri, String::new()))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/orders/BTC-USD")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &traders.alice,
+            "GET",
+            "/orders/BTC-USD",
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let open: Vec<OrderReport> = json(res).await;
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].order_id, maker.order_id);
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &traders.bob,
+            "GET",
+            "/orders/BTC-USD",
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    let open: Vec<OrderReport> = json(res).await;
+    assert!(open.is_empty());
+
+    let res = app
+        .oneshot(signed(
+            &traders.alice,
+            "GET",
+            "/orders/BTC-USD/123",
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn instruments_are_listed_and_enforced_on_entry() {
+    let (app, traders, _tmp) = test_app().await;
+
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/instruments")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let specs = body_json(res).await;
+    let btc = specs
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["pair"] == "BTC-USD")
+        .unwrap()
+        .clone();
+
+    let too_big = json!({
+        "side": "Buy",
+        "order_type": "Limit",
+        "price": 48,
+        "quantity": btc["max_quantity"].as_u64().unwrap() + 1,
+        "symbol": "BTC-USD"
+    });
+    let res = app
+        .oneshot(signed(
+            &traders.alice,
+            "POST",
+            "/orders",
+            too_big.to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let v = body_json(res).await;
+    assert_eq!(v["violation"]["reason"], "quantity_above_max");
+    assert_eq!(v["violation"]["max_quantity"], btc["max_quantity"]);
+}
+
+#[tokio::test]
+async fn admin_lists_new_crypto_pair_at_runtime() {
+    let (app, traders, _tmp) = test_app().await;
+
+    let spec = json!({
+        "pair": "ETH-BTC",
+        "tick_size": 1,
+        "lot_size": 1,
+        "min_quantity": 1,
+        "max_quantity": 1000,
+        "min_notional": 1,
+        "max_price": 1000
+    });
+    let add = || admin("POST", "/admin/instruments", spec.to_string());
+    let book = || {
+        Request::builder()
+            .uri("/book/ETH-BTC")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let res = app.clone().oneshot(book()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = app.clone().oneshot(add()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let res = app.clone().oneshot(add()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let order = json!({
+        "side": "Sell",
+        "order_type": "Limit",
+        "price": 30,
+        "quantity": 2,
+        "symbol": "ETH-BTC"
+    });
+    let res = app
+        .clone()
+        .oneshot(signed(&traders.alice, "POST", "/orders", order.to_string()))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = app.clone().oneshot(book()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(body_json(res).await["asks"], json!([[30, 2]]));
+
+    let res = app
+        .oneshot(admin(
+            "PUT",
+            "/admin/instruments/ETH-BTC",
+            json!({
+                "pair": "ETH-BTC",
+                "tick_size": 5,
+                "lot_size": 1,
+                "min_quantity": 1,
+                "max_quantity": 1000,
+                "min_notional": 1,
+                "max_price": 1000
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(body_json(res).await["tick_size"], 5);
+}
+
+#[tokio::test]
+async fn market_phase_gates_orders_and_cancels() {
+    let (app, traders, _tmp) = test_app().await;
+
+    let set_phase = |phase: &str| {
+        admin(
+            "PUT",
+            "/admin/markets/BTC-USD/phase",
+            json!({ "phase": phase }).to_string(),
+        )
+    };
+    let limit_buy = || {
+        signed(
+            &traders.alice,
+            "POST",
+            "/orders",
+            json!({
+                "side": "Buy",
+                "order_type": "Limit",
+                "price": 48,
+                "quantity": 1,
+                "symbol": "BTC-USD"
+            })
+            .to_string(),
+        )
+    };
+
+    let res = app.clone().oneshot(limit_buy()).await.unwrap();
+    let ack: OrderAck = json(res).await;
+
+    let res = app.clone().oneshot(set_phase("Halted")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let change = body_json(res).await;
+    assert_eq!(change["from"], "Continuous");
+    assert_eq!(change["phase"], "Halted");
+
+    let res = app.clone().oneshot(limit_buy()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    app.clone().oneshot(set_phase("Closed")).await.unwrap();
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &traders.alice,
+            "DELETE",
+            &format!("/orders/BTC-USD/{}", ack.order_id),
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let res = app.clone().oneshot(set_phase("Continuous")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let res = app
+        .oneshot(
+            Request::builder()
+                .uri("/markets/BTC-USD")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(body_json(res).await["phase"], "Closed");
+}
+
+#[tokio::test]
+async fn opening_auction_reports_indicative_price_then_uncrosses() {
+    let (app, traders, _tmp) = test_app().await;
+
+    let set_phase = |phase: &str| {
+        admin(
+            "PUT",
+            "/admin/markets/BTC-USD/phase",
+            json!({ "phase": phase }).to_string(),
+        )
+    };
+    let limit = |key: &ApiCredentials, side: &str, price: u64| {
+        signed(
+            key,
+            "POST",
+            "/orders",
+            json!({
+                "side": side,
+                "order_type": "Limit",
+                "price": price,
+                "quantity": 2,
+                "symbol": "BTC-USD"
+            })
+            .to_string(),
+        )
+    };
+    let market_status = || {
+        Request::builder()
+            .uri("/markets/BTC-USD")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    app.clone().oneshot(set_phase("Halted")).await.unwrap();
+    app.clone().oneshot(set_phase("PreOpen")).await.unwrap();
+    for (key, side, price) in [(&traders.alice, "Sell", 48), (&traders.bob, "Buy", 50)] {
+        let res = app.clone().oneshot(limit(key, side, price)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    let status = body_json(app.clone().oneshot(market_status()).await.unwrap()).await;
+    assert_eq!(status["phase"], "PreOpen");
+    assert_eq!(status["auction"]["price"], 48);
+    assert_eq!(status["auction"]["volume"], 2);
+
+    let res = app.clone().oneshot(set_phase("Continuous")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/trades/BTC-USD")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let page = body_json(res).await;
+    let trades = page["items"].as_array().unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0]["price"], 48);
+    assert_eq!(trades[0]["quantity"], 2);
+
+    let status = body_json(app.oneshot(market_status()).await.unwrap()).await;
+    assert!(status["auction"].is_null());
+}
+
+#[tokio::test]
+async fn price_band_breach_rejects_and_starts_volatility_auction() {
+    let (app, traders, _tmp) = test_app().await;
+
+    let res = app
+        .clone()
+        .oneshot(admin(
+            "PUT",
+            "/admin/instruments/BTC-USD",
+            json!({
+                "pair": "BTC-USD", "tick_size": 1, "lot_size": 1, "min_quantity": 1,
+                "max_quantity": 1000, "min_notional": 1, "max_price": 1000,
+                "price_bands": { "dynamic_bps": 1000, "auction_secs": 60 }
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let order =
+        |key: &ApiCredentials, body: Value| signed(key, "POST", "/orders", body.to_string());
+    let limit = |key: &ApiCredentials, side: &str, price: u64, quantity: u64| {
+        order(
+            key,
+            json!({
+                "side": side, "order_type": "Limit", "price": price,
+                "quantity": quantity, "symbol": "BTC-USD"
+            }),
+        )
+    };
+    let (alice, bob) = (&traders.alice, &traders.bob);
+    for (key, side, price, quantity) in [
+        (alice, "Sell", 50, 2),
+        (alice, "Sell", 80, 5),
+        (bob, "Buy", 50, 1),
+    ] {
+        let res = app
+            .clone()
+            .oneshot(limit(key, side, price, quantity))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    // Last trade 50 with a 10% band: [45, 55]
+    let res = app.clone().oneshot(limit(bob, "Buy", 60, 1)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert!(
+        body_json(res).await["error"]
+            .as_str()
+            .unwrap()
+            .contains("outside the price band")
+    );
+
+    let res = app
+        .clone()
+        .oneshot(order(
+            bob,
+            json!({
+                "side": "Buy", "order_type": "Market", "quantity": 5, "symbol": "BTC-USD"
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let ack = body_json(res).await;
+    assert_eq!(
+        ack["trades"].as_array().unwrap().len(),
+        1,
+        "only the order inside the band trades"
+    );
+
+    let res = app
+        .oneshot(
+            Request::builder()
+                .uri("/markets/BTC-USD")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = body_json(res).await;
+    assert_eq!(status["phase"], "VolatilityAuction");
+    assert_eq!(status["reference_price"], 50);
+    assert!(status["auction_ends_at"].is_u64());
+}
+
+#[tokio::test]
+async fn market_orders_use_default_slippage_protection() {
+    let dir = tempdir().unwrap();
+    let state = AppState::new(dir.path())
+        .await
+        .unwrap()
+        .with_default_slippage(Some(MaxSlippage::Ticks(2)));
+    let traders = Traders::new(&state).await;
+    let app = router(state);
+
+    let order =
+        |key: &ApiCredentials, body: Value| signed(key, "POST", "/orders", body.to_string());
+    for price in [50, 52, 60] {
+        let res = app
+            .clone()
+            .oneshot(order(
+                &traders.alice,
+                json!({
+                    "side": "Sell", "order_type": "Limit", "price": price,
+                    "quantity": 1, "symbol": "BTC-USD"
+                }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    let res = app
+        .clone()
+        .oneshot(order(
+            &traders.bob,
+            json!({
+                "side": "Buy", "order_type": "Limit", "price": 60, "quantity": 1,
+                "symbol": "BTC-USD", "max_slippage": { "ticks": 1 }
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = app
+        .clone()
+        .oneshot(order(
+            &traders.bob,
+            json!({
+                "side": "Buy", "order_type": "Market", "quantity": 3, "symbol": "BTC-USD"
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let ack = body_json(res).await;
+    let prices: Vec<u64> = ack["trades"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["price"].as_u64().unwrap())
+        .collect();
+    assert_eq!(
+        prices,
+        vec![50, 52],
+        "default protection stops two ticks above 50"
+    );
+
+    let res = app
+        .oneshot(order(
+            &traders.bob,
+            json!({
+                "side": "Buy", "order_type": "Market", "quantity": 1,
+                "symbol": "BTC-USD", "max_slippage": { "bps": 0 }
+            }),
+        ))
+        .await
+        .unwrap();
+    let ack = body_json(res).await;
+    assert_eq!(
+        ack["trades"][0]["price"], 60,
+        "protection is measured from the best price at arrival"
+    );
+}
+
+#[tokio::test]
+async fn fees_are_charged_on_trades_and_reported() {
+    let (app, traders, _tmp) = test_app().await;
+
+    let res = app
+        .clone()
+        .oneshot(admin(
+            "PUT",
+            "/admin/instruments/BTC-USD",
+            json!({
+                "pair": "BTC-USD", "tick_size": 1, "lot_size": 1, "min_quantity": 1,
+                "max_quantity": 1000, "min_notional": 1, "max_price": 100000,
+                "fees": { "maker_bps": -2, "taker_bps": 5 }
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let order =
+        |key: &ApiCredentials, body: Value| signed(key, "POST", "/orders", body.to_string());
+    app.clone()
+        .oneshot(order(
+            &traders.alice,
+            json!({
+                "side": "Sell", "order_type": "Limit", "price": 10000, "quantity": 10,
+                "symbol": "BTC-USD"
+            }),
+        ))
+        .await
+        .unwrap();
+    let res = app
+        .clone()
+        .oneshot(order(
+            &traders.bob,
+            json!({
+                "side": "Buy", "order_type": "Market", "quantity": 10,
+                "symbol": "BTC-USD"
+            }),
+        ))
+        .await
+        .unwrap();
+    let ack = body_json(res).await;
+    assert_eq!(ack["trades"][0]["maker_fee"], -20);
+    assert_eq!(ack["trades"][0]["taker_fee"], 50);
+
+    let res = app
+        .oneshot(
+            Request::builder()
+                .uri("/fees/BTC-USD")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let report = body_json(res).await;
+    assert_eq!(report["volume"], 100_000);
+    assert_eq!(report["net_fees"], 30);
+    assert_eq!(report["accounts"]["alice"]["maker_fees"], -20);
+    assert_eq!(report["accounts"]["bob"]["taker_fees"], 50);
+}
+
+#[tokio::test]
+async fn order_entry_requires_signed_requests_from_the_owner() {
+    let (app, traders, _tmp) = test_app().await;
+    let order = json!({
+        "side": "Buy", "order_type": "Limit", "price": 48, "quantity": 1, "symbol": "BTC-USD"
+    });
+
+    let res = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/orders")
+                .header("content-type", "application/json")
+                .body(Body::from(order.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(body_json(res).await["error"], "missing `x-api-key` header");
+
+    // A signed request may only be sent once
+    let req = signed(&traders.alice, "POST", "/orders", order.to_string());
+    let (parts, body) = req.into_parts();
+    let replay = Request::from_parts(parts.clone(), Body::from(order.to_string()));
+    let res = app
+        .clone()
+        .oneshot(Request::from_parts(parts, body))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let ack: OrderAck = json(res).await;
+    let res = app.clone().oneshot(replay).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // The body is covered by the signature
+    let mut req = signed(&traders.alice, "POST", "/orders", order.to_string());
+    *req.body_mut() = Body::from(
+        json!({
+            "side": "Buy", "order_type": "Limit", "price": 48, "quantity": 500, "symbol": "BTC-USD"
+        })
+        .to_string(),
+    );
+    let res = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let mut spoofed = order.clone();
+    spoofed["account"] = json!("alice");
+    let res = app
+        .clone()
+        .oneshot(signed(&traders.bob, "POST", "/orders", spoofed.to_string()))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let uri = format!("/orders/BTC-USD/{}", ack.order_id);
+    let res = app
+        .clone()
+        .oneshot(signed(&traders.bob, "DELETE", &uri, String::new()))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &traders.bob,
+            "PATCH",
+            &uri,
+            json!({ "price": 47 }).to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let res = app
+        .clone()
+        .oneshot(signed(&traders.alice, "DELETE", &uri, String::new()))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = app
+        .oneshot(signed(&traders.alice, "GET", &uri, String::new()))
+        .await
+        .unwrap();
+    let report = body_json(res).await;
+    assert_eq!(report["status"], "Cancelled");
+}
+
+#[tokio::test]
+async fn admin_routes_require_the_admin_token() {
+    let (app, _, _tmp) = test_app().await;
+    let issue_key = |token: Option<&str>| {
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/admin/accounts/mallory/keys");
+        if let Some(token) = token {
+            req = req.header(ADMIN_TOKEN_HEADER, token);
+        }
+        req.body(Body::empty()).unwrap()
+    };
+
+    let res = app.clone().oneshot(issue_key(None)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        body_json(res).await["error"],
+        "missing `x-admin-token` header"
+    );
+    let res = app.clone().oneshot(issue_key(Some("guess"))).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(body_json(res).await["error"], "invalid admin token");
+    let res = app
+        .clone()
+        .oneshot(issue_key(Some(ADMIN_TOKEN)))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let res = app
+        .oneshot(admin(
+            "PUT",
+            "/admin/markets/BTC-USD/phase",
+            json!({ "phase": "Halted" }).to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // Without a configured token the admin API is off altogether
+    let dir = tempdir().unwrap();
+    let app = router(AppState::new(dir.path()).await.unwrap());
+    let res = app.oneshot(issue_key(Some(ADMIN_TOKEN))).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn balances_are_reserved_settled_and_restored() {
+    let dir = tempdir().unwrap();
+    let state = AppState::new(dir.path())
+        .await
+        .unwrap()
+        .with_admin_token(ADMIN_TOKEN);
+    let carol = state.create_api_key("carol").await;
+    let dave = state.create_api_key("dave").await;
+    let app = router(state.clone());
+
+    let transfer = |account: &str, kind: &str, asset: &str, amount: u64| {
+        admin(
+            "POST",
+            &format!("/admin/accounts/{account}/{kind}"),
+            json!({ "asset": asset, "amount": amount }).to_string(),
+        )
+    };
+    // Balances only move with the admin token
+    for kind in ["deposit", "withdraw"] {
+        let (mut parts, body) = transfer("carol", kind, "USD", 1_000).into_parts();
+        parts.headers.remove(ADMIN_TOKEN_HEADER);
+        let res = app
+            .clone()
+            .oneshot(Request::from_parts(parts, body))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+    assert!(state.balances_of("carol").is_empty());
+
+    let res = app
+        .clone()
+        .oneshot(transfer("carol", "deposit", "USD", 1_000))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(body_json(res).await["balances"]["USD"]["available"], 1_000);
+    let res = app
+        .clone()
+        .oneshot(transfer("dave", "deposit", "BTC", 4))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let buy = |quantity: u64| {
+        json!({
+            "side": "Buy", "order_type": "Limit", "price": 50, "quantity": quantity,
+            "symbol": "BTC-USD"
+        })
+        .to_string()
+    };
+    let res = app
+        .clone()
+        .oneshot(signed(&carol, "POST", "/orders", buy(30)))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        body_json(res).await["error"],
+        "insufficient USD: 1500 required, 1000 available"
+    );
+    let res = app
+        .clone()
+        .oneshot(signed(&carol, "POST", "/orders", buy(10)))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = app
+        .clone()
+        .oneshot(signed(&carol, "GET", "/balances", String::new()))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let balances = body_json(res).await;
+    assert_eq!(balances["account"], "carol");
+    assert_eq!(
+        balances["balances"]["USD"],
+        json!({ "available": 500, "reserved": 500 })
+    );
+
+    // Funds held by open orders cannot be withdrawn
+    let res = app
+        .clone()
+        .oneshot(transfer("carol", "withdraw", "USD", 600))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = app
+        .clone()
+        .oneshot(transfer("carol", "withdraw", "USD", 500))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &dave,
+            "POST",
+            "/orders",
+            json!({
+                "side": "Sell", "order_type": "Limit", "price": 50, "quantity": 4,
+                "symbol": "BTC-USD"
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(body_json(res).await["trades"].as_array().unwrap().len(), 1);
+    let res = app
+        .clone()
+        .oneshot(signed(&carol, "GET", "/balances", String::new()))
+        .await
+        .unwrap();
+    let balances = body_json(res).await;
+    assert_eq!(
+        balances["balances"]["USD"],
+        json!({ "available": 0, "reserved": 300 })
+    );
+    assert_eq!(balances["balances"]["BTC"]["available"], 4);
+    assert_eq!(
+        state.balances_of("dave")[&Asset::USD].available,
+        200,
+        "the seller is paid"
+    );
+
+    // Balances survive a restart; holds of the orders that did not do not
+    drop((app, state));
+    let state = AppState::new(dir.path()).await.unwrap();
+    let carol = state.balances_of("carol");
+    assert_eq!(carol[&Asset::USD].total(), 300);
+    assert_eq!(carol[&Asset::USD].available, 300);
+    assert_eq!(carol[&Asset::BTC].available, 4);
+}
+
+#[tokio::test]
+async fn risk_limits_reject_orders_with_codes_and_count_them() {
+    let (app, Traders { alice, .. }, _tmp) = test_app().await;
+
+    let limits =
+        json!({ "max_order_quantity": 10, "max_open_orders": 1, "max_orders_per_second": 3 })
+            .to_string();
+    // Traders cannot set their own limits, signed or not
+    let mut unauthenticated = admin("PUT", "/admin/accounts/alice/risk-limits", limits.clone());
+    unauthenticated.headers_mut().remove(ADMIN_TOKEN_HEADER);
+    let res = app.clone().oneshot(unauthenticated).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &alice,
+            "PUT",
+            "/admin/accounts/alice/risk-limits",
+            limits.clone(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = app
+        .clone()
+        .oneshot(admin("PUT", "/admin/accounts/alice/risk-limits", limits))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        body_json(res).await["limits"]["max_order_notional"],
+        Value::Null
+    );
+
+    let buy = |quantity: u64| {
+        json!({
+            "side": "Buy", "order_type": "Limit", "price": 50, "quantity": quantity,
+            "symbol": "BTC-USD"
+        })
+        .to_string()
+    };
+    let res = app
+        .clone()
+        .oneshot(signed(&alice, "POST", "/orders", buy(11)))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = body_json(res).await;
+    assert_eq!(body["code"], "max_order_quantity");
+    assert_eq!(body["limit"], 10);
+
+    let res = app
+        .clone()
+        .oneshot(signed(&alice, "POST", "/orders", buy(10)))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = app
+        .clone()
+        .oneshot(signed(&alice, "POST", "/orders", buy(1)))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(body_json(res).await["code"], "max_open_orders");
+
+    let res = app
+        .clone()
+        .oneshot(signed(&alice, "POST", "/orders", buy(1)))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body_json(res).await["code"], "max_order_rate");
+
+    let res = app
+        .oneshot(
+            Request::builder()
+                .uri("/metrics")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let bytes = res.into_body().collect().await.unwrap().to_bytes();
+    let metrics = String::from_utf8(bytes.to_vec()).unwrap();
+    for code in ["max_order_quantity", "max_open_orders", "max_order_rate"] {
+        assert!(
+            metrics.contains(&format!("risk_rejections_total{{code=\"{code}\"}} 1")),
+            "{metrics}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn mass_cancel_filters_by_pair_and_side() {
+    let (app, Traders { alice, bob }, _tmp) = test_app().await;
+
+    let mut ids = Vec::new();
+    for (key, side, price, symbol) in [
+        (&alice, "Buy", 40, "BTC-USD"),
+        (&alice, "Sell", 60, "BTC-USD"),
+        (&alice, "Buy", 40, "ETH-USD"),
+        (&bob, "Buy", 41, "BTC-USD"),
+    ] {
+        let order = json!({
+            "side": side, "order_type": "Limit", "price": price, "quantity": 1, "symbol": symbol
+        });
+        let res = app
+            .clone()
+            .oneshot(signed(key, "POST", "/orders", order.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        ids.push(
+            body_json(res).await["order_id"]
+                .as_str()
+                .unwrap()
+                .to_string(),
+        );
+    }
+
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &alice,
+            "DELETE",
+            "/orders?pair=BTC-USD&side=Buy",
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(body_json(res).await["cancelled"], json!([ids[0]]));
+
+    let res = app
+        .clone()
+        .oneshot(signed(&alice, "DELETE", "/orders", String::new()))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let mut cancelled: MassCancelAck = serde_json::from_value(body_json(res).await).unwrap();
+    cancelled.cancelled.sort();
+    let mut expected: Vec<u128> = ids[1..3].iter().map(|id| id.parse().unwrap()).collect();
+    expected.sort();
+    assert_eq!(cancelled.cancelled, expected);
+
+    // Bob's order is untouched
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &bob,
+            "GET",
+            &format!("/orders/BTC-USD/{}", ids[3]),
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(body_json(res).await["status"], "New");
+
+    let res = app
+        .oneshot(signed(
+            &alice,
+            "DELETE",
+            "/orders?pair=BTC-EUR",
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn cancel_all_after_cancels_unless_refreshed() {
+    let dir = tempdir().unwrap();
+    let state = AppState::new(dir.path()).await.unwrap();
+    let Traders { alice, .. } = Traders::new(&state).await;
+    let app = router(state.clone());
+
+    let order = json!({
+        "side": "Buy", "order_type": "Limit", "price": 40, "quantity": 1, "symbol": "BTC-USD"
+    });
+    let res = app
+        .clone()
+        .oneshot(signed(&alice, "POST", "/orders", order.to_string()))
+        .await
+        .unwrap();
+    let order_id = body_json(res).await["order_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let arm = |timeout_ms: u64| {
+        signed(
+            &alice,
+            "POST",
+            "/cancel-all-after",
+            json!({ "timeout_ms": timeout_ms }).to_string(),
+        )
+    };
+    let status = |app: Router| {
+        let req = signed(
+            &alice,
+            "GET",
+            &format!("/orders/BTC-USD/{}", order_id),
+            String::new(),
+        );
+        async move {
+            let res = app.oneshot(req).await.unwrap();
+            body_json(res).await["status"].clone()
+        }
+    };
+
+    // Disarming in time keeps the order
+    let res = app.clone().oneshot(arm(10)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(body_json(res).await["cancel_at"].is_u64());
+    let res = app.clone().oneshot(arm(0)).await.unwrap();
+    assert_eq!(body_json(res).await["cancel_at"], Value::Null);
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    assert_eq!(state.trip_cancel_deadlines().await, 0);
+    assert_eq!(status(app.clone()).await, "New");
+
+    // Not refreshing it cancels everything, once
+    app.clone().oneshot(arm(10)).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    assert_eq!(state.trip_cancel_deadlines().await, 1);
+    assert_eq!(status(app.clone()).await, "Cancelled");
+    assert!(state.cancel_deadlines.read().await.is_empty());
+}
+
+#[tokio::test]
+async fn client_order_ids_make_retries_idempotent() {
+    let dir = tempdir().unwrap();
+    let state = AppState::new(dir.path())
+        .await
+        .unwrap()
+        .with_client_order_id_window(std::time::Duration::from_millis(200));
+    let Traders { alice, bob } = Traders::new(&state).await;
+    let app = router(state.clone());
+
+    let order = |client_order_id: &str| {
+        json!({
+            "side": "Buy", "order_type": "Limit", "price": 40, "quantity": 1,
+            "symbol": "BTC-USD", "client_order_id": client_order_id
+        })
+        .to_string()
+    };
+    let place = |key: &ApiCredentials, body: String| {
+        let app = app.clone();
+        let req = signed(key, "POST", "/orders", body);
+        async move {
+            let res = app.oneshot(req).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            body_json(res).await["order_id"].clone()
+        }
+    };
+
+    // A retry gets the original order back; another account may use the same ID
+    let first = place(&alice, order("quote-1")).await;
+    assert_eq!(place(&alice, order("quote-1")).await, first);
+    assert_ne!(place(&bob, order("quote-1")).await, first);
+    let res = app
+        .clone()
+        .oneshot(signed(&alice, "GET", "/orders/BTC-USD", String::new()))
+        .await
+        .unwrap();
+    assert_eq!(body_json(res).await.as_array().unwrap().len(), 1);
+
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &alice,
+            "GET",
+            "/orders/client/quote-1",
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let report = body_json(res).await;
+    assert_eq!(report["order_id"], first);
+    assert_eq!(report["client_order_id"], "quote-1");
+
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &alice,
+            "DELETE",
+            "/orders/client/quote-1",
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &alice,
+            "GET",
+            "/orders/client/quote-2",
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // After the window the ID is forgotten and places a new order
+    tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+    assert_eq!(state.prune_client_order_ids().await, 2);
+    assert_eq!(state.prune_client_order_ids().await, 0);
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &alice,
+            "GET",
+            "/orders/client/quote-1",
+            String::new(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_ne!(place(&alice, order("quote-1")).await, first);
+
+    let res = app
+        .oneshot(signed(&alice, "POST", "/orders", order("")))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn batches_place_and_cancel_in_order_with_per_item_results() {
+    let dir = tempdir().unwrap();
+    let state = AppState::new(dir.path()).await.unwrap();
+    let Traders { alice, bob } = Traders::new(&state).await;
+    let app = router(state.clone());
+    let mut book_rx = state.book_tx.subscribe();
+
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &bob,
+            "POST",
+            "/orders",
+            json!({
+                "side": "Sell", "order_type": "Limit", "price": 50, "quantity": 1,
+                "symbol": "BTC-USD"
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    book_rx.recv().await.unwrap();
+
+    let batch = json!({ "orders": [
+        { "side": "Buy", "order_type": "Limit", "price": 49, "quantity": 2, "symbol": "BTC-USD" },
+        { "side": "Buy", "order_type": "Limit", "price": 40, "quantity": 0, "symbol": "BTC-USD" },
+        { "side": "Buy", "order_type": "Limit", "price": 50, "quantity": 1, "symbol": "BTC-USD" },
+        { "side": "Buy", "order_type": "Limit", "price": 30, "quantity": 1, "symbol": "ETH-USD" },
+    ]});
+    let res = app
+        .clone()
+        .oneshot(signed(&alice, "POST", "/orders/batch", batch.to_string()))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let results = body_json(res).await["results"].clone();
+    let statuses: Vec<_> = results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["status"].as_u64().unwrap())
+        .collect();
+    assert_eq!(statuses, vec![200, 400, 200, 200]);
+    assert_eq!(results[1]["body"]["error"], "quantity must be > 0");
+    assert_eq!(results[2]["body"]["trades"][0]["price"], 50);
+
+    // One book update per pair, however many orders touched it
+    let mut updated = vec![
+        book_rx.recv().await.unwrap().code(),
+        book_rx.recv().await.unwrap().code(),
+    ];
+    updated.sort();
+    assert_eq!(updated, vec!["BTC-USD", "ETH-USD"]);
+    assert!(book_rx.try_recv().is_err());
+
+    let cancels = json!({ "orders": [
+        { "symbol": "BTC-USD", "order_id": results[0]["body"]["order_id"] },
+        { "symbol": "BTC-USD", "order_id": results[0]["body"]["order_id"] },
+        { "symbol": "ETH-USD", "order_id": results[3]["body"]["order_id"] },
+    ]});
+    let res = app
+        .clone()
+        .oneshot(signed(&bob, "DELETE", "/orders/batch", cancels.to_string()))
+        .await
+        .unwrap();
+    let results = body_json(res).await["results"].clone();
+    assert_eq!(results[0]["status"], 403);
+
+    let res = app
+        .clone()
+        .oneshot(signed(
+            &alice,
+            "DELETE",
+            "/orders/batch",
+            cancels.to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let results = body_json(res).await["results"].clone();
+    assert_eq!(results[0]["status"], 200);
+    assert_eq!(results[0]["body"]["status"], "cancelled");
+    assert_eq!(results[1]["status"], 404);
+    assert_eq!(results[2]["status"], 200);
+
+    let res = app
+        .oneshot(signed(
+            &alice,
+            "POST",
+            "/orders/batch",
+            json!({ "orders": [] }).to_string(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
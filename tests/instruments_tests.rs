@@ -10,7 +10,9 @@ fn asset_display_and_parse() {
     assert_eq!("BTC".parse::<Asset>().unwrap(), Asset::BTC);
     assert_eq!("ETH".parse::<Asset>().unwrap(), Asset::ETH);
     assert_eq!("USD".parse::<Asset>().unwrap(), Asset::USD);
-    assert!("DOGE".parse::<Asset>().is_err());
+    assert_eq!("DOGE".parse::<Asset>().unwrap().code(), "DOGE");
+    assert!("doge".parse::<Asset>().is_err());
+    assert!("".parse::<Asset>().is_err());
 }
 
 #[test]
@@ -26,8 +28,14 @@ fn pair_display_and_parse_supported() {
 
 #[test]
 fn pair_parse_rejects_unsupported() {
-    let e = "BTC-EUR".parse::<Pair>().unwrap_err();
-    assert!(e.contains("unsupported"));
+    for bad in ["BTCEUR", "BTC-", "BTC-BTC", "btc-usd"] {
+        let e = bad.parse::<Pair>().unwrap_err();
+        assert!(e.contains("unsupported"));
+    }
+    // Well-formed pairs parse; listing is the registry's job
+    let eth_btc = "ETH-BTC".parse::<Pair>().unwrap();
+    assert_eq!((&eth_btc.base, &eth_btc.quote), (&Asset::ETH, &Asset::BTC));
+    assert!(!InstrumentRegistry::default().contains(&eth_btc));
 }
 
 #[test]
@@ -85,3 +93,93 @@ fn asset_serde_as_string() {
     let a: Asset = serde_json::from_str("\"ETH\"").unwrap();
     assert_eq!(a, Asset::ETH);
 }
+
+#[test]
+fn spec_checks_tick_lot_bounds_and_notional() {
+    let spec = InstrumentSpec {
+        tick_size: 5,
+        lot_size: 10,
+        min_quantity: 10,
+        max_quantity: 1_000,
+        min_notional: 500,
+        max_price: 100,
+        ..InstrumentSpec::new(BTC_USD)
+    };
+    assert!(spec.check_price(95).is_ok());
+    assert!(spec.check_price(97).is_err());
+    assert!(spec.check_price(0).is_err());
+    assert!(spec.check_price(105).is_err());
+
+    assert!(spec.check_quantity(20).is_ok());
+    assert!(spec.check_quantity(25).is_err());
+    assert!(spec.check_quantity(0).is_err());
+    assert!(spec.check_quantity(1_010).is_err());
+
+    assert!(spec.check_notional(50, 10).is_ok());
+    let violation = spec.check_notional(45, 10).unwrap_err();
+    assert_eq!(
+        serde_json::to_value(&violation).unwrap(),
+        serde_json::json!({"reason": "notional_below_min", "notional": 450, "min_notional": 500})
+    );
+}
+
+#[test]
+fn price_bands_intersect_static_and_dynamic() {
+    let bands = PriceBands {
+        static_bps: Some(1_000),
+        dynamic_bps: Some(200),
+        ..PriceBands::default()
+    };
+    assert_eq!(bands.limits(None, None), None);
+    assert_eq!(bands.limits(Some(100), None), Some((90, 110)));
+    assert_eq!(bands.limits(None, Some(100)), Some((98, 102)));
+    assert_eq!(bands.limits(Some(100), Some(109)), Some((107, 110)));
+    assert_eq!(PriceBands::default().limits(Some(100), Some(100)), None);
+
+    // Specs written before bands existed still load, with bands off
+    let spec: InstrumentSpec = serde_json::from_str(
+        r#"{"pair": "BTC-USD", "tick_size": 1, "lot_size": 1, "min_quantity": 1,
+            "max_quantity": 10, "min_notional": 1, "max_price": 100}"#,
+    )
+    .unwrap();
+    assert_eq!(spec.price_bands, PriceBands::default());
+}
+
+#[test]
+fn default_registry_lists_supported_pairs() {
+    let registry = InstrumentRegistry::default();
+    let listed: Vec<&Pair> = registry.pairs().collect();
+    assert_eq!(listed, Pair::supported().iter().collect::<Vec<_>>());
+    for p in Pair::supported() {
+        assert_eq!(&registry.get(p).unwrap().pair, p);
+    }
+}
+
+#[test]
+fn registry_loads_from_file_and_rejects_bad_specs() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("instruments.json");
+    let eth_btc: Pair = "ETH-BTC".parse().unwrap();
+    let specs = vec![
+        InstrumentSpec::new(BTC_USD),
+        InstrumentSpec {
+            tick_size: 10,
+            ..InstrumentSpec::new(eth_btc.clone())
+        },
+    ];
+    std::fs::write(&path, serde_json::to_vec(&specs).unwrap()).unwrap();
+
+    let mut registry = InstrumentRegistry::load(&path).unwrap();
+    assert_eq!(registry.get(&eth_btc).unwrap().tick_size, 10);
+    assert!(!registry.contains(&ETH_USD));
+
+    assert!(registry.insert(InstrumentSpec::new(BTC_USD)).is_err());
+    let zero_tick = InstrumentSpec {
+        tick_size: 0,
+        ..InstrumentSpec::new(ETH_USD)
+    };
+    assert!(registry.insert(zero_tick).is_err());
+    assert!(registry.update(InstrumentSpec::new(ETH_USD)).is_err());
+    registry.insert(InstrumentSpec::new(ETH_USD)).unwrap();
+    assert!(registry.contains(&ETH_USD));
+}
@@ -1,19 +1,41 @@
 use std::time::Duration;
 
 use axum::Router;
-use futures_util::StreamExt;
+use futures_util::{SinkExt, StreamExt};
 use order_book_engine::{
-    api::{WsFrame, router},
+    accounts::{ADMIN_TOKEN_HEADER, ApiCredentials},
+    api::{AccountPositions, WsFrame, router},
+    depth::LevelUpdate,
+    orderbook::BookSnapshot,
+    orders::Side,
     state::AppState,
 };
 use serde_json::json;
 use tempfile::tempdir;
-use tokio::net::TcpListener;
-use tokio_tungstenite::connect_async;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{Message, client::IntoClientRequest},
+};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// The admin token of the servers under test.
+const ADMIN_TOKEN: &str = "test-admin-token";
 
 async fn spawn_server() -> (String, tokio::task::JoinHandle<()>, tempfile::TempDir) {
+    spawn_server_with_heartbeat(Duration::from_secs(10)).await
+}
+
+async fn spawn_server_with_heartbeat(
+    heartbeat: Duration,
+) -> (String, tokio::task::JoinHandle<()>, tempfile::TempDir) {
     let dir = tempdir().unwrap();
-    let state = AppState::new(dir.path()).await.unwrap();
+    let state = AppState::new(dir.path())
+        .await
+        .unwrap()
+        .with_ws_heartbeat(heartbeat)
+        .with_admin_token(ADMIN_TOKEN);
     let app: Router = router(state);
 
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -27,10 +49,10 @@ async fn spawn_server() -> (String, tokio::task::JoinHandle<()>, tempfile::TempD
     let client = reqwest::Client::new();
     let ok = tokio::time::timeout(Duration::from_secs(2), async {
         loop {
-            if let Ok(r) = client.get(format!("{}/book/BTC-USD", base)).send().await {
-                if r.status().is_success() {
-                    break;
-                }
+            if let Ok(r) = client.get(format!("{}/book/BTC-USD", base)).send().await
+                && r.status().is_success()
+            {
+                break;
             }
             tokio::time::sleep(Duration::from_millis(25)).await;
         }
@@ -42,6 +64,29 @@ async fn spawn_server() -> (String, tokio::task::JoinHandle<()>, tempfile::TempD
     (base.to_string(), handle, dir)
 }
 
+/// Issues an API key for `account` and funds it with BTC and USD.
+async fn open_account(client: &reqwest::Client, http_base: &str, account: &str) -> ApiCredentials {
+    let r = client
+        .post(format!("{}/admin/accounts/{}/keys", http_base, account))
+        .header(ADMIN_TOKEN_HEADER, ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::CREATED);
+    let key = r.json::<ApiCredentials>().await.unwrap();
+    for asset in ["BTC", "USD"] {
+        let r = client
+            .post(format!("{}/admin/accounts/{}/deposit", http_base, account))
+            .header(ADMIN_TOKEN_HEADER, ADMIN_TOKEN)
+            .json(&json!({ "asset": asset, "amount": 1_000_000 }))
+            .send()
+            .await
+            .unwrap();
+        assert!(r.status().is_success());
+    }
+    key
+}
+
 #[tokio::test]
 async fn websocket_snapshot_and_trade_flow() {
     let (http_base, server, _tmpdir) = spawn_server().await;
@@ -59,12 +104,15 @@ async fn websocket_snapshot_and_trade_flow() {
         other => panic!("expected text frame, got {:?}", other),
     };
     let init: WsFrame = serde_json::from_str(&text).expect("parse WsFrame");
-    match init {
-        WsFrame::BookSnapshot(_snap) => { /* good */ }
+    let seq = match init {
+        WsFrame::BookSnapshot(snap) => snap.seq,
         _ => panic!("expected initial BookSnapshot, got {:?}", init),
-    }
+    };
 
     let client = reqwest::Client::new();
+    let alice = &open_account(&client, &http_base, "alice").await;
+    let bob = &open_account(&client, &http_base, "bob").await;
+
     let body = json!({
         "side": "Buy",
         "order_type": "Limit",
@@ -72,9 +120,8 @@ async fn websocket_snapshot_and_trade_flow() {
         "quantity": 5,
         "symbol": "BTC-USD"
     });
-    let r = client
-        .post(format!("{}/orders", http_base))
-        .json(&body)
+    let r = alice
+        .request(&client, reqwest::Method::POST, &http_base, "/orders", &body)
         .send()
         .await
         .unwrap();
@@ -89,15 +136,25 @@ async fn websocket_snapshot_and_trade_flow() {
         tokio_tungstenite::tungstenite::Message::Text(t) => t,
         other => panic!("expected text frame, got {:?}", other),
     };
-    let snap: WsFrame = serde_json::from_str(&text).expect("parse WsFrame");
-    let (bids, asks) = match snap {
-        WsFrame::BookSnapshot(s) => (s.bids, s.asks),
-        other => panic!("expected BookSnapshot, got {:?}", other),
+    let delta: WsFrame = serde_json::from_str(&text).expect("parse WsFrame");
+    let delta = match delta {
+        WsFrame::BookDelta(d) => d,
+        other => panic!("expected BookDelta, got {:?}", other),
     };
-    assert!(asks.is_empty(), "should not have asks yet");
-    assert!(!bids.is_empty(), "bids should not be empty");
-    assert_eq!(bids[0].0, 48, "top bid price should be 48");
-    assert_eq!(bids[0].1, 5, "top bid qty should be 5");
+    assert_eq!(
+        delta.seq,
+        seq + 1,
+        "deltas follow the snapshot's sequence number"
+    );
+    assert_eq!(
+        delta.changes,
+        vec![LevelUpdate {
+            side: Side::Buy,
+            price: 48,
+            quantity: 5
+        }],
+        "only the new bid level should change"
+    );
 
     let market = json!({
         "side": "Sell",
@@ -105,9 +162,14 @@ async fn websocket_snapshot_and_trade_flow() {
         "quantity": 2,
         "symbol": "BTC-USD"
     });
-    let r = client
-        .post(format!("{}/orders", http_base))
-        .json(&market)
+    let r = bob
+        .request(
+            &client,
+            reqwest::Method::POST,
+            &http_base,
+            "/orders",
+            &market,
+        )
         .send()
         .await
         .unwrap();
@@ -126,7 +188,7 @@ async fn websocket_snapshot_and_trade_flow() {
         let frame: WsFrame = serde_json::from_str(&text).expect("parse WsFrame");
         match frame {
             WsFrame::Trade(t) => break t,
-            WsFrame::BookSnapshot(_) => continue, // keep reading until the trade arrives
+            _ => continue, // keep reading until the trade arrives
         }
     };
 
@@ -138,3 +200,343 @@ async fn websocket_snapshot_and_trade_flow() {
 
     server.abort();
 }
+
+#[tokio::test]
+async fn account_websocket_streams_own_positions() {
+    let (http_base, server, _tmpdir) = spawn_server().await;
+    let ws_url = http_base.replace("http://", "ws://") + "/ws/account";
+    let client = reqwest::Client::new();
+    let alice = open_account(&client, &http_base, "alice").await;
+    let bob = open_account(&client, &http_base, "bob").await;
+
+    assert!(
+        connect_async(&ws_url).await.is_err(),
+        "the account socket requires a signed request"
+    );
+    let mut request = ws_url.as_str().into_client_request().unwrap();
+    for (name, value) in bob.headers("GET", "/ws/account", b"") {
+        request.headers_mut().insert(name, value.parse().unwrap());
+    }
+    let (mut ws, _resp) = connect_async(request).await.expect("ws connect");
+
+    for (key, side) in [(&alice, "Sell"), (&bob, "Buy")] {
+        let order = json!({
+            "side": side, "order_type": "Limit", "price": 50, "quantity": 2, "symbol": "BTC-USD"
+        });
+        let r = key
+            .request(
+                &client,
+                reqwest::Method::POST,
+                &http_base,
+                "/orders",
+                &order,
+            )
+            .send()
+            .await
+            .unwrap();
+        assert!(r.status().is_success());
+    }
+
+    // Alice's resting sell alone moves no position of Bob's; his fill does
+    let msg = tokio::time::timeout(Duration::from_secs(2), ws.next())
+        .await
+        .expect("ws recv timeout after fill")
+        .expect("ws closed")
+        .expect("ws error");
+    let text = match msg {
+        tokio_tungstenite::tungstenite::Message::Text(t) => t,
+        other => panic!("expected text frame, got {:?}", other),
+    };
+    match serde_json::from_str::<WsFrame>(&text).expect("parse WsFrame") {
+        WsFrame::Position(position) => {
+            assert_eq!(position.pair.code(), "BTC-USD");
+            assert_eq!(position.quantity, 2);
+            assert_eq!(position.average_entry_price, Some(50.0));
+        }
+        other => panic!("expected Position, got {:?}", other),
+    }
+
+    let positions: AccountPositions = bob
+        .get(&client, &http_base, "/positions")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(positions.account, "bob");
+    assert_eq!(positions.positions.len(), 1);
+    assert_eq!(positions.positions[0].quantity, 2);
+
+    server.abort();
+}
+
+/// Reads frames until the next `BookSnapshot` or `BookDelta`.
+async fn next_depth_frame(ws: &mut WsStream) -> WsFrame {
+    loop {
+        let msg = tokio::time::timeout(Duration::from_secs(2), ws.next())
+            .await
+            .expect("ws recv timeout")
+            .expect("ws closed")
+            .expect("ws error");
+        if let tokio_tungstenite::tungstenite::Message::Text(text) = msg {
+            let frame: WsFrame = serde_json::from_str(&text).expect("parse WsFrame");
+            if matches!(frame, WsFrame::BookSnapshot(_) | WsFrame::BookDelta(_)) {
+                return frame;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn websocket_deltas_keep_a_local_book_in_sync() {
+    let (http_base, server, _tmpdir) = spawn_server().await;
+    let client = reqwest::Client::new();
+    let alice = open_account(&client, &http_base, "alice").await;
+    let bob = open_account(&client, &http_base, "bob").await;
+
+    let place = |key: &ApiCredentials, side: &str, price: u64, quantity: u64| {
+        let order = json!({
+            "side": side, "order_type": "Limit", "price": price, "quantity": quantity, "symbol": "BTC-USD"
+        });
+        key.request(
+            &client,
+            reqwest::Method::POST,
+            &http_base,
+            "/orders",
+            &order,
+        )
+        .send()
+    };
+    // Changes made before connecting are part of the first snapshot
+    assert!(
+        place(&alice, "Buy", 48, 5)
+            .await
+            .unwrap()
+            .status()
+            .is_success()
+    );
+
+    let ws_url = http_base.replace("http://", "ws://") + "/ws/BTC-USD";
+    let (mut ws, _resp) = connect_async(&ws_url).await.expect("ws connect");
+    let mut local = match next_depth_frame(&mut ws).await {
+        WsFrame::BookSnapshot(snap) => snap,
+        other => panic!("expected initial BookSnapshot, got {:?}", other),
+    };
+    assert_eq!(local.seq, 1);
+    assert_eq!(local.bids, vec![(48, 5)]);
+
+    assert!(
+        place(&alice, "Buy", 47, 3)
+            .await
+            .unwrap()
+            .status()
+            .is_success()
+    );
+    assert!(
+        place(&bob, "Sell", 52, 4)
+            .await
+            .unwrap()
+            .status()
+            .is_success()
+    );
+    // Fills the bid at 48 and moves nothing else
+    assert!(
+        place(&bob, "Sell", 48, 5)
+            .await
+            .unwrap()
+            .status()
+            .is_success()
+    );
+
+    for expected in 2..=4 {
+        match next_depth_frame(&mut ws).await {
+            WsFrame::BookDelta(delta) => {
+                assert_eq!(delta.seq, expected, "sequence numbers have no gaps");
+                assert!(local.apply(&delta));
+            }
+            other => panic!("expected BookDelta, got {:?}", other),
+        }
+    }
+    assert_eq!(local.bids, vec![(47, 3)]);
+    assert_eq!(local.asks, vec![(52, 4)]);
+
+    // A REST snapshot is numbered like the feed, for resyncing after a gap
+    let book: BookSnapshot = client
+        .get(format!("{}/book/BTC-USD", http_base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(book.seq, 4);
+    assert_eq!((book.bids, book.asks), (local.bids, local.asks));
+
+    server.abort();
+}
+
+/// Polls `GET /orders/{pair}/{id}`, signed with the owner's `key`, until the
+/// order reports `status`.
+async fn wait_for_status(
+    client: &reqwest::Client,
+    http_base: &str,
+    key: &ApiCredentials,
+    order_id: &str,
+    status: &str,
+) {
+    let path = format!("/orders/BTC-USD/{}", order_id);
+    let reached = tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            let report: serde_json::Value = key
+                .get(client, http_base, &path)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            if report["status"] == status {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+    })
+    .await
+    .is_ok();
+    assert!(reached, "order {} never became {}", order_id, status);
+}
+
+/// Opens `path` on the account websocket, signed with `key`.
+async fn connect_account(http_base: &str, key: &ApiCredentials, path: &str) -> WsStream {
+    let mut request = (http_base.replace("http://", "ws://") + path)
+        .into_client_request()
+        .unwrap();
+    for (name, value) in key.headers("GET", path, b"") {
+        request.headers_mut().insert(name, value.parse().unwrap());
+    }
+    let (ws, _resp) = connect_async(request).await.expect("ws connect");
+    ws
+}
+
+/// Places a resting bid for `key`'s account and returns its order ID.
+async fn place_bid(client: &reqwest::Client, http_base: &str, key: &ApiCredentials) -> String {
+    let order = json!({
+        "side": "Buy", "order_type": "Limit", "price": 40, "quantity": 1, "symbol": "BTC-USD"
+    });
+    let r = key
+        .request(client, reqwest::Method::POST, http_base, "/orders", &order)
+        .send()
+        .await
+        .unwrap();
+    assert!(r.status().is_success());
+    let ack: serde_json::Value = r.json().await.unwrap();
+    ack["order_id"].as_str().unwrap().to_string()
+}
+
+/// Makes the session on `ws` the owner of `order_id` and waits for it to be taken.
+async fn own(ws: &mut WsStream, order_id: &str) {
+    let own = json!({ "type": "Own", "data": { "pair": "BTC-USD", "order_ids": [order_id] } });
+    ws.send(Message::Text(own.to_string().into()))
+        .await
+        .unwrap();
+    loop {
+        let msg = tokio::time::timeout(Duration::from_secs(2), ws.next())
+            .await
+            .expect("ws recv timeout")
+            .expect("ws closed")
+            .expect("ws error");
+        if let Message::Text(text) = msg
+            && let WsFrame::Owned(owned) = serde_json::from_str(&text).expect("parse WsFrame")
+        {
+            assert_eq!(owned.order_ids, vec![order_id.parse::<u128>().unwrap()]);
+            return;
+        }
+    }
+}
+
+#[tokio::test]
+async fn account_websocket_cancels_orders_on_disconnect() {
+    let (http_base, server, _tmpdir) = spawn_server_with_heartbeat(Duration::from_millis(50)).await;
+    let client = reqwest::Client::new();
+    let alice = open_account(&client, &http_base, "alice").await;
+
+    // A session that did not opt in leaves its orders alone when it closes
+    let mut ws = connect_account(&http_base, &alice, "/ws/account").await;
+    let earlier = place_bid(&client, &http_base, &alice).await;
+    own(&mut ws, &earlier).await;
+    ws.close(None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    wait_for_status(&client, &http_base, &alice, &earlier, "New").await;
+
+    // Closing the socket
+    let mut ws = connect_account(&http_base, &alice, "/ws/account?cancel_on_disconnect=true").await;
+    let order_id = place_bid(&client, &http_base, &alice).await;
+    own(&mut ws, &order_id).await;
+    ws.close(None).await.unwrap();
+    wait_for_status(&client, &http_base, &alice, &order_id, "Cancelled").await;
+    // Orders the session never owned stay
+    wait_for_status(&client, &http_base, &alice, &earlier, "New").await;
+
+    // Never answering the pings
+    let mut ws = connect_account(&http_base, &alice, "/ws/account?cancel_on_disconnect=true").await;
+    let order_id = place_bid(&client, &http_base, &alice).await;
+    own(&mut ws, &order_id).await;
+    wait_for_status(&client, &http_base, &alice, &order_id, "Cancelled").await;
+
+    server.abort();
+}
+
+#[tokio::test]
+async fn account_websocket_sessions_cancel_only_their_own_orders() {
+    let (http_base, server, _tmpdir) = spawn_server().await;
+    let client = reqwest::Client::new();
+    let alice = open_account(&client, &http_base, "alice").await;
+    let bob = open_account(&client, &http_base, "bob").await;
+    let path = "/ws/account?cancel_on_disconnect=true";
+
+    // Two quoting processes on one account
+    let mut first = connect_account(&http_base, &alice, path).await;
+    let mut second = connect_account(&http_base, &alice, path).await;
+    let first_order = place_bid(&client, &http_base, &alice).await;
+    own(&mut first, &first_order).await;
+    let second_order = place_bid(&client, &http_base, &alice).await;
+    own(&mut second, &second_order).await;
+    let rest_order = place_bid(&client, &http_base, &alice).await;
+
+    // Other accounts' orders cannot be taken
+    let bobs_order = place_bid(&client, &http_base, &bob).await;
+    let own_bobs =
+        json!({ "type": "Own", "data": { "pair": "BTC-USD", "order_ids": [bobs_order] } });
+    first
+        .send(Message::Text(own_bobs.to_string().into()))
+        .await
+        .unwrap();
+    let owned = loop {
+        let msg = tokio::time::timeout(Duration::from_secs(2), first.next())
+            .await
+            .expect("ws recv timeout")
+            .expect("ws closed")
+            .expect("ws error");
+        if let Message::Text(text) = msg
+            && let WsFrame::Owned(owned) = serde_json::from_str(&text).expect("parse WsFrame")
+        {
+            break owned;
+        }
+    };
+    assert!(owned.order_ids.is_empty());
+
+    first.close(None).await.unwrap();
+    wait_for_status(&client, &http_base, &alice, &first_order, "Cancelled").await;
+    for order_id in [&second_order, &rest_order] {
+        wait_for_status(&client, &http_base, &alice, order_id, "New").await;
+    }
+    wait_for_status(&client, &http_base, &bob, &bobs_order, "New").await;
+
+    second.close(None).await.unwrap();
+    wait_for_status(&client, &http_base, &alice, &second_order, "Cancelled").await;
+    wait_for_status(&client, &http_base, &alice, &rest_order, "New").await;
+    wait_for_status(&client, &http_base, &bob, &bobs_order, "New").await;
+
+    server.abort();
+}